@@ -13,19 +13,21 @@
 //! The benchmarking entrypoint is [`benchmark_file`].
 
 use std::time::{Duration, Instant};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::path::Path;
 use std::collections::HashMap;
 
 use aho_corasick::{AhoCorasick, AhoCorasickBuilder, AhoCorasickKind};
-use bio::pattern_matching::myers::{Myers, MyersBuilder};
+use bio::pattern_matching::myers::Myers;
 use edlib_rs::edlibrs::{
     edlibAlign, edlibDefaultAlignConfig, edlibFreeAlignResult,
     EdlibAlignConfig, EdlibAlignMode_EDLIB_MODE_HW, EdlibAlignTask_EDLIB_TASK_LOC,
 };
 
-use crate::kit::{SequenceRecord, SeqKind, Kit};
+use crate::iupac;
+use crate::kit::{SequenceRecord, SeqKind, Kit, LayoutSlot};
+use crate::readmeta::SequencingSummary;
 use crate::seqio;
 
 /// Algorithms available to the benchmark.
@@ -94,20 +96,7 @@ pub struct LabelHit {
 /// We keep this lightweight; only AC is immutable and free to share
 /// across threads. We build Myers per-candidate to avoid interior mutability.
 fn revcomp_bytes(seq: &[u8]) -> Vec<u8> {
-    fn comp(b: u8) -> u8 {
-        match b {
-            b'A'|b'a' => b'T',
-            b'C'|b'c' => b'G',
-            b'G'|b'g' => b'C',
-            b'T'|b't' => b'A',
-            b'U'|b'u' => b'A',
-            b'N'|b'n' => b'N',
-            _ => b'N',
-        }
-    }
-    let mut out = Vec::with_capacity(seq.len());
-    for &b in seq.iter().rev() { out.push(comp(b)); }
-    out
+    iupac::revcomp(seq)
 }
 
 /// struct `Prebuilt` — auto‑generated rustdoc.
@@ -126,9 +115,12 @@ pub fn prebuild_for(records: &[SequenceRecord]) -> Prebuilt {
     let mut pat_is_rc: Vec<bool> = Vec::new();
     let owned: Arc<Vec<SequenceRecord>> = Arc::new(records.to_vec());
     for (i, r) in owned.iter().enumerate() {
-        let fwd = r.sequence.as_bytes().to_vec();
+        // Index the longest wobble-free seed; Myers verifies the full motif.
+        let motif = iupac::normalize_motif(r.sequence.as_bytes());
+        let fwd = iupac::concrete_seed(&motif).to_vec();
         pats.push(fwd); pat2rec.push(i); pat_is_rc.push(false);
-        let rc = revcomp_bytes(r.sequence.as_bytes());
+        let rc_motif = revcomp_bytes(&motif);
+        let rc = iupac::concrete_seed(&rc_motif).to_vec();
         pats.push(rc); pat2rec.push(i); pat_is_rc.push(true);
     }
     let pat_refs: Vec<&[u8]> = pats.iter().map(|v| v.as_slice()).collect();
@@ -162,16 +154,17 @@ pub fn classify_best(
 fn myers_best(seq: &[u8], records: &[SequenceRecord], max_dist: usize) -> Option<LabelHit> {
     let mut best: Option<LabelHit> = None;
     for r in records {
+        let motif = iupac::normalize_motif(r.sequence.as_bytes());
         // forward
-        let mut m: Myers<u64> = MyersBuilder::new().build_64(r.sequence.as_bytes().iter().copied());
+        let mut m: Myers<u64> = iupac::myers_builder().build_64(motif.iter().copied());
         if let Some((_, end, dist)) = m.find_all(seq, max_dist as u8).next() {
             let score = -(dist as i32);
             let hit = LabelHit { name: r.name.to_string(), kind: r.kind, score, pos: Some(end) };
             if best.as_ref().map(|b| hit.score > b.score).unwrap_or(true) { best = Some(hit); }
         } else {
             // reverse-complement of reference
-            let rc = revcomp_bytes(r.sequence.as_bytes());
-            let mut mrc: Myers<u64> = MyersBuilder::new().build_64(rc.into_iter());
+            let rc = revcomp_bytes(&motif);
+            let mut mrc: Myers<u64> = iupac::myers_builder().build_64(rc);
             if let Some((_, end, dist)) = mrc.find_all(seq, max_dist as u8).next() {
                 let score = -(dist as i32);
                 let hit = LabelHit { name: r.name.to_string(), kind: r.kind, score, pos: Some(end) };
@@ -191,10 +184,11 @@ fn ac_myers_best(seq: &[u8], pre: &Prebuilt, max_dist: usize) -> Option<LabelHit
         if !seen.insert(pid) { continue; }
         let ridx = pre.pat2rec[pid];
         let r = &&pre.records[ridx];
+        let motif = iupac::normalize_motif(r.sequence.as_bytes());
         let pat_bytes: Vec<u8> = if pre.pat_is_rc[pid] {
-            revcomp_bytes(r.sequence.as_bytes())
-        } else { r.sequence.as_bytes().to_vec() };
-        let mut my: Myers<u64> = MyersBuilder::new().build_64(pat_bytes.into_iter());
+            revcomp_bytes(&motif)
+        } else { motif.into_owned() };
+        let mut my: Myers<u64> = iupac::myers_builder().build_64(pat_bytes);
         if let Some((_, end, dist)) = my.find_all(seq, max_dist as u8).next() {
             let score = -(dist as i32);
             let hit = LabelHit { name: r.name.to_string(), kind: r.kind, score, pos: Some(end) };
@@ -208,21 +202,25 @@ fn ac_myers_best(seq: &[u8], pre: &Prebuilt, max_dist: usize) -> Option<LabelHit
 /// Edlib distance (C FFI; distance-only, semiglobal).
 fn edlib_best(seq: &[u8], records: &[SequenceRecord], max_dist: usize) -> Option<LabelHit> {
     let mut best: Option<LabelHit> = None;
+    let eq = iupac::edlib_equalities();
     for r in records {
         let mut cfg: EdlibAlignConfig = unsafe { edlibDefaultAlignConfig() };
         cfg.mode = EdlibAlignMode_EDLIB_MODE_HW; // semiglobal (end-free)
         cfg.task = EdlibAlignTask_EDLIB_TASK_LOC;
         cfg.k = max_dist as i32;
+        cfg.additionalEqualities = eq.as_ptr();
+        cfg.additionalEqualitiesLength = eq.len() as i32;
 
         // forward
-        let q = r.sequence.as_bytes();
+        let motif = iupac::normalize_motif(r.sequence.as_bytes());
+        let q: &[u8] = &motif;
         let res = unsafe { edlibAlign(q.as_ptr() as *const i8, q.len() as i32, seq.as_ptr() as *const i8, seq.len() as i32, cfg) };
         let mut best_local: Option<(i32, Option<i32>)> = None;
         if res.editDistance >= 0 { best_local = Some((res.editDistance, None)); }
         unsafe { edlibFreeAlignResult(res) };
 
         // reverse-complement of reference
-        let rc = revcomp_bytes(r.sequence.as_bytes());
+        let rc = revcomp_bytes(&motif);
         let res2 = unsafe { edlibAlign(rc.as_ptr() as *const i8, rc.len() as i32, seq.as_ptr() as *const i8, seq.len() as i32, cfg) };
         if res2.editDistance >= 0 {
             if let Some((d,_)) = best_local {
@@ -234,7 +232,7 @@ fn edlib_best(seq: &[u8], records: &[SequenceRecord], max_dist: usize) -> Option
         unsafe { edlibFreeAlignResult(res2) };
 
         if let Some((d,_)) = best_local {
-            let score = -(d as i32);
+            let score = -d;
             let hit = LabelHit { name: r.name.to_string(), kind: r.kind, score, pos: None };
             if best.as_ref().map(|b| hit.score > b.score).unwrap_or(true) { best = Some(hit); }
         }
@@ -248,6 +246,24 @@ fn parasail_best(_seq: &[u8], _records: &[SequenceRecord]) -> Option<LabelHit> {
     None
 }
 
+/// Records a read can be labelled with: the adapter the kit's read layout places (its top
+/// strand), or for layouts without an adapter slot the placed primers and adapters; barcode
+/// flanks only for kits that list nothing else. One label per layout keeps reads that carry
+/// several of these motifs from having more than one right answer.
+/// [`benchmark_file`] scores against this set, and `simulate` writes its truth labels from it.
+pub fn label_records(kit: &Kit) -> Vec<&'static SequenceRecord> {
+    let records = kit.adapters_and_primers;
+    let adapter = records.iter().find(|r| r.kind == SeqKind::AdapterTop)
+        .or_else(|| records.iter().find(|r| r.kind == SeqKind::AdapterBottom));
+    if let Some(a) = adapter.filter(|_| kit.layout.elements().iter().any(|e| e.slot == LayoutSlot::Adapter)) {
+        return vec![a];
+    }
+    let labels: Vec<&'static SequenceRecord> = records.iter()
+        .filter(|r| r.kind != SeqKind::Flank && kit.layout.elements().iter().any(|e| e.slot.accepts(r.name, r.kind)))
+        .collect();
+    if labels.is_empty() { records.iter().collect() } else { labels }
+}
+
 /// Load a simple truth map (read_id -> expected_label). Supports CSV or TSV.
 /// fn `load_truth` — auto‑generated rustdoc.
 pub fn load_truth<P: AsRef<Path>>(path: P) -> anyhow::Result<HashMap<String, String>> {
@@ -264,18 +280,49 @@ pub fn load_truth<P: AsRef<Path>>(path: P) -> anyhow::Result<HashMap<String, Str
     Ok(map)
 }
 
-/// Benchmark a single file.
-///
-/// Returns:
-/// `(tp, fp, fn, elapsed, nseq, cpu_util (placeholder), input_format)`
+/// Reads of a benchmark run found in a sequencing summary, with MinKNOW's mean qscore
+/// for the reads that got a label and for those that did not.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SummaryStats {
+    pub joined: u64,
+    pub qscore_labelled: Option<f64>,
+    pub qscore_unlabelled: Option<f64>,
+}
+
+/// `(tp, fp, fn, elapsed, nseq, cpu_util (placeholder), input_format, summary_stats)`;
+/// `summary_stats` is all zero/None without a `summary`.
+pub type BenchmarkResult = (u64, u64, u64, Duration, usize, f32, seqio::InputFormat, SummaryStats);
+
+/// Benchmark a single file; see [`BenchmarkResult`]. Only reads kept by `sampling` are
+/// classified and counted in `nseq`.
+#[allow(clippy::too_many_arguments)]
 pub fn benchmark_file<P: AsRef<Path>>(
     path: P,
     kit: &Kit,
     algo: BenchmarkAlgo,
     truth: Option<HashMap<String, String>>,
+    summary: Option<Arc<SequencingSummary>>,
+    sampling: seqio::Sampling,
     threads: Option<usize>,
     max_dist: usize,
-) -> anyhow::Result<(u64, u64, u64, Duration, usize, f32, seqio::InputFormat)> {
+) -> anyhow::Result<BenchmarkResult> {
+    let input = seqio::InputFile { path: path.as_ref().to_path_buf(), source: None, secondary: false };
+    benchmark_inputs(std::slice::from_ref(&input), kit, algo, truth, summary, sampling, threads, max_dist)
+}
+
+/// Benchmark expanded inputs read in order as one stream; each input's `secondary` setting
+/// applies to it. The input format in the result is the first input's.
+#[allow(clippy::too_many_arguments)]
+pub fn benchmark_inputs(
+    inputs: &[seqio::InputFile],
+    kit: &Kit,
+    algo: BenchmarkAlgo,
+    truth: Option<HashMap<String, String>>,
+    summary: Option<Arc<SequencingSummary>>,
+    sampling: seqio::Sampling,
+    threads: Option<usize>,
+    max_dist: usize,
+) -> anyhow::Result<BenchmarkResult> {
     let start = Instant::now();
 
     // Atomic counters to be shared by worker threads.
@@ -287,9 +334,12 @@ pub fn benchmark_file<P: AsRef<Path>>(
     // Owned truth map moved into closure (if any).
     let truth_owned = truth;
 
+    // Own a copy of the static records so the closure can capture without borrowing `kit`.
+    let records_arc: Arc<Vec<SequenceRecord>> = Arc::new(label_records(kit).into_iter().cloned().collect());
+
     // Prebuild AC for ACMyers (immutable, thread-safe).
     let pre: Option<Prebuilt> = match algo {
-        BenchmarkAlgo::ACMyers => Some(prebuild_for(&kit.adapters_and_primers)),
+        BenchmarkAlgo::ACMyers => Some(prebuild_for(&records_arc)),
         _ => None,
     };
 
@@ -297,25 +347,40 @@ pub fn benchmark_file<P: AsRef<Path>>(
     let fp_c = fp.clone();
     let fn_c = fn_.clone();
     let nseq_c = nseq.clone();
+    // (qscore sum, reads) for labelled and unlabelled reads found in the summary
+    let qscores = Arc::new(Mutex::new([(0.0f64, 0u64); 2]));
+    let joined = Arc::new(AtomicU64::new(0));
+    let qscores_c = qscores.clone();
+    let joined_c = joined.clone();
 
-        // Own a copy of the static records so the closure can capture without borrowing `kit`.
-    let records_arc: Arc<Vec<SequenceRecord>> = Arc::new(kit.adapters_and_primers.to_vec());
-let fmt_n = seqio::for_each_parallel(path.as_ref(), threads, move |rec: seqio::NARead| {
+let fmt_n = seqio::for_each_inputs(inputs, threads, sampling, move |mut rec: seqio::NARead| {
         nseq_c.fetch_add(1, Ordering::Relaxed);
+        if let Some(s) = &summary { s.annotate(&mut rec); }
 
         let records = records_arc.as_slice();
+        let seq = iupac::normalize_read(&rec.seq);
         let label = match algo {
-            BenchmarkAlgo::Myers => myers_best(&rec.seq, records, max_dist),
+            BenchmarkAlgo::Myers => myers_best(&seq, records, max_dist),
             BenchmarkAlgo::ACMyers => {
                 // Rebuild a minimal pre each call (safe if `pre` is None),
                 // otherwise use the computed AC.
                 let local_pre = if let Some(ref pr) = pre { Some(pr) } else { None };
-                if let Some(pr) = local_pre { ac_myers_best(&rec.seq, pr, max_dist) } else { myers_best(&rec.seq, records, max_dist) }
+                if let Some(pr) = local_pre { ac_myers_best(&seq, pr, max_dist) } else { myers_best(&seq, records, max_dist) }
             }
-            BenchmarkAlgo::Edlib => edlib_best(&rec.seq, records, max_dist),
-            BenchmarkAlgo::Parasail => parasail_best(&rec.seq, records),
+            BenchmarkAlgo::Edlib => edlib_best(&seq, records, max_dist),
+            BenchmarkAlgo::Parasail => parasail_best(&seq, records),
         };
 
+        if let Some(row) = &rec.meta.summary {
+            joined_c.fetch_add(1, Ordering::Relaxed);
+            if let Some(q) = row.mean_qscore {
+                let mut g = qscores_c.lock().unwrap();
+                let slot = &mut g[if label.is_some() { 0 } else { 1 }];
+                slot.0 += q;
+                slot.1 += 1;
+            }
+        }
+
         if let Some(ref tmap) = truth_owned {
             let id = rec.id.as_str();
             let expected = tmap.get(id);
@@ -342,7 +407,15 @@ let fmt_n = seqio::for_each_parallel(path.as_ref(), threads, move |rec: seqio::N
     // Portable placeholder for CPU util (can wire sysinfo back if desired)
     let cpu_util = 0.0_f32;
 
-    Ok((tp_v, fp_v, fn_v, elapsed, nseq_v, cpu_util, fmt_n.0))
+    let q = *qscores.lock().unwrap();
+    let mean = |(sum, n): (f64, u64)| if n > 0 { Some(sum / n as f64) } else { None };
+    let stats = SummaryStats {
+        joined: joined.load(Ordering::Relaxed),
+        qscore_labelled: mean(q[0]),
+        qscore_unlabelled: mean(q[1]),
+    };
+
+    Ok((tp_v, fp_v, fn_v, elapsed, nseq_v, cpu_util, fmt_n.0, stats))
 }    // Own a copy of the static records so the closure can capture without borrowing `kit`.
 
 impl std::fmt::Display for BenchmarkAlgo {
@@ -371,12 +444,13 @@ pub fn classify_all(
                     let ridx = pre.pat2rec[pid];
                     let is_rc = pre.pat_is_rc[pid];
                     let r = &pre.records[ridx];
+                    let motif = iupac::normalize_motif(r.sequence.as_bytes());
                     let pat_bytes: Vec<u8> = if is_rc {
-                        revcomp_bytes(r.sequence.as_bytes())
+                        revcomp_bytes(&motif)
                     } else {
-                        r.sequence.as_bytes().to_vec()
+                        motif.into_owned()
                     };
-                    let mut my: Myers<u64> = MyersBuilder::new().build_64(pat_bytes.into_iter());
+                    let mut my: Myers<u64> = iupac::myers_builder().build_64(pat_bytes);
                     if let Some((s, _e, dist)) = my.find_all(seq, max_dist as u8).next() { let _ = dist; out.push((r.name.to_string(), r.kind, is_rc, s));
                     }
                 }
@@ -384,28 +458,33 @@ pub fn classify_all(
         }
         BenchmarkAlgo::Myers => {
             for r in records {
+                let motif = iupac::normalize_motif(r.sequence.as_bytes());
                 // forward
-                let mut m: Myers<u64> = MyersBuilder::new().build_64(r.sequence.as_bytes().iter().copied());
+                let mut m: Myers<u64> = iupac::myers_builder().build_64(motif.iter().copied());
                 if let Some((s,_e,dist)) = m.find_all(seq, max_dist as u8).next() { let _ = dist; out.push((r.name.to_string(), r.kind, false, s));
                     continue;
                 }
                 // reverse-complement motif
-                let rc = revcomp_bytes(r.sequence.as_bytes());
-                let mut mrc: Myers<u64> = MyersBuilder::new().build_64(rc.into_iter());
+                let rc = revcomp_bytes(&motif);
+                let mut mrc: Myers<u64> = iupac::myers_builder().build_64(rc);
                 if let Some((s,_e,dist)) = mrc.find_all(seq, max_dist as u8).next() { let _ = dist; out.push((r.name.to_string(), r.kind, true, s));
                 }
             }
         }
         BenchmarkAlgo::Edlib => {
+            let eq = iupac::edlib_equalities();
             unsafe {
                 for r in records {
                     let mut cfg: EdlibAlignConfig = edlibDefaultAlignConfig();
                     cfg.mode = EdlibAlignMode_EDLIB_MODE_HW; // semiglobal (end-free)
                     cfg.task = EdlibAlignTask_EDLIB_TASK_LOC;
                     cfg.k = max_dist as i32;
+                    cfg.additionalEqualities = eq.as_ptr();
+                    cfg.additionalEqualitiesLength = eq.len() as i32;
 
                     // forward
-                    let q = r.sequence.as_bytes();
+                    let motif = iupac::normalize_motif(r.sequence.as_bytes());
+                    let q: &[u8] = &motif;
                     let res = edlibAlign(q.as_ptr() as *const i8, q.len() as i32, seq.as_ptr() as *const i8, seq.len() as i32, cfg);
                     let mut matched = false;
                     if res.editDistance >= 0 && res.numLocations > 0 {
@@ -416,7 +495,7 @@ pub fn classify_all(
                     edlibFreeAlignResult(res);
 
                     if !matched {
-                        let rc = revcomp_bytes(r.sequence.as_bytes());
+                        let rc = revcomp_bytes(&motif);
                         let res2 = edlibAlign(rc.as_ptr() as *const i8, rc.len() as i32, seq.as_ptr() as *const i8, seq.len() as i32, cfg);
                         if res2.editDistance >= 0 && res2.numLocations > 0 {
                             let spos = *res2.startLocations as usize;
@@ -430,12 +509,13 @@ pub fn classify_all(
         BenchmarkAlgo::Parasail => {
             // Fallback: behave like Myers (forward + RC motifs), do not RC the read
             for r in records {
-                let mut m: Myers<u64> = MyersBuilder::new().build_64(r.sequence.as_bytes().iter().copied());
+                let motif = iupac::normalize_motif(r.sequence.as_bytes());
+                let mut m: Myers<u64> = iupac::myers_builder().build_64(motif.iter().copied());
                 if let Some((s,_e,dist)) = m.find_all(seq, max_dist as u8).next() { let _ = dist; out.push((r.name.to_string(), r.kind, false, s));
                     continue;
                 }
-                let rc = revcomp_bytes(r.sequence.as_bytes());
-                let mut mrc: Myers<u64> = MyersBuilder::new().build_64(rc.into_iter());
+                let rc = revcomp_bytes(&motif);
+                let mut mrc: Myers<u64> = iupac::myers_builder().build_64(rc);
                 if let Some((s,_e,dist)) = mrc.find_all(seq, max_dist as u8).next() { let _ = dist; out.push((r.name.to_string(), r.kind, true, s));
                 }
             }
@@ -1,5 +1,5 @@
 //! Module `porkchop` — auto‑generated docs for porkchop.
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use polars::prelude::*;
 
 /// Porkchop CLI
@@ -8,12 +8,68 @@ use polars::prelude::*;
 #[command(version)]
 #[command(about = "ONP kit registry, IO and benchmarking", long_about = None)]
 struct Cli {
+    /// Extra kit registry file(s) (TOML/JSON) merged with the built-in kits
+    #[arg(long, global = true, value_name = "FILE")]
+    registry: Vec<std::path::PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
 enum OutputFormat { Csv, Md, Table }
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum ReportFormat { Json, Text }
+
+/// Input options shared by `clean`, `screen` and `benchmark`.
+#[derive(Args, Clone, Debug)]
+struct InputArgs {
+    /// Glob a file inside a directory input must match, on its name or path below the directory
+    /// (repeatable; default: FASTQ/FASTA/SAM/BAM names in any compression)
+    #[arg(long = "include", value_name = "GLOB")]
+    include: Vec<String>,
+    /// Glob that skips a file inside a directory input (repeatable), e.g. '*fastq_fail*'
+    #[arg(long = "exclude", value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Record each read's source folder (e.g. barcode07); clean and screen only
+    #[arg(long = "by-source")]
+    by_source: bool,
+    /// Keep secondary and supplementary alignments of aligned SAM/BAM input (skipped by default)
+    #[arg(long = "secondary")]
+    secondary: bool,
+    /// MinKNOW/dorado sequencing_summary.txt to join onto reads by read id
+    #[arg(long = "summary", value_name = "FILE")]
+    summary: Option<std::path::PathBuf>,
+    /// Subsample reads across all inputs: all | fraction:P | reservoir:N | head:N | bases:N (k/M/G suffixes allowed)
+    #[arg(long = "sample", value_name = "MODE")]
+    sample: Option<porkchop::seqio::SampleMode>,
+    /// Seed for fraction and reservoir sampling; the same seed gives the same reads
+    #[arg(long = "seed", default_value_t = 1)]
+    seed: u64,
+}
+
+impl InputArgs {
+    fn expand<P: AsRef<std::path::Path>>(&self, files: &[P]) -> anyhow::Result<Vec<porkchop::seqio::InputFile>> {
+        let opts = porkchop::seqio::InputOptions {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            by_source: self.by_source,
+            secondary: self.secondary,
+        };
+        porkchop::seqio::expand_inputs(files, &opts)
+    }
+
+    fn load_summary(&self) -> anyhow::Result<Option<std::sync::Arc<porkchop::readmeta::SequencingSummary>>> {
+        match &self.summary {
+            Some(p) => Ok(Some(std::sync::Arc::new(porkchop::readmeta::SequencingSummary::load(p)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// `--sample` with `--seed`, or `default` when no mode was given.
+    fn sampling(&self, default: porkchop::seqio::SampleMode) -> porkchop::seqio::Sampling {
+        porkchop::seqio::Sampling { mode: self.sample.unwrap_or(default), seed: self.seed }
+    }
+}
 #[derive(Subcommand)]
 enum Commands {
 
@@ -27,22 +83,31 @@ enum Commands {
         gz_threads: usize,
         /// Number of FASTQ records per analysis chunk
         #[arg(long = "chunk-size", default_value_t = 500)]
-        chunk_size: usize,/// Kit id (must match a known ONT kit, e.g. "LSK114")
+        chunk_size: usize,/// Kit id or expression (e.g. "LSK114" or "LSK114+NBD114.24")
         #[arg(short, long)]
         kit: String,
                 /// Allowed edit distance (global)
         #[arg(long, default_value_t = 2)]
         edits: i32,
-/// Output FASTQ.GZ path
+/// Output path; the format follows the extension (.fastq[.gz], .fasta[.gz], .sam, .bam; default BGZF FASTQ)
         #[arg(short, long, value_name = "OUT.fastq.gz")]
         output: std::path::PathBuf,
-        /// One or more input files (SAM, BAM, FASTQ, FASTQ.GZ)
+        /// Output format, overriding the extension: fastq | fastq.gz | fasta | fasta.gz | sam | bam
+        #[arg(long = "output-format", value_name = "FORMAT")]
+        output_format: Option<porkchop::seqio::OutputFormat>,
+        /// Reads without qualities (FASTA input): error | fasta (write FASTA output) | <PHRED> (constant score)
+        #[arg(long = "missing-qual", default_value = "error", value_name = "MODE")]
+        missing_qual: porkchop::clean::MissingQual,
+        /// One or more input files or directories (SAM, BAM, FASTQ, FASTA; plain or compressed; `-` for stdin)
 
 /// Max bars/bins per histogram in the TUI (1–100; default 15)
         #[arg(long = "tui-max-bins", default_value_t = 20, value_name = "BINS", value_parser = clap::value_parser!(usize))]
         tui_max_bins: usize,
         #[arg(value_name = "FILES", num_args = 1..)]
         files: Vec<std::path::PathBuf>,
+        // --by-source appends `source=<folder>` to each output header
+        #[command(flatten)]
+        inputs: InputArgs,
     },
 
     /// List all supported kits
@@ -60,17 +125,103 @@ enum Commands {
 
     /// Describe a kit by id (e.g., "LSK114")
     Describe {
-        /// Kit id to describe
+        /// Kit id or expression to describe (e.g., "LSK114+NBD114.24")
         id: String,
+        /// Output format: text | json | yaml | md | fasta
+        #[arg(long, default_value = "text")]
+        format: porkchop::describe::DescribeFormat,
+    },
+
+    /// Check the kit registry (built-in plus --registry files) for integrity problems
+    ValidateRegistry {
+        /// Report format: json | text
+        #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+        format: ReportFormat,
+        /// Treat warnings as errors for the exit status
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// Export a kit for another tool (fasta | cutadapt | porechop-py | dorado-toml)
+    Export {
+        /// Kit id or expression to export (e.g., "NBD114.24")
+        #[arg(short, long)]
+        kit: String,
+        /// Output format: fasta | cutadapt | porechop-py | dorado-toml
+        #[arg(long, default_value = "fasta")]
+        format: porkchop::export::ExportFormat,
+        /// Output path (stdout if omitted; required for dorado-toml, which also writes <OUT>.fasta)
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Find registry records (and their kits) that contain or match a sequence
+    Whois {
+        /// Query sequence (IUPAC codes allowed)
+        sequence: String,
+        /// Maximum edit distance, in either orientation
+        #[arg(long, default_value_t = 2)]
+        max_dist: usize,
+        /// Report format: json | text
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+
+    /// Simulate reads for a kit, with a truth CSV for `benchmark --truth`
+    Simulate {
+        /// Kit id or expression (e.g., "RBK114.24")
+        #[arg(short, long)]
+        kit: String,
+        /// Number of reads
+        #[arg(short, long, default_value_t = 1000)]
+        n: usize,
+        /// FASTQ output (gzip if it ends in .gz)
+        #[arg(short, long, default_value = "simulated.fastq.gz")]
+        output: std::path::PathBuf,
+        /// Truth CSV (default: <OUT>.truth.csv next to the FASTQ)
+        #[arg(long)]
+        truth: Option<std::path::PathBuf>,
+        /// FASTA/FASTQ of inserts to sample from (default: random sequence)
+        #[arg(long)]
+        inserts: Option<std::path::PathBuf>,
+        /// Minimum random insert length
+        #[arg(long, default_value_t = 500)]
+        insert_min: usize,
+        /// Maximum random insert length
+        #[arg(long, default_value_t = 5000)]
+        insert_max: usize,
+        /// Substitution rate per base
+        #[arg(long, default_value_t = 0.01)]
+        sub_rate: f64,
+        /// Insertion rate per base
+        #[arg(long, default_value_t = 0.005)]
+        ins_rate: f64,
+        /// Deletion rate per base
+        #[arg(long, default_value_t = 0.01)]
+        del_rate: f64,
+        /// Probability of a homopolymer run being called one base off
+        #[arg(long, default_value_t = 0.05)]
+        homopolymer_rate: f64,
+        /// Fraction of reads with a truncated end adapter
+        #[arg(long, default_value_t = 0.05)]
+        truncated_rate: f64,
+        /// Fraction of chimeric reads (two molecules)
+        #[arg(long, default_value_t = 0.01)]
+        chimera_rate: f64,
+        /// Random seed; the same seed gives the same reads
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
     },
 
     /// Benchmark classification algorithms against an optional truth set
     Benchmark {
-        /// Input files (FASTQ/FASTA/FASTQ.GZ/SAM/BAM)
+        /// Input files or directories (FASTQ/FASTA/FASTQ.GZ/SAM/BAM; `-` for stdin)
         #[arg(required = true)]
         #[arg(value_name = "FILES", required = true)]
         files: Vec<String>,
-        /// Kit id (e.g., "LSK114")
+        #[command(flatten)]
+        inputs: InputArgs,
+        /// Kit id or expression (e.g., "LSK114", "LSK114+NBD114.24")
         kit: String,
         /// Truth CSV (optional)
         #[arg(long)]
@@ -91,10 +242,13 @@ enum Commands {
 
     /// Screen a dataset to infer library chemistry by scoring adapters/primers/barcodes
     Screen {
-        /// Input files (FASTQ/FASTQ.GZ/SAM/BAM)
+        /// Input files or directories (FASTQ/FASTA/FASTQ.GZ/SAM/BAM; `-` for stdin)
         #[arg(required = true)]
         #[arg(value_name = "FILES", required = true)]
         files: Vec<String>,
+        // --by-source adds a per-folder breakdown to the JSON and HTML reports
+        #[command(flatten)]
+        inputs: InputArgs,
         /// Algorithm (default: edlib)
         #[arg(long, default_value = "edlib")]
         algorithm: String,
@@ -102,7 +256,7 @@ enum Commands {
         #[arg(long, default_value_t = 24)]
         #[arg(long, default_value_t = 2)]
         max_dist: usize,
-        /// Fraction of reads to sample (0.0-1.0; default 0.05)
+        /// Fraction of reads to sample (0.0-1.0; default 0.05); `--sample` takes precedence
         #[arg(long, default_value_t = 0.05)]
         fraction: f64,
         /// UI refresh in seconds (default: 2)
@@ -126,19 +280,80 @@ enum Commands {
 fn main() -> polars::prelude::PolarsResult<()> {
     let cli = Cli::parse();
 
+    // kept unmerged for validate-registry, which checks for repeated ids
+    let mut loaded = Vec::new();
+    for path in &cli.registry {
+        match porkchop::kits::load_registry(path) {
+            Ok(kits) => loaded.extend(kits),
+            Err(e) => {
+                eprintln!("registry error: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if !loaded.is_empty() { porkchop::kits::install_registry(loaded.clone()); }
+
     match cli.command {
-            Commands::Clean { threads, gz_threads, chunk_size, kit, edits, tui_max_bins, output, files } => { if let Err(e) = porkchop::clean::run(threads, gz_threads, chunk_size, &kit, edits, tui_max_bins, &output, files) {
+            Commands::Clean { threads, gz_threads, chunk_size, kit, edits, tui_max_bins, missing_qual, output, output_format, files, inputs } => { if let Err(e) = inputs.expand(&files).and_then(|files| {
+                let summary = inputs.load_summary()?;
+                let sampling = inputs.sampling(porkchop::seqio::SampleMode::All);
+                porkchop::clean::run(threads, gz_threads, chunk_size, &kit, edits, tui_max_bins, missing_qual, &output, output_format, files, summary.as_deref(), sampling)
+            }) {
             eprintln!("clean error: {:?}", e);
             std::process::exit(1);
         }
     },
 Commands::ListKits { format, full, truncate } => { cmd_list_kits(format, full, truncate); }
 
-        Commands::Describe { id } => {
-            cmd_describe(id);
+        Commands::Describe { id, format } => {
+            cmd_describe(id, format);
+        }
+
+        Commands::ValidateRegistry { format, strict } => {
+            cmd_validate_registry(format, strict, &loaded);
+        }
+
+        Commands::Export { kit, format, output } => {
+            if let Err(e) = cmd_export(&kit, format, output) {
+                eprintln!("export error: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+
+        Commands::Whois { sequence, max_dist, format } => {
+            if let Err(e) = cmd_whois(&sequence, max_dist, format) {
+                eprintln!("whois error: {:?}", e);
+                std::process::exit(1);
+            }
         }
 
-        Commands::Benchmark { files, kit, truth, algorithms, max_dist, threads, csv } => {
+        Commands::Simulate { kit, n, output, truth, inserts, insert_min, insert_max, sub_rate, ins_rate, del_rate,
+                             homopolymer_rate, truncated_rate, chimera_rate, seed } => {
+            use porkchop::simulate::{default_truth_path, run_simulate, ErrorModel, SimulateOpts};
+            let truth = truth.unwrap_or_else(|| default_truth_path(&output));
+            let opts = SimulateOpts {
+                kit,
+                n,
+                output,
+                truth,
+                inserts,
+                insert_len: (insert_min, insert_max),
+                errors: ErrorModel { substitution: sub_rate, insertion: ins_rate, deletion: del_rate, homopolymer: homopolymer_rate },
+                truncation_rate: truncated_rate,
+                chimera_rate,
+                seed,
+            };
+            match run_simulate(&opts) {
+                Ok(st) => eprintln!("simulated {} reads ({} reverse, {} truncated, {} chimeric) -> {} + {}",
+                    st.reads, st.reverse, st.truncated, st.chimeras, opts.output.display(), opts.truth.display()),
+                Err(e) => {
+                    eprintln!("simulate error: {:?}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Benchmark { files, inputs, kit, truth, algorithms, max_dist, threads, csv } => {
             use porkchop::benchmark::{self, BenchmarkAlgo};
 
             let algorithms = algorithms.to_lowercase();
@@ -150,20 +365,37 @@ let truth_map = match truth {
                 None => None,
             };
 
-            for file in files {
-                for algo in &algos {
-                    let kit_ref = match porkchop::get_sequences_for_kit(kit.as_str()) {
-                        Some(k) => k,
-                        None => {
-                            return Err(polars::prelude::PolarsError::ComputeError(
-                                format!("Unknown kit: {}", kit).into(),
-                            ));
-                        }
-                    };
-
-                    let (tp, fp, fn_, dur, nseq, cpu, _input_format) =
-                        benchmark::benchmark_file(file.clone(), kit_ref, *algo, truth_map.clone(), threads, max_dist)
-                        .map_err(|e| polars::prelude::PolarsError::ComputeError(e.to_string().into()))?;
+            if inputs.by_source {
+                return Err(polars::prelude::PolarsError::ComputeError("--by-source is not supported by benchmark".into()));
+            }
+            let files = inputs.expand(&files)
+                .map_err(|e| polars::prelude::PolarsError::ComputeError(e.to_string().into()))?;
+            let summary = inputs.load_summary()
+                .map_err(|e| polars::prelude::PolarsError::ComputeError(format!("{e:#}").into()))?;
+            let sampling = inputs.sampling(porkchop::seqio::SampleMode::All);
+
+            // Files get a row each unless head/bases/reservoir sampling makes all inputs one
+            // stream, as in clean and screen; then each algorithm gets one row for all files.
+            let groups: Vec<Vec<porkchop::seqio::InputFile>> = if sampling.is_per_read() {
+                files.into_iter().map(|f| vec![f]).collect()
+            } else {
+                vec![files]
+            };
+            let kit_ref = porkchop::kits::resolve_kit_expr(kit.as_str())
+                .map_err(|e| polars::prelude::PolarsError::ComputeError(e.to_string().into()))?;
+            for group in groups {
+                let file = group.iter().map(|f| f.path.display().to_string()).collect::<Vec<_>>().join(",");
+                // A stream can be read only once, so it gets a single algorithm.
+                let file_algos = if group.iter().any(|f| porkchop::seqio::is_stream(&f.path)) && algos.len() > 1 {
+                    eprintln!("benchmark: {} is a stream and can be read only once; running {} only", file, algos[0].as_str());
+                    &algos[..1]
+                } else {
+                    &algos[..]
+                };
+                for algo in file_algos {
+                    let (tp, fp, fn_, dur, nseq, cpu, _input_format, stats) =
+                        benchmark::benchmark_inputs(&group, kit_ref, *algo, truth_map.clone(), summary.clone(), sampling, threads, max_dist)
+                        .map_err(|e| polars::prelude::PolarsError::ComputeError(format!("{e:#}").into()))?;
 
                     rows.push((
                         file.clone(),
@@ -175,6 +407,7 @@ let truth_map = match truth {
                         nseq,
                         cpu,
                         threads.unwrap_or(0),
+                        stats,
                     ));
                 }
             }
@@ -183,41 +416,67 @@ let truth_map = match truth {
                 let mut df = df!(
                     "file"        => rows.iter().map(|r| r.0.clone()).collect::<Vec<_>>(),
                     "algorithm"   => rows.iter().map(|r| r.1.clone()).collect::<Vec<_>>(),
-                    "tp"          => rows.iter().map(|r| r.2 as u64).collect::<Vec<_>>(),
-                    "fp"          => rows.iter().map(|r| r.3 as u64).collect::<Vec<_>>(),
-                    "fn"          => rows.iter().map(|r| r.4 as u64).collect::<Vec<_>>(),
+                    "tp"          => rows.iter().map(|r| r.2).collect::<Vec<_>>(),
+                    "fp"          => rows.iter().map(|r| r.3).collect::<Vec<_>>(),
+                    "fn"          => rows.iter().map(|r| r.4).collect::<Vec<_>>(),
                     "elapsed_ms"  => rows.iter().map(|r| r.5 as u64).collect::<Vec<_>>(),
                     "nseq"        => rows.iter().map(|r| r.6 as u64).collect::<Vec<_>>(),
-                    "cpu"         => rows.iter().map(|r| r.7 as f32).collect::<Vec<_>>(),
+                    "cpu"         => rows.iter().map(|r| r.7).collect::<Vec<_>>(),
                     "threads"     => rows.iter().map(|r| r.8 as u64).collect::<Vec<_>>(),
                 )?;
+                if !sampling.is_all() {
+                    df.with_column(Series::new("sampling", vec![sampling.to_string(); rows.len()]))?;
+                }
+                if summary.is_some() {
+                    df.with_column(Series::new("summary_joined", rows.iter().map(|r| r.9.joined).collect::<Vec<_>>()))?;
+                    df.with_column(Series::new("qscore_labelled", rows.iter().map(|r| r.9.qscore_labelled).collect::<Vec<_>>()))?;
+                    df.with_column(Series::new("qscore_unlabelled", rows.iter().map(|r| r.9.qscore_unlabelled).collect::<Vec<_>>()))?;
+                }
                 let w = CsvWriter::new(std::io::stdout());
                 w.include_header(true).finish(&mut df)?;
             } else {
-                for (file, algo, tp, fp, fn_, dur, nseq, cpu, threads) in rows {
-                    println!("{file}\t{algo}\tTP={tp}\tFP={fp}\tFN={fn_}\tms={dur}\tN={nseq}\tcpu={cpu}\tthreads={threads}");
+                let fmt_q = |q: Option<f64>| q.map(|q| format!("{:.2}", q)).unwrap_or_else(|| "-".to_string());
+                for (file, algo, tp, fp, fn_, dur, nseq, cpu, threads, stats) in rows {
+                    let joined = if summary.is_some() {
+                        format!("\tjoined={}\tqs_labelled={}\tqs_unlabelled={}",
+                            stats.joined, fmt_q(stats.qscore_labelled), fmt_q(stats.qscore_unlabelled))
+                    } else {
+                        String::new()
+                    };
+                    let sampled = if sampling.is_all() { String::new() } else { format!("\tsampling={}", sampling) };
+                    println!("{file}\t{algo}\tTP={tp}\tFP={fp}\tFN={fn_}\tms={dur}\tN={nseq}\tcpu={cpu}\tthreads={threads}{sampled}{joined}");
                 }
             }
         }
 
-        Commands::Screen { files, algorithm, max_dist, fraction, tick, threads, json, kit_prob_min, html } => {
+        Commands::Screen { files, inputs, algorithm, max_dist, fraction, tick, threads, json, kit_prob_min, html } => {
             let algo = match algorithm.parse::<porkchop::benchmark::BenchmarkAlgo>() {
                 Ok(a) => a,
                 Err(_) => porkchop::benchmark::BenchmarkAlgo::Edlib,
             };
+            let (files, summary) = match inputs.expand(&files).and_then(|f| Ok((f, inputs.load_summary()?))) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("screen error: {e:#}");
+                    std::process::exit(1);
+                }
+            };
+            let sampling = inputs.sampling(porkchop::seqio::SampleMode::Fraction(fraction.clamp(0.0, 1.0)));
             let opts = porkchop::screen::ScreenOpts {
                 files,
                 threads,
-                fraction,
+                sampling,
                 tick_secs: tick,
                 algo,
                 max_dist,
                 json,
                 kit_prob_min,
                 html,
+                summary,
             };
             if let Err(e) = porkchop::screen::run_screen(opts) {
-                eprintln!("screen error: {e}");
+                eprintln!("screen error: {e:#}");
+                std::process::exit(1);
             }
         }
     }
@@ -301,20 +560,94 @@ fn print_df_markdown(df: &DataFrame) {
 }
 
 
-fn cmd_describe(id: String) {
-    use porkchop::{get_sequences_for_kit, base_chemistry_of, kit_is_legacy};
+fn cmd_describe(id: String, format: porkchop::describe::DescribeFormat) {
+    match porkchop::kits::resolve_kit_expr(id.as_str()) {
+        Ok(kit) => match porkchop::describe::render_description(kit, format) {
+            Ok(text) => print!("{}", text),
+            Err(e) => {
+                eprintln!("describe error: {:?}", e);
+                std::process::exit(1);
+            }
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+fn cmd_validate_registry(format: ReportFormat, strict: bool, loaded: &[porkchop::kit::Kit]) {
+    use porkchop::validate::{validate_loaded, Severity};
+
+    let report = validate_loaded(porkchop::kits::KITS, loaded);
+    match format {
+        ReportFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report).expect("serialise report"));
+        }
+        ReportFormat::Text => {
+            for i in &report.issues {
+                let sev = match i.severity { Severity::Error => "ERROR", Severity::Warning => "WARN" };
+                println!("{}\t{}\t{}\t{}", sev, i.code, i.kit.as_deref().unwrap_or("-"), i.message);
+            }
+            for b in &report.barcode_sets {
+                let pair = b.closest.as_ref().map(|(x, y)| format!("{} ~ {}", x, y)).unwrap_or_default();
+                let d = b.min_distance.map(|d| d.to_string()).unwrap_or_else(|| "-".to_string());
+                println!("BARCODES\t{}\tn={}\tmin_distance={}\t{}", b.kit, b.barcodes, d, pair);
+            }
+            println!("kits={} sequences={} errors={} warnings={}", report.kits, report.sequences, report.errors, report.warnings);
+        }
+    }
+    if report.has_errors() || (strict && report.warnings > 0) {
+        std::process::exit(1);
+    }
+}
+
+fn cmd_whois(sequence: &str, max_dist: usize, format: ReportFormat) -> anyhow::Result<()> {
+    let hits = porkchop::whois::whois(sequence, max_dist)?;
+    match format {
+        ReportFormat::Json => {
+            let rows: Vec<serde_json::Value> = hits.iter().map(|h| serde_json::json!({
+                "name": h.record.name,
+                "kind": h.record.kind.as_str(),
+                "sequence": h.record.sequence,
+                "strand": if h.reverse { "-" } else { "+" },
+                "distance": h.distance,
+                "kits": h.kits,
+                "source": h.record.provenance.source,
+                "appendix": h.record.provenance.appendix,
+                "notes": h.record.provenance.notes,
+            })).collect();
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+        }
+        ReportFormat::Text => {
+            if hits.is_empty() {
+                println!("no registry record within {} edits", max_dist);
+            }
+            for h in &hits {
+                println!("{}\t{}\t{}\t{}\t{}\t{}",
+                    h.distance, if h.reverse { "-" } else { "+" }, h.record.name,
+                    h.record.kind.as_str(), h.kits.join(","), h.record.provenance.source);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_export(id: &str, format: porkchop::export::ExportFormat, output: Option<std::path::PathBuf>) -> anyhow::Result<()> {
+    use porkchop::export::{dorado_barcode_fasta, export_kit, ExportFormat};
 
-    match get_sequences_for_kit(id.as_str()) {
-        Some(kit) => {
-            println!("id: {}", kit.id.0);
-            println!("description: {}", kit.description);
-            println!("legacy: {}", kit_is_legacy(kit));
-            println!("chemistry: {}", base_chemistry_of(kit).to_string());
-            println!("adapters/primers: {}", kit.adapters_and_primers.len());
-            println!("barcodes: {}", kit.barcodes.len());
+    let kit = porkchop::kits::resolve_kit_expr(id)?;
+    let text = export_kit(kit, format)?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, text)?;
+            if format == ExportFormat::DoradoToml {
+                std::fs::write(path.with_extension("fasta"), dorado_barcode_fasta(kit))?;
+            }
         }
-        None => {
-            eprintln!("Unknown kit: {}", id);
+        None if format == ExportFormat::DoradoToml => {
+            anyhow::bail!("dorado-toml writes an arrangement and a barcode FASTA; pass --output");
         }
+        None => print!("{}", text),
     }
-}
\ No newline at end of file
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use crossterm::event::{self, Event, KeyCode};
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
 
-use std::collections::{BTreeSet, HashMap};
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
 use std::sync::mpsc;
 use std::time::{Duration, Instant};
 static mut LAST_DRAW: Option<Instant> = None;
@@ -12,116 +12,183 @@ use rayon::prelude::*;
 struct OwnedRecord {
     id: String,
     seq: Vec<u8>,  // uppercase A/C/G/T/N
-    qual: Vec<u8>, // phred+33, or 'I' if missing
+    qual: Vec<u8>, // phred+33; empty if the input has none
 }
 
-fn write_fastq_record<W: std::io::Write>(w: &mut W, id: &str, seq: &[u8], qual: &[u8]) -> std::io::Result<()> {
-    w.write_all(b"@")?;
-    w.write_all(id.as_bytes())?;
-    w.write_all(b"\n")?;
-    w.write_all(seq)?;
-    w.write_all(b"\n+\n")?;
-    w.write_all(qual)?;
-    w.write_all(b"\n")?;
-    // return moved to end for cleanup
+/// What `clean` does with reads that carry no base qualities (FASTA input, SAM `*`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissingQual {
+    /// Refuse such reads.
+    Error,
+    /// Write FASTA output; qualities of every input are dropped.
+    Fasta,
+    /// Write FASTQ with this constant Phred score (0–93).
+    Phred(u8),
+}
 
-    Ok(())
+impl std::str::FromStr for MissingQual {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "fasta" | "fa" => Ok(Self::Fasta),
+            other => match other.parse::<u8>() {
+                Ok(q) if q <= 93 => Ok(Self::Phred(q)),
+                _ => Err(format!("Unknown missing-qual mode: {} (expected error, fasta or a Phred score 0-93)", other)),
+            },
+        }
+    }
 }
 
-// ---------- edlib wrapper ----------
-mod edwrap {
-    use edlib_rs::edlibrs::{edlibAlignRs, EdlibAlignConfigRs, EdlibAlignModeRs, EdlibAlignTaskRs, EdlibEqualityPairRs};
-    pub struct Hit { pub start: i32, pub end: i32, pub edits: i32 }
-    pub fn locate(pattern: &[u8], text: &[u8], max_edits: i32) -> Option<Hit> {
-        // edlib_rs 0.1.2 expects a slice for 'additionalequalities'
-        let empty: &[EdlibEqualityPairRs] = &[];
-        let cfg = EdlibAlignConfigRs { k: max_edits, mode: EdlibAlignModeRs::EDLIB_MODE_HW, task: EdlibAlignTaskRs::EDLIB_TASK_LOC, additionalequalities: empty };
-        let res = edlibAlignRs(pattern, text, &cfg);
-        if res.editDistance < 0 { return None; }
-        let start = res.startLocations.as_ref()?.get(0).copied()?;
-        let end   = res.endLocations.as_ref()?.get(0).copied()?;
-        Some(Hit { start, end, edits: res.editDistance })
+impl std::fmt::Display for MissingQual {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MissingQual::Error => f.write_str("error"),
+            MissingQual::Fasta => f.write_str("fasta"),
+            MissingQual::Phred(q) => write!(f, "{}", q),
+        }
     }
 }
 
+// ---------- edlib wrapper ----------
+use crate::edwrap;
+use crate::readmeta::{ReadMeta, SequencingSummary};
+use crate::seqio::{self, AuxTags, InputFile, InputFormat, OutRecord, OutputFormat, OutputHeader, Sampling, Sniffed, TagValue};
+
 #[derive(Clone)]
-struct Motif<'a> { name: &'a str, kind: &'a str, seq: &'a [u8] }
+struct Motif<'a> { name: &'a str, kind: crate::kit::SeqKind, seq: Vec<u8>, rc_seq: Vec<u8> }
+
+/// Best-distance locations kept per motif, orientation and read half, nearest the read end.
+/// Short motifs such as the NB flanks match in several places near an end, and the layout
+/// picks the one that abuts its neighbours.
+const HITS_PER_END: usize = 2;
+/// At most this many hits, fewest edits first, are placed on the layout per read.
+const MAX_LAYOUT_HITS: usize = 32;
+
+/// A kit's motifs plus the read layout they are placed on.
+///
+/// `seeds` indexes, for every motif and orientation, the code-free seeds of its `edits + 1`
+/// pieces: a hit within `edits` leaves at least one piece intact, so a read without any of
+/// a motif's seeds cannot hold that motif and edlib is not run for it.
+struct KitMotifs<'a> {
+    layout: crate::kit::ReadLayout,
+    motifs: Vec<Motif<'a>>,
+    seeds: aho_corasick::AhoCorasick,
+    /// (motif index, reverse complement) for each seed pattern.
+    seed_targets: Vec<Vec<(usize, bool)>>,
+    /// Motifs with a piece made only of ambiguity codes; always searched.
+    unseeded: Vec<(usize, bool)>,
+}
 
-fn motifs_for_kit<'a>(kit: &'static crate::kit::Kit) -> Vec<Motif<'a>> {
+fn motifs_for_kit<'a>(kit: &'static crate::kit::Kit, edits: i32) -> KitMotifs<'a> {
     let mut m = Vec::new();
-    for s in kit.adapters_and_primers {
-        m.push(Motif { name: s.name, kind: "adapter_or_primer", seq: s.sequence.as_bytes() });
+    for s in kit.adapters_and_primers.iter().chain(kit.barcodes.iter()) {
+        let seq = crate::iupac::normalize_motif(s.sequence.as_bytes()).into_owned();
+        let rc_seq = crate::iupac::revcomp(&seq);
+        m.push(Motif { name: s.name, kind: s.kind, seq, rc_seq });
     }
-    for s in kit.barcodes {
-        m.push(Motif { name: s.name, kind: "barcode_or_flank", seq: s.sequence.as_bytes() });
+    let pieces = edits.max(0) as usize + 1;
+    let mut seed_index: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut seed_targets: Vec<Vec<(usize, bool)>> = Vec::new();
+    let mut unseeded = Vec::new();
+    for (i, motif) in m.iter().enumerate() {
+        for (pat, is_rc) in [(&motif.seq, false), (&motif.rc_seq, true)] {
+            let seeds: Vec<&[u8]> = (0..pieces)
+                .map(|k| crate::iupac::concrete_seed(&pat[k * pat.len() / pieces..(k + 1) * pat.len() / pieces]))
+                .collect();
+            if seeds.iter().any(|s| s.is_empty()) {
+                unseeded.push((i, is_rc));
+                continue;
+            }
+            for seed in seeds {
+                let at = *seed_index.entry(seed.to_vec()).or_insert_with(|| {
+                    seed_targets.push(Vec::new());
+                    seed_targets.len() - 1
+                });
+                if !seed_targets[at].contains(&(i, is_rc)) { seed_targets[at].push((i, is_rc)); }
+            }
+        }
     }
-    m
+    let mut patterns: Vec<(Vec<u8>, usize)> = seed_index.into_iter().collect();
+    patterns.sort_by_key(|(_, at)| *at);
+    let seeds = aho_corasick::AhoCorasick::new(patterns.iter().map(|(p, _)| p))
+        .expect("failed to build Aho-Corasick automaton");
+    KitMotifs { layout: kit.layout, motifs: m, seeds, seed_targets, unseeded }
 }
 
 fn normalize_seq(seq: &[u8]) -> Vec<u8> {
     seq.iter().map(|&b| match b { b'a'..=b'z' => b.to_ascii_uppercase(), _ => b }).collect()
 }
 #[allow(dead_code)]
-#[allow(dead_code)]
 fn max_edits_for(len: usize) -> i32 { ((len as f64 * 0.15).ceil() as i32).max(1) }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct ModalityKey { left: String, right: String, barcode: String }
 
+/// Where a read was cut, in untrimmed coordinates, and the motif hits at each end
+/// as `name:start-end:edits`.
+#[derive(Clone, Default)]
+struct Trim { start: usize, end: usize, len: usize, five: Vec<String>, three: Vec<String>, barcode: Option<String> }
+
 #[derive(Clone)]
-struct CleanResult { rec: OwnedRecord, #[allow(dead_code)] modality: ModalityKey, clipped: bool, structure: String }
+struct CleanResult { rec: OwnedRecord, #[allow(dead_code)] modality: ModalityKey, clipped: bool, structure: String, trim: Trim }
 
-fn annotate_and_trim_one(seq: &[u8], qual: &[u8], _kit_id: &str, motifs: &[Motif], edits: i32) -> CleanResult {
+fn annotate_and_trim_one(seq: &[u8], qual: &[u8], _kit_id: &str, km: &KitMotifs, edits: i32) -> CleanResult {
+    use crate::kit::{LayoutHit, SeqKind};
     let s = normalize_seq(seq);
     let n = s.len() as i32;
-    let mut left_best: Option<(i32, i32, i32, &str)> = None;
-    let mut right_best: Option<(i32, i32, i32, &str)> = None;
-    let mut barcode_left: Option<(i32, i32, i32, &str)> = None;
-    let mut barcode_right: Option<(i32, i32, i32, &str)> = None;
-    let barcode: Option<String> = None;
-    for m in motifs {
-        if let Some(hit) = edwrap::locate(m.seq, &s, edits) {
-            let center = (hit.start + hit.end) / 2;
-            match m.kind {
-                "adapter_or_primer" => {
-                    if center < 300 {
-                        if left_best.map_or(true, |lb| hit.edits < lb.2) {
-                            left_best = Some((hit.start, hit.end, hit.edits, m.name));
-                        }
-                    }
-                    if center > n - 300 {
-                        if right_best.map_or(true, |rb| hit.edits < rb.2) {
-                            right_best = Some((hit.start, hit.end, hit.edits, m.name));
-                        }
-                    }
-                }
-                "barcode_or_flank" => {
-                    if center <= n / 2 {
-                        if barcode_left.map_or(true, |b| hit.edits < b.2) {
-                            barcode_left = Some((hit.start, hit.end, hit.edits, m.name));
-                        }
-                    } else {
-                        if barcode_right.map_or(true, |b| hit.edits < b.2) {
-                            barcode_right = Some((hit.start, hit.end, hit.edits, m.name));
-                        }
-                    }
-                }
-                _ => {}
-            }
+    // U is matched as T; the trimmed output keeps the read's own spelling.
+    let query = crate::iupac::normalize_read(&s);
+
+    // Motifs whose seeds occur in the read, both orientations; the kit layout decides which hits belong where.
+    let mut candidates: BTreeSet<(usize, bool)> = km.unseeded.iter().copied().collect();
+    for m in km.seeds.find_overlapping_iter(query.as_ref()) {
+        candidates.extend(km.seed_targets[m.pattern().as_usize()].iter().copied());
+    }
+    let mut hits: Vec<LayoutHit> = Vec::new();
+    for (i, is_rc) in candidates {
+        let m = &km.motifs[i];
+        let pat = if is_rc { &m.rc_seq } else { &m.seq };
+        // edlib reports every location at the best distance; keep the ones nearest each read end
+        let (mut five, mut three): (Vec<_>, Vec<_>) = edwrap::locate_all(pat, &query, edits).into_iter()
+            .partition(|h| h.start + h.end < n - 1);
+        five.sort_by_key(|h| h.start);
+        three.sort_by_key(|h| std::cmp::Reverse(h.end));
+        for hit in five.into_iter().take(HITS_PER_END).chain(three.into_iter().take(HITS_PER_END)) {
+            hits.push(LayoutHit { name: m.name, kind: m.kind, rc: is_rc, start: hit.start as usize, end: hit.end as usize, edits: hit.edits as u32 });
         }
     }
+    if hits.len() > MAX_LAYOUT_HITS {
+        let dist = |h: &LayoutHit| h.start.min(s.len() - 1 - h.end);
+        hits.sort_by_key(|h| (h.edits, dist(h)));
+        hits.truncate(MAX_LAYOUT_HITS);
+    }
+    let lm = km.layout.match_hits(&hits, s.len());
 
     let mut left_cut:  i32 = 0;
     let mut right_cut: i32 = n;
     let mut notes: Vec<String> = Vec::new();
-
-    if let Some((st, en, ed, nm)) = left_best  { left_cut = en + 1; notes.push(format!("L:{}:{}-{}:ed={}", nm, st, en, ed)); }
-    if let Some((st, en, ed, nm)) = right_best { right_cut = st;   notes.push(format!("R:{}:{}-{}:ed={}", nm, st, en, ed)); }
-
-    // Also clip barcodes at ends if detected
-    if let Some((st, en, _ed, nm)) = barcode_left { if en + 1 > left_cut { left_cut = en + 1; notes.push(format!("BL:{}:{}-{}", nm, st, en)); } }
-    if let Some((st, en, _ed, nm)) = barcode_right { if st < right_cut { right_cut = st; notes.push(format!("BR:{}:{}-{}", nm, st, en)); } }
-
+    let mut left_name: Option<&str> = None;
+    let mut right_name: Option<&str> = None;
+    let mut barcode: Option<&str> = None;
+    let (mut five, mut three) = (Vec::new(), Vec::new());
+    for a in &lm.assigned {
+        let h = &hits[a.hit];
+        let (st, en) = (h.start as i32, h.end as i32);
+        let is_bc = h.kind == SeqKind::Barcode;
+        if is_bc && barcode.is_none() { barcode = Some(h.name); }
+        let hit = format!("{}:{}-{}:{}", h.name, st, en, h.edits);
+        if a.five_prime { five.push(hit); } else { three.push(hit); }
+        if a.five_prime {
+            if en + 1 > left_cut { left_cut = en + 1; }
+            if !is_bc && left_name.is_none() { left_name = Some(h.name); }
+            notes.push(format!("{}:{}:{}-{}:ed={}", if is_bc { "BL" } else { "L" }, h.name, st, en, h.edits));
+        } else {
+            if st < right_cut { right_cut = st; }
+            if !is_bc { right_name = Some(h.name); }
+            notes.push(format!("{}:{}:{}-{}:ed={}", if is_bc { "BR" } else { "R" }, h.name, st, en, h.edits));
+        }
+    }
 
     if left_cut < 0 { left_cut = 0; }
     if right_cut > n { right_cut = n; }
@@ -130,39 +197,31 @@ fn annotate_and_trim_one(seq: &[u8], qual: &[u8], _kit_id: &str, motifs: &[Motif
     let start = left_cut as usize;
     let end   = right_cut as usize;
     let new_seq  = s[start..end].to_vec();
-    let new_qual = if !qual.is_empty() { qual[start..end].to_vec() } else { vec![b'I'; new_seq.len()] };
+    let new_qual = if !qual.is_empty() { qual[start..end].to_vec() } else { Vec::new() };
 
     let id = format!("trim={}..{};len={};{}", left_cut, right_cut, n, notes.join(";"));
     let modality = ModalityKey {
-        left:    left_best.map(|t| t.3.to_string()).unwrap_or_else(|| "-".into()),
-        right:   right_best.map(|t| t.3.to_string()).unwrap_or_else(|| "-".into()),
-        barcode: barcode.unwrap_or_else(|| "-".into()),
+        left:    left_name.map(|t| t.to_string()).unwrap_or_else(|| "-".into()),
+        right:   right_name.map(|t| t.to_string()).unwrap_or_else(|| "-".into()),
+        barcode: barcode.map(|t| t.to_string()).unwrap_or_else(|| "-".into()),
     };
-    let clipped = left_best.is_some() || right_best.is_some();
-    let mut structure: Vec<&str> = Vec::new();
-    if left_best.is_some() { structure.push("sequencing adapter"); }
-    if barcode_left.is_some() { structure.push("barcode"); }
-    structure.push("insert");
-    if barcode_right.is_some() { structure.push("reverse barcode"); }
-    if right_best.is_some() { structure.push("reverse adapter"); }
-    CleanResult { rec: OwnedRecord { id, seq: new_seq, qual: new_qual }, modality, clipped, structure: structure.join(" > ") }
+    let clipped = !lm.assigned.is_empty();
+    // Observed structure in read order, in the layout's own vocabulary.
+    let els = km.layout.elements();
+    let mut structure: Vec<String> = Vec::new();
+    for a in lm.assigned.iter().filter(|a| a.five_prime) { structure.push(els[a.element].to_string()); }
+    structure.push("insert".to_string());
+    for a in lm.assigned.iter().filter(|a| !a.five_prime) { structure.push(els[a.element].to_string()); }
+    if lm.reverse { structure.push("(reverse strand)".to_string()); }
+    let trim = Trim { start, end, len: s.len(), five, three, barcode: barcode.map(str::to_string) };
+    CleanResult { rec: OwnedRecord { id, seq: new_seq, qual: new_qual }, modality, clipped, structure: structure.join(" > "), trim }
 }
 
-struct Tallies { total: u64, clipped: u64, unclippable: u64, by_structure: HashMap<String, u64>, clip5_hist: HashMap<usize,u64>, clip3_hist: HashMap<usize,u64> }
-
-impl Default for Tallies {
-    fn default() -> Self {
-        Tallies {
-            total: 0,
-            clipped: 0,
-            unclippable: 0,
-            by_structure: HashMap::new(),
-            clip5_hist: HashMap::new(),
-            clip3_hist: HashMap::new(),
-        }
-    }
-}
-enum StatEvent { Seen(String, bool), Clip(usize, usize), Done }
+#[derive(Default)]
+struct Tallies { total: u64, clipped: u64, unclippable: u64, by_structure: HashMap<String, u64>, clip5_hist: HashMap<usize,u64>, clip3_hist: HashMap<usize,u64>, by_stratum: BTreeMap<String, (u64, u64)> }
+
+/// `Seen(structure, clipped, strata)`; strata are `run=...`, `flow_cell=...`, `model=...` from the read's metadata.
+enum StatEvent { Seen(String, bool, Vec<String>), Clip(usize, usize), Done }
 
 fn draw_dashboard<B: ratatui::backend::Backend>(terminal: &mut ratatui::Terminal<B>, tallies: &Tallies, max_bins: usize) -> std::io::Result<()> {
     use ratatui::layout::{Constraint, Direction, Layout};
@@ -187,7 +246,7 @@ terminal.draw(|f| {
 
         // Observed contexts (top)
         let mut rows: Vec<(String, u64)> = tallies.by_structure.iter().map(|(k,v)| (k.clone(), *v)).collect();
-        rows.sort_by(|a,b| b.1.cmp(&a.1));
+        rows.sort_by_key(|r| std::cmp::Reverse(r.1));
         rows.truncate(20);
         let table_rows = rows.into_iter().map(|(k,c)| Row::new(vec![k, c.to_string()]));
         let table = Table::new(
@@ -196,10 +255,24 @@ terminal.draw(|f| {
         )
             .header(Row::new(vec!["Structure", "Count"]))
             .block(Block::default().borders(Borders::ALL).title("Observed modalities (top 20)"));
-        f.render_widget(table, chunks[0]);
+        if tallies.by_stratum.is_empty() {
+            f.render_widget(table, chunks[0]);
+        } else {
+            // reads and clipped reads per run / flow cell / model
+            let top = Layout::default().direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+                .split(chunks[0]);
+            f.render_widget(table, top[0]);
+            let strata_rows = tallies.by_stratum.iter().map(|(k, (n, c))| Row::new(vec![k.clone(), n.to_string(), c.to_string()]));
+            let strata = Table::new(strata_rows, [Constraint::Percentage(60), Constraint::Length(10), Constraint::Length(10)])
+                .header(Row::new(vec!["Run / flow cell / model", "Reads", "Clipped"]))
+                .block(Block::default().borders(Borders::ALL).title("By acquisition"));
+            f.render_widget(strata, top[1]);
+        }
 
         // Helper to build dynamic bins for a given histogram map; excludes zero-length bins
-        fn build_binned<'a>(hm: &HashMap<usize,u64>, max_bars: usize) -> (Vec<(&'a str, u64)>, Vec<(String,u64)>, usize, usize, usize) {
+        type Binned<'a> = (Vec<(&'a str, u64)>, Vec<(String,u64)>, usize, usize, usize);
+        fn build_binned<'a>(hm: &HashMap<usize,u64>, max_bars: usize) -> Binned<'a> {
             let mut keys_pos: Vec<usize> = hm.keys().cloned().filter(|k| *k > 0).collect();
             if keys_pos.is_empty() {
                 return (vec![("-", 0)], vec![("-".to_string(), 0)], 0, 0, 1);
@@ -209,8 +282,8 @@ terminal.draw(|f| {
             let max_k = *keys_pos.last().unwrap();
             let span = max_k - min_k + 1;
             let max_bars = if max_bars == 0 { 1 } else { max_bars };
-            let bin_size = std::cmp::max(1usize, (span + max_bars - 1) / max_bars);
-            let bin_count = (span + bin_size - 1) / bin_size;
+            let bin_size = std::cmp::max(1usize, span.div_ceil(max_bars));
+            let bin_count = span.div_ceil(bin_size);
 
             let mut bins: Vec<u64> = vec![0; bin_count];
             for (k, v) in hm.iter() {
@@ -221,12 +294,11 @@ terminal.draw(|f| {
 
             let mut data: Vec<(&'a str, u64)> = Vec::with_capacity(bin_count);
             let mut summary: Vec<(String,u64)> = Vec::with_capacity(bin_count);
-            for i in 0..bin_count {
+            for (i, &count) in bins.iter().enumerate() {
                 let start = min_k + i*bin_size;
                 let end = std::cmp::min(start + bin_size - 1, max_k);
                 let lbl = if bin_size == 1 { format!("{}", start) } else { format!("{}-{}", start, end) };
                 let leaked: &'a str = Box::leak(lbl.clone().into_boxed_str());
-                let count = bins[i];
                 data.push((leaked, count));
                 summary.push((lbl, count));
             }
@@ -262,7 +334,7 @@ terminal.draw(|f| {
         // Bin summaries
         fn top_rows<'a>(pairs: &[(String,u64)], n: usize) -> Vec<Row<'a>> {
             let mut v = pairs.to_vec();
-            v.sort_by(|a,b| b.1.cmp(&a.1));
+            v.sort_by_key(|r| std::cmp::Reverse(r.1));
             v.truncate(n);
             v.into_iter().map(|(k,c)| Row::new(vec![k, c.to_string()])).collect()
         }
@@ -290,15 +362,13 @@ terminal.draw(|f| {
             let left_area = charts_row[0];
             let left_bins = u16::try_from(left_data.len()).unwrap_or(0);
             let left_inner = left_area.width.saturating_sub(2); // borders
-            let bar_w_left: u16 = if left_bins > 0 {
-                (left_inner / left_bins).max(1)
-            } else { 1 };
+            let bar_w_left: u16 = left_inner.checked_div(left_bins).unwrap_or(1).max(1);
 
             // Throttle bin labels if there isn't enough room: keep every Nth label
             let approx_label_cols = bar_w_left.max(1);
             let max_label_slots = left_inner / (approx_label_cols.saturating_mul(2).max(1)); // leave space between
             let denom_left = usize::max(1, max_label_slots as usize);
-            let stride_left: usize = if left_bins == 0 { 1 } else { ((left_bins as usize) + denom_left - 1) / denom_left };
+            let stride_left: usize = if left_bins == 0 { 1 } else { (left_bins as usize).div_ceil(denom_left) };
             // Build render-time data from the String summary to avoid leaking labels
             let left_pairs_render: Vec<(String, u64)> = left_summary.iter().enumerate().map(|(i,(s,v))| {
                 if stride_left == 1 || i % stride_left == 0 { (s.clone(), *v) } else { (String::from(""), *v) }
@@ -317,14 +387,12 @@ let left_chart = BarChart::default()
             let right_area = charts_row[1];
             let right_bins = u16::try_from(right_data.len()).unwrap_or(0);
             let right_inner = right_area.width.saturating_sub(2); // borders
-            let bar_w_right: u16 = if right_bins > 0 {
-                (right_inner / right_bins).max(1)
-            } else { 1 };
+            let bar_w_right: u16 = right_inner.checked_div(right_bins).unwrap_or(1).max(1);
 
             let approx_label_cols_r = bar_w_right.max(1);
             let max_label_slots_r = right_inner / (approx_label_cols_r.saturating_mul(2).max(1));
             let denom_right = usize::max(1, max_label_slots_r as usize);
-            let stride_right: usize = if right_bins == 0 { 1 } else { ((right_bins as usize) + denom_right - 1) / denom_right };
+            let stride_right: usize = if right_bins == 0 { 1 } else { (right_bins as usize).div_ceil(denom_right) };
             let right_pairs_render: Vec<(String, u64)> = right_summary.iter().enumerate().map(|(i,(s,v))| {
                 if stride_right == 1 || i % stride_right == 0 { (s.clone(), *v) } else { (String::from(""), *v) }
             }).collect();
@@ -366,20 +434,26 @@ let tick = Duration::from_millis(200);
             
             // minimal 'q' to quit (clean dashboard)
             if event::poll(std::time::Duration::from_millis(16)).unwrap_or(false) {
-                if let Ok(ev) = event::read() {
-                    if let Event::Key(k) = ev {
-                        if matches!(k.code, KeyCode::Char('q') | KeyCode::Char('Q')) { cancel.store(true, Ordering::Relaxed); done = true; }
-                    }
+                if let Ok(Event::Key(k)) = event::read() {
+                    if matches!(k.code, KeyCode::Char('q') | KeyCode::Char('Q')) { cancel.store(true, Ordering::Relaxed); done = true; }
                 }
             }
 while let Ok(ev) = rx.try_recv() {
                 match ev {
-                    StatEvent::Seen(modality, clipped) => {
+                    StatEvent::Seen(modality, clipped, strata) => {
                         tallies.total += 1;
                         if clipped { tallies.clipped += 1; } else { tallies.unclippable += 1; }
                         *tallies.by_structure.entry(modality).or_insert(0) += 1;
+                        for s in strata {
+                            let e = tallies.by_stratum.entry(s).or_insert((0, 0));
+                            e.0 += 1;
+                            if clipped { e.1 += 1; }
+                        }
                     }
-                    StatEvent::Clip(l5, l3) => { if l5>0 { *tallies.clip5_hist.entry(l5).or_insert(0) += 1; } if l3>0 { *tallies.clip3_hist.entry(l3).or_insert(0) += 1; } },
+                    StatEvent::Clip(l5, l3) => {
+                        if l5>0 { *tallies.clip5_hist.entry(l5).or_insert(0) += 1; }
+                        if l3>0 { *tallies.clip3_hist.entry(l3).or_insert(0) += 1; }
+                    },
                     StatEvent::Done => { done = true; }
                 }
             }
@@ -407,7 +481,7 @@ fn parse_trim_from_id(id: &str) -> (usize, usize) {
 
     if let Some(rest) = id.strip_prefix("trim=") {
         let parts: Vec<&str> = rest.split(';').collect();
-        if let Some(range) = parts.get(0) {
+        if let Some(range) = parts.first() {
             if let Some((a, b)) = range.split_once("..") {
                 if let (Ok(l), Ok(r)) = (a.parse::<i32>(), b.parse::<i32>()) {
                     left = if l > 0 { l as usize } else { 0 };
@@ -426,205 +500,182 @@ fn parse_trim_from_id(id: &str) -> (usize, usize) {
     }
     let total = total.unwrap_or_else(|| right.max(left));
     let clip5 = left;
-    let clip3 = if right <= total { total - right } else { 0 };
+    let clip3 = total.saturating_sub(right);
     (clip5, clip3)
 }
 fn ensure_known_kit(kit: &str) -> anyhow::Result<()> {
-    if crate::get_sequences_for_kit(kit).is_none() {
-        anyhow::bail!(
-            "Unknown kit: {}. Use \"porkchop list-kits --format table\" to see valid kit ids.",
-            kit
-        );
-    }
-    Ok(())
+    crate::kits::resolve_kit_expr(kit).map(|_| ())
 }
 
     // return moved to end for cleanup
 
-fn split_supported_files(paths: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<PathBuf>) {
+/// Sniff every input by content; unreadable or unrecognised files are returned as `path: reason`.
+/// Streams (stdin, FIFOs) are accepted unsniffed, since sniffing would consume them.
+fn split_supported_files(paths: Vec<InputFile>) -> (Vec<(InputFile, Option<Sniffed>)>, Vec<String>) {
     let mut ok = Vec::new();
     let mut bad = Vec::new();
     for p in paths {
-        let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
-        let ext  = p.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
-        let is_ok = name.ends_with(".fastq.gz") || name.ends_with(".fq.gz") || ext == "fastq" || ext == "fq" || ext == "sam" || ext == "bam";
-        if is_ok { ok.push(p); } else { bad.push(p); }
+        if seqio::is_stream(&p.path) { ok.push((p, None)); continue; }
+        match seqio::sniff_path(&p.path) {
+            Ok(sn) => ok.push((p, Some(sn))),
+            Err(e) => bad.push(format!("{:#}", e)),
+        }
     }
     (ok, bad)
 }
 
-fn process_fastx_to_gz(out_path: &Path, input_files: Vec<PathBuf>, chunk_size: usize, kit_id: &str, edits: i32, kit_ref: &'static crate::kit::Kit, events: &mpsc::Sender<StatEvent>, cancel: &Arc<AtomicBool>) -> anyhow::Result<()> {
-    use rust_htslib::{bgzf::{Writer as BgzfWriter}, tpool::ThreadPool};
-    use std::io::Write;
-    use needletail::parser::parse_fastx_file;
+/// Output header comment: trim notes, then the input's own header comment.
+fn output_comment(notes: &str, extra_notes: &str, comment: Option<&str>) -> String {
+    match comment {
+        Some(c) => format!("{}{} {}", notes, extra_notes, c),
+        None => format!("{}{}", notes, extra_notes),
+    }
+}
+
+/// The input's aux tags for the output read, cut to the kept bases (see [`AuxTags::trimmed`]).
+fn output_tags(seq: &[u8], trim: &Trim, tags: Option<&AuxTags>) -> Option<AuxTags> {
+    tags.map(|t| t.trimmed(seq, trim.start, trim.end))
+}
+
+/// Aux tags that record the trim in SAM/BAM output, in the local-use `Y?` namespace:
+/// `YS:i`/`YE:i` the kept bases `start..end` of the untrimmed read (`YE` exclusive), `YL:i`
+/// its length, `Y5:Z`/`Y3:Z` the motif hits at each end (`name:start-end:edits`,
+/// comma-separated, with `end` inclusive as edlib reports it) and `YB:Z` the first barcode hit.
+/// All coordinates are 0-based.
+const TRIM_TAGS: &[&[u8; 2]] = &[b"YS", b"YE", b"YL", b"Y5", b"Y3", b"YB"];
+
+fn trim_tags(t: &Trim) -> Vec<([u8; 2], TagValue)> {
+    let mut tags = vec![
+        (*b"YS", TagValue::I32(t.start as i32)),
+        (*b"YE", TagValue::I32(t.end as i32)),
+        (*b"YL", TagValue::I32(t.len as i32)),
+    ];
+    if !t.five.is_empty() { tags.push((*b"Y5", TagValue::String(t.five.join(",")))); }
+    if !t.three.is_empty() { tags.push((*b"Y3", TagValue::String(t.three.join(",")))); }
+    if let Some(bc) = &t.barcode { tags.push((*b"YB", TagValue::String(bc.clone()))); }
+    tags
+}
 
-    let motifs = motifs_for_kit(kit_ref);
+/// A cleaned read on its way to the writer thread; `qual` is Phred+33, empty if the input had none.
+struct OutRead { id: String, comment: Option<String>, seq: Vec<u8>, qual: Vec<u8>, tags: Option<AuxTags> }
 
-    // Pipeline: processing thread -> bounded channel -> writer thread (owns gz)
+/// `;summary_barcode=...;mean_qscore=...` for a read joined with a sequencing summary.
+fn summary_notes(meta: &ReadMeta) -> String {
+    let Some(row) = &meta.summary else { return String::new() };
+    let mut notes = String::new();
+    if let Some(bc) = &row.barcode_arrangement { notes.push_str(&format!(";summary_barcode={}", bc)); }
+    if let Some(q) = row.mean_qscore { notes.push_str(&format!(";mean_qscore={:.2}", q)); }
+    notes
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_fastx_to_gz(out_path: &Path, format: OutputFormat, input_files: Vec<InputFile>, summary: Option<&SequencingSummary>, sampling: Sampling, missing_qual: MissingQual, chunk_size: usize, kit_id: &str, edits: i32, kit_ref: &'static crate::kit::Kit, events: &mpsc::Sender<StatEvent>, cancel: &Arc<AtomicBool>) -> anyhow::Result<()> {
+    let motifs = motifs_for_kit(kit_ref, edits);
+    let header = match format {
+        OutputFormat::Sam | OutputFormat::Bam => OutputHeader::new(seqio::command_line()).with_inputs(&input_files)?,
+        _ => OutputHeader::default(),
+    };
+
+    // Pipeline: processing thread -> bounded channel -> writer thread (owns the output)
     let out_path_owned = out_path.to_path_buf();
-    let (txw, rxw) = std::sync::mpsc::sync_channel::<Vec<(String, Vec<u8>, Vec<u8>)>>(8);
+    let (txw, rxw) = std::sync::mpsc::sync_channel::<Vec<OutRead>>(8);
     let writer_handle = std::thread::spawn(move || -> anyhow::Result<()> {
-        /* replaced GzEncoder with BGZF Writer */
-        let mut gz = BgzfWriter::from_path(&out_path_owned)?;
-        let tpool = ThreadPool::new(num_cpus::get_physical() as u32)?;
-        gz.set_thread_pool(&tpool)?;
+        let mut out = seqio::Writer::create(&out_path_owned, format, &header, num_cpus::get_physical())?;
         while let Ok(batch) = rxw.recv() {
-            for (id, seq, qual) in batch {
-                write_fastq_record(&mut gz, &id, &seq, &qual)?;
+            for r in batch {
+                let filled;
+                let qual = match missing_qual {
+                    MissingQual::Fasta => None,
+                    _ if !r.qual.is_empty() || r.seq.is_empty() => Some(r.qual.as_slice()),
+                    MissingQual::Phred(q) => { filled = vec![q + 33; r.seq.len()]; Some(filled.as_slice()) }
+                    MissingQual::Error if !format.requires_qual() => None,
+                    MissingQual::Error => anyhow::bail!("read {} has no base qualities; use --missing-qual fasta or --missing-qual <PHRED>", r.id),
+                };
+                out.write(&OutRecord { id: &r.id, comment: r.comment.as_deref(), seq: &r.seq, qual, tags: r.tags.as_ref() })?;
             }
         }
-        // finalize gzip stream
-        let _ = gz.flush()?;
-        // return moved to end for cleanup
-    
+        out.finish()?;
         Ok(())
 });
 
 
     let chunk: usize = chunk_size.max(1);
-for path in input_files {
+    let hts_out = matches!(format, OutputFormat::Sam | OutputFormat::Bam);
+    // one stream over all inputs, so head and reservoir samples span files
+    let mut reader = sampling.apply(seqio::read_inputs(input_files));
+    loop {
         if cancel.load(Ordering::Relaxed) { break; }
-        let lower = path.to_string_lossy().to_ascii_lowercase();
-
-        if lower.ends_with(".sam") {
-            use rust_htslib::bam::{self, Read};
-            let mut reader = bam::Reader::from_path(&path)?;
-            let mut buf: Vec<rust_htslib::bam::Record> = Vec::new();
-
-            for r in reader.records() {
-                if cancel.load(Ordering::Relaxed) { break; }
-                if let Ok(rec) = r { buf.push(rec); }
-                if cancel.load(Ordering::Relaxed) { break; }
-                    if buf.len() >= chunk {
-                    let processed: Vec<(String, CleanResult)> = buf.par_iter().map(|r| {
-                        let name = std::str::from_utf8(r.qname()).unwrap_or("SAM");
-                        let seq = r.seq().as_bytes();
-                        let qual = r.qual().iter().map(|q| (q + 33) as u8).collect::<Vec<u8>>();
-                        let cr = annotate_and_trim_one(&seq, &qual, kit_id, &motifs, edits);
-                        (name.to_string(), cr)
-                    }).collect();
-                    for (name, cr) in &processed {
-let mut out_batch: Vec<(String, Vec<u8>, Vec<u8>)> = Vec::new();
-let _ = events.send(StatEvent::Seen(cr.structure.clone(), cr.clipped)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc)); let rid = format!("{}
- {}", name, cr.rec.id); out_batch.push((rid, cr.rec.seq.clone(), cr.rec.qual.clone())); 
-let _ = txw.send(out_batch);
-}
-                    buf.clear();
-                }
-            }
-            if !buf.is_empty() {
-                let processed: Vec<(String, CleanResult)> = buf.par_iter().map(|r| {
-                        let name = std::str::from_utf8(r.qname()).unwrap_or("SAM");
-                        let seq = r.seq().as_bytes();
-                        let qual = r.qual().iter().map(|q| (q + 33) as u8).collect::<Vec<u8>>();
-                        let cr = annotate_and_trim_one(&seq, &qual, kit_id, &motifs, edits);
-                        (name.to_string(), cr)
-                    }).collect();
-                for (name, cr) in &processed {
-let mut out_batch: Vec<(String, Vec<u8>, Vec<u8>)> = Vec::new();
-let _ = events.send(StatEvent::Seen(cr.structure.clone(), cr.clipped)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc)); let rid = format!("{}
- {}", name, cr.rec.id); out_batch.push((rid, cr.rec.seq.clone(), cr.rec.qual.clone())); 
-let _ = txw.send(out_batch);
-}
-            }
-
-        } else if lower.ends_with(".bam") {
-            use rust_htslib::bam::{self, Read};
-            let mut reader = bam::Reader::from_path(&path)?;
-            let mut buf: Vec<rust_htslib::bam::Record> = Vec::new();
-
-            for r in reader.records() {
-                if cancel.load(Ordering::Relaxed) { break; }
-                if let Ok(rec) = r { buf.push(rec); }
-                if cancel.load(Ordering::Relaxed) { break; }
-                    if buf.len() >= chunk {
-                    let processed: Vec<(String, CleanResult)> = buf.par_iter().map(|r| {
-                        let name = std::str::from_utf8(r.qname()).unwrap_or("BAM");
-                        let seq = r.seq().as_bytes();
-                        let qual = r.qual().iter().map(|q| (q + 33) as u8).collect::<Vec<u8>>();
-                        let cr = annotate_and_trim_one(&seq, &qual, kit_id, &motifs, edits);
-                        (name.to_string(), cr)
-                    }).collect();
-                    for (name, cr) in &processed {
-let mut out_batch: Vec<(String, Vec<u8>, Vec<u8>)> = Vec::new();
-let _ = events.send(StatEvent::Seen(cr.structure.clone(), cr.clipped)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc)); let rid = format!("{}
- {}", name, cr.rec.id); out_batch.push((rid, cr.rec.seq.clone(), cr.rec.qual.clone())); 
-let _ = txw.send(out_batch);
-}
-                    buf.clear();
-                }
-            }
-            if !buf.is_empty() {
-                let processed: Vec<(String, CleanResult)> = buf.par_iter().map(|r| {
-                        let name = std::str::from_utf8(r.qname()).unwrap_or("BAM");
-                        let seq = r.seq().as_bytes();
-                        let qual = r.qual().iter().map(|q| (q + 33) as u8).collect::<Vec<u8>>();
-                        let cr = annotate_and_trim_one(&seq, &qual, kit_id, &motifs, edits);
-                        (name.to_string(), cr)
-                    }).collect();
-                for (name, cr) in &processed {
-let mut out_batch: Vec<(String, Vec<u8>, Vec<u8>)> = Vec::new();
-let _ = events.send(StatEvent::Seen(cr.structure.clone(), cr.clipped)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc)); let rid = format!("{}
- {}", name, cr.rec.id); out_batch.push((rid, cr.rec.seq.clone(), cr.rec.qual.clone())); 
-let _ = txw.send(out_batch);
-}
-            }
-
-        } else {
-            let mut reader = parse_fastx_file(&path)?;
-            loop {
-                let mut owned_chunk: Vec<OwnedRecord> = Vec::with_capacity(chunk);
-                for _ in 0..chunk {
-                    match reader.next() {
-                        Some(Ok(rec)) => {
-                            let id   = String::from_utf8_lossy(rec.id()).to_string();
-                            let seq  = rec.seq().to_vec();
-                            let qual = rec.qual().map(|q| q.to_vec()).unwrap_or_else(|| vec![b'I'; seq.len()]);
-                            owned_chunk.push(OwnedRecord { id, seq, qual });
-                        }
-                        Some(Err(_)) => continue,
-                        None => break,
-                    }
-                }
-                if owned_chunk.is_empty() { break; }
-                let processed: Vec<CleanResult> = owned_chunk.par_iter()
-                    .map(|r| annotate_and_trim_one(&r.seq, &r.qual, kit_id, &motifs, edits))
-                    .collect();
-                for (src, cr) in owned_chunk.iter().zip(processed.iter()) {
-                    let mut out_batch: Vec<(String, Vec<u8>, Vec<u8>)> = Vec::new();
-let _ = events.send(StatEvent::Seen(cr.structure.clone(), cr.clipped)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc)); let (lc, rc) = parse_trim_from_id(&cr.rec.id); let _ = events.send(StatEvent::Clip(lc, rc));
-                    let rid = format!("{} {}", src.id, cr.rec.id);
-                    out_batch.push((rid, cr.rec.seq.clone(), cr.rec.qual.clone()));
-                
-let _ = txw.send(out_batch);
-}
-            }
+        let mut reads = Vec::with_capacity(chunk);
+        for r in reader.by_ref().take(chunk) { reads.push(r?); }
+        if reads.is_empty() { break; }
+        if let Some(s) = summary {
+            for read in reads.iter_mut() { s.annotate(read); }
+        }
+        let processed: Vec<CleanResult> = reads.par_iter()
+            .map(|r| {
+                let qual = r.qual.as_deref().unwrap_or_default();
+                annotate_and_trim_one(&r.seq, qual, kit_id, &motifs, edits)
+            })
+            .collect();
+        let mut out_batch: Vec<OutRead> = Vec::with_capacity(reads.len());
+        for (read, cr) in reads.iter().zip(processed) {
+            let strata = read.meta.strata().into_iter().map(|(f, v)| format!("{}={}", f, v)).collect();
+            let _ = events.send(StatEvent::Seen(cr.structure.clone(), cr.clipped, strata));
+            let (lc, rc) = parse_trim_from_id(&cr.rec.id);
+            let _ = events.send(StatEvent::Clip(lc, rc));
+            // appended to the trim notes of every output header
+            let source_tag = read.source.as_ref().map(|s| format!(";source={}", s)).unwrap_or_default();
+            let extra = format!("{}{}", source_tag, summary_notes(&read.meta));
+            let mut tags = output_tags(&read.seq, &cr.trim, read.tags.as_ref());
+            let comment = if hts_out {
+                // the read id stays the qname; the trim goes to its own tags, other notes to CO
+                // (ahead of an input CO, see `Writer::write`)
+                let t = tags.get_or_insert_with(AuxTags::default);
+                t.0.retain(|(k, _)| !TRIM_TAGS.contains(&k));
+                t.0.extend(trim_tags(&cr.trim));
+                let notes: Vec<&str> = [extra.trim_start_matches(';'), read.comment.as_deref().unwrap_or("")]
+                    .into_iter().filter(|c| !c.is_empty()).collect();
+                if notes.is_empty() { None } else { Some(notes.join(" ")) }
+            } else {
+                Some(output_comment(&cr.rec.id, &extra, read.comment.as_deref()))
+            };
+            out_batch.push(OutRead { id: read.id.clone(), comment, seq: cr.rec.seq, qual: cr.rec.qual, tags });
         }
+        // the writer only hangs up after failing; stop reading and report its error below
+        if txw.send(out_batch).is_err() { break; }
     }
 
-    // gz.finish() now handled in writer thread
-    // return moved to end for cleanup
-
-    // finish pipeline: close channel and join writer
+    // finish pipeline: close the writer channel and join the writer thread
     drop(txw);
-    let _ = writer_handle.join();
-
-// finish pipeline: close the writer channel and join the writer thread
-Ok(())
+    writer_handle.join().map_err(|_| anyhow::anyhow!("clean writer thread panicked"))??;
+    Ok(())
 }
 
-pub fn run(threads: usize, gz_threads: usize, chunk_size: usize, kit: &str, edits: i32, tui_max_bins: usize, output: &Path, files: Vec<PathBuf>) -> anyhow::Result<()> {
+#[allow(clippy::too_many_arguments)]
+pub fn run(threads: usize, gz_threads: usize, chunk_size: usize, kit: &str, edits: i32, tui_max_bins: usize, missing_qual: MissingQual, output: &Path, output_format: Option<OutputFormat>, files: Vec<InputFile>, summary: Option<&SequencingSummary>, sampling: Sampling) -> anyhow::Result<()> {
     let _ = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global();
 
         ensure_known_kit(kit)?;
 ensure_known_kit(kit)?;
     let (ok, bad) = split_supported_files(files);
     if !bad.is_empty() {
-        let mut msg = String::from("Unsupported file type(s):\n");
-        for p in &bad { msg.push_str(&format!("  - {}\n", p.display())); }
-        msg.push_str("Allowed: SAM (.sam), BAM (.bam), FASTQ (.fastq/.fq), and gzipped FASTQ (.fastq.gz/.fq.gz).\n");
+        let mut msg = String::from("Unsupported input file(s):\n");
+        for why in &bad { msg.push_str(&format!("  - {}\n", why)); }
+        msg.push_str("Allowed: SAM, BAM, FASTQ and FASTA (plain, gzip/BGZF, zstd, bzip2 or xz), detected from file content.\n");
         anyhow::bail!(msg);
     }
+    let format = output_format.or_else(|| OutputFormat::from_path(output)).unwrap_or(OutputFormat::FastqGz);
+    let format = if missing_qual == MissingQual::Fasta { format.to_fasta() } else { format };
+    if missing_qual == MissingQual::Error && format.requires_qual() {
+        let fasta: Vec<String> = ok.iter()
+            .filter(|(_, sn)| matches!(sn, Some(sn) if sn.format == InputFormat::Fasta))
+            .map(|(p, _)| p.path.display().to_string())
+            .collect();
+        if !fasta.is_empty() {
+            anyhow::bail!("FASTA input has no base qualities ({}); use --missing-qual fasta to write FASTA \
+                or --missing-qual <PHRED> to write FASTQ with a constant score", fasta.join(", "));
+        }
+    }
 
     let total_threads = if threads == 0 { std::cmp::max(1, num_cpus::get()) } else { threads };
     let gz_threads = std::cmp::max(1, gz_threads);
@@ -633,16 +684,61 @@ ensure_known_kit(kit)?;
     // Configure Rayon pool for cleaning work
     let _ = rayon::ThreadPoolBuilder::new().num_threads(cleaning_threads).build_global();
 
-    let kit_ref: &'static crate::kit::Kit = crate::get_sequences_for_kit(kit).expect("validated kit");
+    let kit_ref: &'static crate::kit::Kit = crate::kits::resolve_kit_expr(kit)?;
     let (tx, rx) = mpsc::channel::<StatEvent>();
     let cancel: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
     let ui_handle = stats_thread(rx, kit_ref, tui_max_bins, cancel.clone());
 
-    eprintln!("clean: kit={} | total_threads={} | clean_threads={} | gz_threads={} | chunk_size={} | inputs={} | output={}", kit, total_threads, cleaning_threads, gz_threads, chunk_size, ok.len(), output.display());
-    let ret = process_fastx_to_gz(output, ok, chunk_size, kit, edits, kit_ref, &tx, &cancel);
+    let inputs: Vec<InputFile> = ok.into_iter().map(|(p, _)| p).collect();
+    eprintln!("clean: kit={} | total_threads={} | clean_threads={} | gz_threads={} | chunk_size={} | inputs={} | sampling={} | output={} ({})", kit, total_threads, cleaning_threads, gz_threads, chunk_size, inputs.len(), sampling, output.display(), format);
+    let ret = process_fastx_to_gz(output, format, inputs, summary, sampling, missing_qual, chunk_size, kit, edits, kit_ref, &tx, &cancel);
 
     let _ = tx.send(StatEvent::Done);
     let _ = ui_handle.join();
 
     ret
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_qual_from_str() {
+        assert_eq!("error".parse::<MissingQual>(), Ok(MissingQual::Error));
+        assert_eq!("FA".parse::<MissingQual>(), Ok(MissingQual::Fasta));
+        assert_eq!("20".parse::<MissingQual>(), Ok(MissingQual::Phred(20)));
+        assert_eq!("93".parse::<MissingQual>(), Ok(MissingQual::Phred(93)));
+        assert!("94".parse::<MissingQual>().is_err());
+        assert!("fastq".parse::<MissingQual>().is_err());
+        for m in [MissingQual::Error, MissingQual::Fasta, MissingQual::Phred(7)] {
+            assert_eq!(m.to_string().parse::<MissingQual>(), Ok(m));
+        }
+    }
+
+    #[test]
+    fn fasta_mode_keeps_output_compression() {
+        assert_eq!(OutputFormat::Fastq.to_fasta(), OutputFormat::Fasta);
+        assert_eq!(OutputFormat::FastqGz.to_fasta(), OutputFormat::FastaGz);
+        assert_eq!(OutputFormat::Bam.to_fasta(), OutputFormat::Bam);
+        assert!(OutputFormat::Fastq.requires_qual() && !OutputFormat::Sam.requires_qual());
+    }
+
+    #[test]
+    fn direct_rna_reads_with_u_trim_like_their_dna_spelling() {
+        let kit = crate::kits::resolve_kit_expr("RNA004").unwrap();
+        let km = motifs_for_kit(kit, 3);
+        let motif = |name: &str| kit.adapters_and_primers.iter().find(|r| r.name == name).unwrap().sequence;
+        let insert = "GTCCATGCTAGCTTCGGACTGCATCGGTACCTGAGCTTGCAGCTCGATGCCTAGGCTTACGCTGATCGGTCA".repeat(3);
+        let dna = format!("{}{}{}", insert, motif("polyA"), motif("RTA_bottom"));
+        let rna = dna.replace('T', "U");
+        let qual = vec![b'I'; dna.len()];
+        let (d, r) = (annotate_and_trim_one(dna.as_bytes(), &qual, "RNA004", &km, 3),
+            annotate_and_trim_one(rna.to_lowercase().as_bytes(), &qual, "RNA004", &km, 3));
+        assert!(d.clipped && r.clipped);
+        assert_eq!((r.trim.start, r.trim.end), (d.trim.start, d.trim.end));
+        assert_eq!(r.structure, d.structure);
+        assert_eq!(d.structure, "insert > polyA > RTA_bottom");
+        assert_eq!(d.trim.end, insert.len());
+        assert_eq!(r.rec.seq, &rna.as_bytes()[..insert.len()]);
+    }
+}
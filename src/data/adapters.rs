@@ -6,6 +6,7 @@
 //! - LA/NA/RA top/bottom sequences
 //! - Rapid Adapter T (RAP T)
 //! - RNA/cDNA: RTP, SSPII (with wobble codes), CRTA, cPRM forward/reverse
+//! - Direct RNA: RT adapter (RTA) duplex and the poly(A) tail it is annealed to
 //!
 //! Notes:
 //! - Sequences are uppercase as published (wobble codes retained: V = A/C/G; mG = riboguanosine).
@@ -118,6 +119,38 @@ pub const CPRM_REV: SequenceRecord = SequenceRecord {
     provenance: CHTD_A15,
 };
 
+const DIRECT_RNA: Provenance = Provenance {
+    source: "https://nanoporetech.com/document/direct-rna-sequencing-sqk-rna004",
+    appendix: None,
+    notes: Some("RTA duplex from the direct RNA protocols (shared by RNA002 and RNA004). The RMX/RLA motor \
+                 adapter sequences are not published and do not basecall as RNA, so they are not modelled."),
+};
+
+/// RT Adapter (RTA) top strand; the 3' oligo(dT) overhang anneals to the poly(A) tail.
+pub const RTA_TOP: SequenceRecord = SequenceRecord {
+    name: "RTA_top",
+    kind: SeqKind::AdapterTop,
+    sequence: "GAGGCGAGCGGTCAATTTTCCTAAGAGCAAGAAGAAGCCTTTTTTTTTT",
+    provenance: DIRECT_RNA,
+};
+
+/// RT Adapter (RTA) bottom strand, ligated (5'-phosphate) to the RNA 3' end.
+/// In a direct RNA read it follows the poly(A) tail.
+pub const RTA_BOTTOM: SequenceRecord = SequenceRecord {
+    name: "RTA_bottom",
+    kind: SeqKind::AdapterBottom,
+    sequence: "GGCTTCTTCTTGCTCTTAGGTAGTAGGTTC",
+    provenance: DIRECT_RNA,
+};
+
+/// Poly(A) tail of direct RNA reads, modelled as a flank between insert and RTA.
+pub const POLY_A: SequenceRecord = SequenceRecord {
+    name: "polyA",
+    kind: SeqKind::Flank,
+    sequence: "AAAAAAAAAAAAAAA",
+    provenance: Provenance { notes: Some("Minimum tail modelled; real tails are longer and variable."), ..DIRECT_RNA },
+};
+
 /// Convenience: all adapter/primer records for current kits.
 /// const `CURRENT_ADAPTERS_AND_PRIMERS` — auto‑generated rustdoc.
 pub const CURRENT_ADAPTERS_AND_PRIMERS: &[SequenceRecord] = &[
@@ -190,6 +190,11 @@ pub const NB_BARCODES_24: &[SequenceRecord] = &[
     nb!("NB24", "GCATAGTTCTGCATGATGGGTTAG")
 ];
 
+/// NB01–NB12, as sold in the Kit 9/10 expansion EXP‑NBD104.
+pub const NB_BARCODES_1_TO_12: &[SequenceRecord] = NB_BARCODES_24.split_at(12).0;
+/// NB13–NB24, as sold in the Kit 9/10 expansion EXP‑NBD114.
+pub const NB_BARCODES_13_TO_24: &[SequenceRecord] = NB_BARCODES_24.split_at(12).1;
+
 // === Shared BP/BC/RB/16S/RLB cores ===
 macro_rules! bx { ($label:literal, $seq:literal) => { SequenceRecord{ name: $label, kind: SeqKind::Barcode, sequence: $seq, provenance: CHTD_A14 } }; }
 
@@ -0,0 +1,264 @@
+//! Full kit descriptions: every record with its sequence, reverse complement,
+//! length and provenance.
+//!
+//! [`describe_kit`] builds a serialisable [`KitDescription`]; [`render_description`]
+//! prints it as text, JSON, YAML, a Markdown table or FASTA.
+
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use crate::export::{export_kit, ExportFormat};
+use crate::iupac;
+use crate::kit::{BaseChemistry, Kit, KitId, Provenance, ReadLayout, SequenceRecord};
+
+/// Supported `describe` formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DescribeFormat {
+    Text,
+    Json,
+    Yaml,
+    Md,
+    Fasta,
+}
+
+impl DescribeFormat {
+    /// Return the CLI name of the format.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DescribeFormat::Text => "text",
+            DescribeFormat::Json => "json",
+            DescribeFormat::Yaml => "yaml",
+            DescribeFormat::Md => "md",
+            DescribeFormat::Fasta => "fasta",
+        }
+    }
+}
+
+impl std::str::FromStr for DescribeFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "md" | "markdown" => Ok(Self::Md),
+            "fasta" | "fa" => Ok(Self::Fasta),
+            other => Err(format!("Unknown describe format: {}", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for DescribeFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A [`SequenceRecord`] with derived fields.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordDescription {
+    #[serde(flatten)]
+    pub record: SequenceRecord,
+    /// Reverse complement of the normalised sequence (ambiguity codes kept).
+    pub reverse_complement: String,
+    /// Length of the normalised sequence (modification prefixes not counted).
+    pub length: usize,
+}
+
+/// A [`Kit`] with every record described.
+#[derive(Debug, Clone, Serialize)]
+pub struct KitDescription {
+    pub id: KitId,
+    pub description: &'static str,
+    pub legacy: bool,
+    pub chemistry: BaseChemistry,
+    pub layout: ReadLayout,
+    pub adapters_and_primers: Vec<RecordDescription>,
+    pub barcodes: Vec<RecordDescription>,
+}
+
+fn describe_record(r: &SequenceRecord) -> RecordDescription {
+    let norm = iupac::normalize_motif(r.sequence.as_bytes());
+    RecordDescription {
+        record: *r,
+        reverse_complement: String::from_utf8_lossy(&iupac::revcomp(&norm)).into_owned(),
+        length: norm.len(),
+    }
+}
+
+/// Describe `kit` and all of its records.
+pub fn describe_kit(kit: &Kit) -> KitDescription {
+    KitDescription {
+        id: kit.id,
+        description: kit.description,
+        legacy: kit.legacy,
+        chemistry: kit.chemistry,
+        layout: kit.layout,
+        adapters_and_primers: kit.adapters_and_primers.iter().map(describe_record).collect(),
+        barcodes: kit.barcodes.iter().map(describe_record).collect(),
+    }
+}
+
+fn source_line(p: &Provenance) -> String {
+    match p.appendix {
+        Some(a) => format!("{} ({})", p.source, a),
+        None => p.source.to_string(),
+    }
+}
+
+fn render_text(d: &KitDescription) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "id: {}", d.id.0);
+    let _ = writeln!(out, "description: {}", d.description);
+    let _ = writeln!(out, "legacy: {}", d.legacy);
+    let _ = writeln!(out, "chemistry: {}", d.chemistry);
+    let _ = writeln!(out, "layout: {}", d.layout);
+    for (title, recs) in [("adapters/primers", &d.adapters_and_primers), ("barcodes", &d.barcodes)] {
+        let _ = writeln!(out, "{}: {}", title, recs.len());
+        for r in recs.iter() {
+            let _ = writeln!(out, "  {} [{}, {} bp]", r.record.name, r.record.kind.as_str(), r.length);
+            let _ = writeln!(out, "    seq:    {}", r.record.sequence);
+            let _ = writeln!(out, "    rc:     {}", r.reverse_complement);
+            let _ = writeln!(out, "    source: {}", source_line(&r.record.provenance));
+            if let Some(n) = r.record.provenance.notes {
+                let _ = writeln!(out, "    notes:  {}", n);
+            }
+        }
+    }
+    out
+}
+
+/// A YAML double-quoted scalar. Every string is quoted, so values such as `0x1F`, `.inf`,
+/// `yes` or `1e3` stay strings under YAML 1.1 and 1.2 readers; JSON string escapes are valid
+/// YAML escapes.
+fn yaml_str(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+fn yaml_opt(s: Option<&str>) -> String {
+    s.map(yaml_str).unwrap_or_else(|| "null".to_string())
+}
+
+fn render_yaml(d: &KitDescription) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "id: {}", yaml_str(d.id.0));
+    let _ = writeln!(out, "description: {}", yaml_str(d.description));
+    let _ = writeln!(out, "legacy: {}", d.legacy);
+    let _ = writeln!(out, "chemistry: {}", yaml_str(d.chemistry.as_str()));
+    let _ = writeln!(out, "layout: {}", yaml_str(&d.layout.to_string()));
+    for (key, recs) in [("adapters_and_primers", &d.adapters_and_primers), ("barcodes", &d.barcodes)] {
+        if recs.is_empty() {
+            let _ = writeln!(out, "{}: []", key);
+            continue;
+        }
+        let _ = writeln!(out, "{}:", key);
+        for r in recs.iter() {
+            let p = &r.record.provenance;
+            let _ = writeln!(out, "  - name: {}", yaml_str(r.record.name));
+            let _ = writeln!(out, "    kind: {}", yaml_str(r.record.kind.as_str()));
+            let _ = writeln!(out, "    sequence: {}", yaml_str(r.record.sequence));
+            let _ = writeln!(out, "    reverse_complement: {}", yaml_str(&r.reverse_complement));
+            let _ = writeln!(out, "    length: {}", r.length);
+            let _ = writeln!(out, "    provenance:");
+            let _ = writeln!(out, "      source: {}", yaml_str(p.source));
+            let _ = writeln!(out, "      appendix: {}", yaml_opt(p.appendix));
+            let _ = writeln!(out, "      notes: {}", yaml_opt(p.notes));
+        }
+    }
+    out
+}
+
+fn md_cell(s: &str) -> String { s.replace('|', "\\|") }
+
+fn render_md(d: &KitDescription) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "## {}", d.id.0);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "{}", d.description);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "- chemistry: {}", d.chemistry);
+    let _ = writeln!(out, "- legacy: {}", d.legacy);
+    let _ = writeln!(out, "- layout: `{}`", d.layout);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "| Name | Kind | Length | Sequence | Reverse complement | Source | Notes |");
+    let _ = writeln!(out, "|---|---|---|---|---|---|---|");
+    for r in d.adapters_and_primers.iter().chain(d.barcodes.iter()) {
+        let p = &r.record.provenance;
+        let _ = writeln!(out, "| {} | {} | {} | `{}` | `{}` | {} | {} |",
+            md_cell(r.record.name), r.record.kind.as_str(), r.length, r.record.sequence, r.reverse_complement,
+            md_cell(&source_line(p)), md_cell(p.notes.unwrap_or("")));
+    }
+    out
+}
+
+/// Render the full description of `kit` in `format`.
+pub fn render_description(kit: &Kit, format: DescribeFormat) -> anyhow::Result<String> {
+    let d = describe_kit(kit);
+    Ok(match format {
+        DescribeFormat::Text => render_text(&d),
+        DescribeFormat::Json => serde_json::to_string_pretty(&d)? + "\n",
+        DescribeFormat::Yaml => render_yaml(&d),
+        DescribeFormat::Md => render_md(&d),
+        DescribeFormat::Fasta => export_kit(kit, ExportFormat::Fasta)?,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kit::SeqKind;
+
+    const SRC: Provenance = Provenance { source: "lab: notes", appendix: None, notes: Some("pipe | and \"quote\"") };
+    static RECORDS: [SequenceRecord; 2] = [
+        SequenceRecord { name: "yes", kind: SeqKind::AdapterTop, sequence: "AmCGTR", provenance: SRC },
+        SequenceRecord { name: "0x1F", kind: SeqKind::Barcode, sequence: "AAGGN", provenance: SRC },
+    ];
+
+    fn kit() -> Kit {
+        Kit {
+            id: KitId("TEST-DESCRIBE"),
+            description: "1e3",
+            legacy: false,
+            chemistry: BaseChemistry::Ligation,
+            adapters_and_primers: &RECORDS[..1],
+            barcodes: &RECORDS[1..],
+            layout: ReadLayout::generic(BaseChemistry::Ligation, true),
+        }
+    }
+
+    #[test]
+    fn records_carry_reverse_complement_and_normalised_length() {
+        let d = describe_kit(&kit());
+        assert_eq!((d.adapters_and_primers[0].reverse_complement.as_str(), d.adapters_and_primers[0].length), ("YACGT", 5));
+        assert_eq!((d.barcodes[0].reverse_complement.as_str(), d.barcodes[0].length), ("NCCTT", 5));
+        let json: serde_json::Value = serde_json::from_str(&render_description(&kit(), DescribeFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["barcodes"][0]["name"], "0x1F");
+        assert_eq!(json["barcodes"][0]["reverse_complement"], "NCCTT");
+        assert_eq!(json["adapters_and_primers"][0]["provenance"]["notes"], "pipe | and \"quote\"");
+    }
+
+    #[test]
+    fn yaml_quotes_every_string() {
+        let yaml = render_description(&kit(), DescribeFormat::Yaml).unwrap();
+        for line in ["id: \"TEST-DESCRIBE\"", "description: \"1e3\"", "legacy: false", "  - name: \"yes\"",
+            "  - name: \"0x1F\"", "    length: 5", "      source: \"lab: notes\"", "      appendix: null",
+            "      notes: \"pipe | and \\\"quote\\\"\""] {
+            assert!(yaml.lines().any(|l| l == line), "missing {:?} in\n{}", line, yaml);
+        }
+    }
+
+    #[test]
+    fn markdown_escapes_pipes_and_formats_parse() {
+        let md = render_description(&kit(), DescribeFormat::Md).unwrap();
+        assert!(md.contains("| yes | adapter_top | 5 | `AmCGTR` | `YACGT` | lab: notes | pipe \\| and \"quote\" |"), "{}", md);
+        let fasta = render_description(&kit(), DescribeFormat::Fasta).unwrap();
+        assert_eq!(fasta.lines().filter(|l| l.starts_with('>')).count(), 2);
+        for f in [DescribeFormat::Text, DescribeFormat::Json, DescribeFormat::Yaml, DescribeFormat::Md, DescribeFormat::Fasta] {
+            assert_eq!(f.as_str().parse::<DescribeFormat>(), Ok(f));
+        }
+        assert_eq!("markdown".parse::<DescribeFormat>(), Ok(DescribeFormat::Md));
+        assert!("xml".parse::<DescribeFormat>().is_err());
+    }
+}
@@ -0,0 +1,44 @@
+//! Semiglobal (infix) edlib search with IUPAC-aware matching.
+//!
+//! Patterns may carry ambiguity codes; they are passed to edlib as
+//! [`iupac::edlib_equalities`](crate::iupac::edlib_equalities).
+
+use edlib_rs::edlibrs::{
+    edlibAlign, edlibDefaultAlignConfig, edlibFreeAlignResult,
+    EdlibAlignMode_EDLIB_MODE_HW, EdlibAlignTask_EDLIB_TASK_LOC,
+};
+
+/// One match location: inclusive `start..=end` in the text, with its edit distance.
+pub struct Hit { pub start: i32, pub end: i32, pub edits: i32 }
+
+/// Up to this many equally good locations are reported per search.
+const MAX_LOCATIONS: usize = 16;
+
+/// All best-scoring locations of `pattern` in `text` (at most `MAX_LOCATIONS`).
+pub fn locate_all(pattern: &[u8], text: &[u8], max_edits: i32) -> Vec<Hit> {
+    // raw FFI so IUPAC codes in the pattern can be passed as additional equalities
+    let eq = crate::iupac::edlib_equalities();
+    let mut cfg = unsafe { edlibDefaultAlignConfig() };
+    cfg.k = max_edits;
+    cfg.mode = EdlibAlignMode_EDLIB_MODE_HW;
+    cfg.task = EdlibAlignTask_EDLIB_TASK_LOC;
+    cfg.additionalEqualities = eq.as_ptr();
+    cfg.additionalEqualitiesLength = eq.len() as i32;
+    let res = unsafe {
+        edlibAlign(pattern.as_ptr() as *const _, pattern.len() as i32,
+                   text.as_ptr() as *const _, text.len() as i32, cfg)
+    };
+    let mut hits = Vec::new();
+    if res.editDistance >= 0 && res.numLocations > 0
+        && !res.startLocations.is_null() && !res.endLocations.is_null() {
+        let k = (res.numLocations as usize).min(MAX_LOCATIONS);
+        let (starts, ends) = unsafe {
+            (std::slice::from_raw_parts(res.startLocations, k), std::slice::from_raw_parts(res.endLocations, k))
+        };
+        for (&start, &end) in starts.iter().zip(ends) {
+            hits.push(Hit { start, end, edits: res.editDistance });
+        }
+    }
+    unsafe { edlibFreeAlignResult(res) };
+    hits
+}
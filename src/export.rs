@@ -0,0 +1,329 @@
+//! Render kits in the native formats of other trimmers/demultiplexers.
+//!
+//! Formats:
+//! - `fasta`: every record of the kit, as registered (modified bases normalised)
+//! - `cutadapt`: adapter FASTA for `-a/-g file:`; barcodes are embedded in their flanks
+//!   and names are made path-safe for `{name}` output templates
+//! - `porechop-py`: `Adapter(...)` entries for Porechop's `adapters.py`
+//! - `dorado-toml`: a custom barcode arrangement built from the kit's flanks, plus
+//!   the matching barcode-core FASTA (see [`dorado_barcode_fasta`]); kits whose
+//!   layout barcodes both read ends also get the bottom-strand masks
+
+use std::fmt::Write as _;
+
+use anyhow::bail;
+
+use crate::iupac;
+use crate::kit::{Kit, SeqKind, SequenceRecord};
+
+/// Supported export formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Fasta,
+    Cutadapt,
+    PorechopPy,
+    DoradoToml,
+}
+
+impl ExportFormat {
+    /// Return the CLI name of the format.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Fasta => "fasta",
+            ExportFormat::Cutadapt => "cutadapt",
+            ExportFormat::PorechopPy => "porechop-py",
+            ExportFormat::DoradoToml => "dorado-toml",
+        }
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "fasta" | "fa" => Ok(Self::Fasta),
+            "cutadapt" => Ok(Self::Cutadapt),
+            "porechop-py" | "porechop" => Ok(Self::PorechopPy),
+            "dorado-toml" | "dorado" => Ok(Self::DoradoToml),
+            other => Err(format!("Unknown export format: {}", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+fn seq_of(r: &SequenceRecord) -> String {
+    String::from_utf8_lossy(&iupac::normalize_motif(r.sequence.as_bytes())).into_owned()
+}
+
+fn revcomp_str(s: &str) -> String {
+    String::from_utf8_lossy(&iupac::revcomp(s.as_bytes())).into_owned()
+}
+
+fn find_flank<'a>(kit: &'a Kit, name: &str) -> Option<&'a SequenceRecord> {
+    kit.adapters_and_primers.iter().chain(kit.barcodes.iter())
+        .find(|r| r.kind == SeqKind::Flank && r.name.eq_ignore_ascii_case(name))
+}
+
+/// Sequence found 5' and 3' of a barcode core on the forward strand.
+///
+/// Native barcodes are documented as reverse flanks (`NB_flank_rev3` and
+/// `NB_flank_rev5`); their reverse complements give the forward context
+/// `ATTGCTAAGGTTAA - barcode - CAGCACC`. Other kits use their `*left*`/`*top*`
+/// and `*right*`/`*bot*` flanks; a kit with a single flank gets it as the front.
+pub fn barcode_flanks(kit: &Kit) -> Option<(String, String)> {
+    if let (Some(r3), Some(r5)) = (find_flank(kit, "NB_flank_rev3"), find_flank(kit, "NB_flank_rev5")) {
+        return Some((revcomp_str(&seq_of(r3)), revcomp_str(&seq_of(r5))));
+    }
+    let flanks: Vec<&SequenceRecord> = kit.adapters_and_primers.iter().chain(kit.barcodes.iter())
+        .filter(|r| r.kind == SeqKind::Flank)
+        .collect();
+    if flanks.is_empty() {
+        return None;
+    }
+    let has = |r: &&SequenceRecord, keys: &[&str]| {
+        let n = r.name.to_ascii_lowercase();
+        keys.iter().any(|k| n.contains(k))
+    };
+    let front = flanks.iter().find(|r| has(r, &["left", "top", "fwd"])).unwrap_or(&flanks[0]);
+    let rear = flanks.iter().find(|r| has(r, &["right", "bot", "rev"]) && r.name != front.name);
+    Some((seq_of(front), rear.map(|r| seq_of(r)).unwrap_or_default()))
+}
+
+/// Short, path-safe barcode label: `BP01/BC01/RB01` → `BC01`, `NB01` → `NB01`.
+pub fn barcode_label(name: &str) -> String {
+    let toks: Vec<&str> = name.split('/').collect();
+    let tok = toks.iter().find(|t| t.starts_with("BC")).unwrap_or(&toks[0]);
+    tok.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' }).collect()
+}
+
+/// Split a barcode label into its alphabetic prefix and index (`NB07` → `("NB", 7)`).
+fn label_index(label: &str) -> Option<(String, usize)> {
+    let digits = label.len() - label.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 { return None; }
+    let (pre, num) = label.split_at(label.len() - digits);
+    num.parse().ok().map(|n| (pre.to_string(), n))
+}
+
+fn barcode_records(kit: &Kit) -> impl Iterator<Item = &'static SequenceRecord> {
+    kit.barcodes.iter().filter(|r| r.kind == SeqKind::Barcode)
+}
+
+fn render_fasta(kit: &Kit) -> String {
+    let mut out = String::new();
+    for r in kit.adapters_and_primers.iter().chain(kit.barcodes.iter()) {
+        let _ = writeln!(out, ">{} kit={} kind={}", r.name, kit.id.0, r.kind.as_str());
+        let _ = writeln!(out, "{}", seq_of(r));
+    }
+    out
+}
+
+fn render_cutadapt(kit: &Kit) -> String {
+    let mut out = String::new();
+    for r in kit.adapters_and_primers.iter().filter(|r| r.kind != SeqKind::Flank) {
+        let _ = writeln!(out, ">{}", barcode_label(r.name));
+        let _ = writeln!(out, "{}", seq_of(r));
+    }
+    let (front, rear) = barcode_flanks(kit).unwrap_or_default();
+    for r in barcode_records(kit) {
+        let _ = writeln!(out, ">{}", barcode_label(r.name));
+        let _ = writeln!(out, "{}{}{}", front, seq_of(r), rear);
+    }
+    out
+}
+
+fn py_adapter(out: &mut String, label: &str, start: (&str, &str), end: (&str, &str)) {
+    let _ = writeln!(out, "    Adapter('{}',", label);
+    let _ = writeln!(out, "            start_sequence=('{}', '{}'),", start.0, start.1);
+    let _ = writeln!(out, "            end_sequence=('{}', '{}')),", end.0, end.1);
+}
+
+fn render_porechop(kit: &Kit) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# porkchop export: {} ({})", kit.id.0, kit.description);
+    let _ = writeln!(out, "# Append these entries to ADAPTERS in porechop/adapters.py.");
+    let _ = writeln!(out, "from porechop.adapters import Adapter");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "ADAPTERS = [");
+
+    let tops: Vec<&SequenceRecord> = kit.adapters_and_primers.iter().filter(|r| r.kind == SeqKind::AdapterTop).collect();
+    let bottoms: Vec<&SequenceRecord> = kit.adapters_and_primers.iter().filter(|r| r.kind == SeqKind::AdapterBottom).collect();
+    for i in 0..tops.len().max(bottoms.len()) {
+        match (tops.get(i), bottoms.get(i)) {
+            (Some(t), Some(b)) => py_adapter(&mut out, &format!("{} adapter", kit.id.0),
+                (t.name, &seq_of(t)), (b.name, &seq_of(b))),
+            (Some(r), None) | (None, Some(r)) => {
+                let s = seq_of(r);
+                py_adapter(&mut out, &format!("{} {}", kit.id.0, r.name),
+                    (r.name, &s), (&format!("{}_rev", r.name), &revcomp_str(&s)));
+            }
+            (None, None) => {}
+        }
+    }
+    for r in kit.adapters_and_primers.iter().filter(|r| r.kind == SeqKind::Primer) {
+        let s = seq_of(r);
+        py_adapter(&mut out, &format!("{} {}", kit.id.0, r.name),
+            (r.name, &s), (&format!("{}_rev", r.name), &revcomp_str(&s)));
+    }
+    let (front, rear) = barcode_flanks(kit).unwrap_or_default();
+    for r in barcode_records(kit) {
+        let label = barcode_label(r.name);
+        let full = format!("{}{}{}", front, seq_of(r), rear);
+        py_adapter(&mut out, &format!("Barcode {} (forward)", label),
+            (&label, &full), (&format!("{}_rev", label), &revcomp_str(&full)));
+    }
+    let _ = writeln!(out, "]");
+    out
+}
+
+fn render_dorado(kit: &Kit) -> anyhow::Result<String> {
+    let mut labels: Vec<(String, usize)> = Vec::new();
+    for r in barcode_records(kit) {
+        let label = barcode_label(r.name);
+        match label_index(&label) {
+            Some(l) => labels.push(l),
+            None => bail!("kit {} has barcode {} without an index; dorado needs numbered barcodes", kit.id.0, label),
+        }
+    }
+    if labels.is_empty() {
+        bail!("kit {} has no barcodes to arrange", kit.id.0);
+    }
+    let prefix = labels[0].0.clone();
+    if labels.iter().any(|(p, _)| *p != prefix) {
+        bail!("kit {} mixes barcode prefixes; dorado needs a single pattern", kit.id.0);
+    }
+    let (front, rear) = match barcode_flanks(kit) {
+        Some(f) => f,
+        None => bail!("kit {} has no barcode flanks; dorado arrangements need them", kit.id.0),
+    };
+    let first = labels.iter().map(|l| l.1).min().unwrap_or(1);
+    let last = labels.iter().map(|l| l.1).max().unwrap_or(1);
+    let double_ended = kit.layout.barcoded_both_ends();
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# porkchop export: {} ({})", kit.id.0, kit.description);
+    let _ = writeln!(out, "# dorado demux --barcode-arrangement <this file> --barcode-sequences <barcode FASTA>");
+    let _ = writeln!(out, "[arrangement]");
+    let _ = writeln!(out, "name = \"{}\"", kit.id.0);
+    let _ = writeln!(out, "kit = \"{}\"", prefix);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "mask1_front = \"{}\"", front);
+    let _ = writeln!(out, "mask1_rear = \"{}\"", rear);
+    if double_ended {
+        // the bottom strand reads the same context reverse-complemented
+        let _ = writeln!(out, "mask2_front = \"{}\"", revcomp_str(&rear));
+        let _ = writeln!(out, "mask2_rear = \"{}\"", revcomp_str(&front));
+    }
+    let _ = writeln!(out);
+    let _ = writeln!(out, "barcode1_pattern = \"{}%02i\"", prefix);
+    if double_ended {
+        let _ = writeln!(out, "barcode2_pattern = \"{}%02i\"", prefix);
+    }
+    let _ = writeln!(out, "first_index = {}", first);
+    let _ = writeln!(out, "last_index = {}", last);
+    Ok(out)
+}
+
+/// Barcode cores named as the dorado arrangement expects (`NB01`, `BC01`, ...).
+pub fn dorado_barcode_fasta(kit: &Kit) -> String {
+    let mut out = String::new();
+    for r in barcode_records(kit) {
+        let _ = writeln!(out, ">{}", barcode_label(r.name));
+        let _ = writeln!(out, "{}", seq_of(r));
+    }
+    out
+}
+
+/// Render `kit` in `format`.
+pub fn export_kit(kit: &Kit, format: ExportFormat) -> anyhow::Result<String> {
+    match format {
+        ExportFormat::Fasta => Ok(render_fasta(kit)),
+        ExportFormat::Cutadapt => Ok(render_cutadapt(kit)),
+        ExportFormat::PorechopPy => Ok(render_porechop(kit)),
+        ExportFormat::DoradoToml => render_dorado(kit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kit::{KitId, Provenance};
+    use crate::kits::KITS;
+
+    fn kit(id: &str) -> &'static Kit { KITS.iter().find(|k| k.id.0 == id).unwrap() }
+
+    fn field<'a>(toml: &'a str, key: &str) -> Option<&'a str> {
+        toml.lines().find_map(|l| l.strip_prefix(key)?.strip_prefix(" = ")).map(|v| v.trim_matches('"'))
+    }
+
+    #[test]
+    fn labels_and_indices() {
+        assert_eq!(barcode_label("BP01/BC01/RB01"), "BC01");
+        assert_eq!(barcode_label("NB07"), "NB07");
+        assert_eq!(barcode_label("odd name"), "odd_name");
+        assert_eq!(label_index("NB07"), Some(("NB".to_string(), 7)));
+        assert_eq!(label_index("BCX"), None);
+    }
+
+    #[test]
+    fn native_flanks_are_the_reverse_flanks_complemented() {
+        let (front, rear) = barcode_flanks(kit("NBD114.24")).unwrap();
+        assert_eq!(front, "ATTGCTAAGGTTAA");
+        assert_eq!(rear, "CAGCACC");
+        let cut = export_kit(kit("NBD114.24"), ExportFormat::Cutadapt).unwrap();
+        let nb01 = kit("NBD114.24").barcodes.iter().find(|r| barcode_label(r.name) == "NB01").unwrap();
+        assert!(cut.contains(&format!(">NB01\n{}{}{}\n", front, seq_of(nb01), rear)));
+    }
+
+    #[test]
+    fn dorado_bottom_masks_are_reverse_complemented_and_swapped() {
+        for id in ["NBD114.24", "PCB114.24"] {
+            let toml = export_kit(kit(id), ExportFormat::DoradoToml).unwrap();
+            let front = field(&toml, "mask1_front").unwrap();
+            let rear = field(&toml, "mask1_rear").unwrap();
+            assert_eq!(field(&toml, "mask2_front").unwrap(), revcomp_str(rear), "{}", id);
+            assert_eq!(field(&toml, "mask2_rear").unwrap(), revcomp_str(front), "{}", id);
+            assert!(field(&toml, "barcode2_pattern").is_some(), "{}", id);
+        }
+        let toml = export_kit(kit("NBD114.96"), ExportFormat::DoradoToml).unwrap();
+        assert_eq!(field(&toml, "kit"), Some("NB"));
+        assert_eq!(field(&toml, "first_index"), Some("1"));
+        assert_eq!(field(&toml, "last_index"), Some("96"));
+    }
+
+    #[test]
+    fn dorado_single_ended_for_rapid_barcodes() {
+        let toml = export_kit(kit("RBK114.24"), ExportFormat::DoradoToml).unwrap();
+        assert!(field(&toml, "mask1_front").is_some());
+        assert!(field(&toml, "mask2_front").is_none());
+        assert!(field(&toml, "barcode2_pattern").is_none());
+    }
+
+    #[test]
+    fn dorado_names_the_unnumbered_barcode() {
+        const PROV: Provenance = Provenance { source: "test", appendix: None, notes: None };
+        const RECS: &[SequenceRecord] = &[
+            SequenceRecord { name: "BC01", kind: SeqKind::Barcode, sequence: "ACGTACGT", provenance: PROV },
+            SequenceRecord { name: "BCX", kind: SeqKind::Barcode, sequence: "TTGGCCAA", provenance: PROV },
+            SequenceRecord { name: "left_flank", kind: SeqKind::Flank, sequence: "GGGG", provenance: PROV },
+        ];
+        let k = Kit { id: KitId("T"), barcodes: RECS, ..*kit("RBK114.24") };
+        let e = export_kit(&k, ExportFormat::DoradoToml).unwrap_err().to_string();
+        assert!(e.contains("barcode BCX without an index"), "{}", e);
+        let e = export_kit(kit("LSK114"), ExportFormat::DoradoToml).unwrap_err().to_string();
+        assert!(e.contains("no barcodes"), "{}", e);
+    }
+
+    #[test]
+    fn fasta_lists_every_record() {
+        let k = kit("NBD114.24");
+        let fa = export_kit(k, ExportFormat::Fasta).unwrap();
+        assert_eq!(fa.lines().filter(|l| l.starts_with('>')).count(), k.adapters_and_primers.len() + k.barcodes.len());
+        assert!("dorado".parse::<ExportFormat>().unwrap() == ExportFormat::DoradoToml);
+        assert!("bogus".parse::<ExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! IUPAC ambiguity codes for motif matching.
+//!
+//! Registry sequences are transcribed verbatim from ONT documentation and may
+//! carry wobble codes (e.g. `V` in SSPII, `M` in the 16S forward primer) and
+//! modified-base notation (`mG` = riboguanosine). A motif position holding a
+//! code must match any of the bases it stands for. Ambiguity only works on the
+//! motif side: an `N` (or any other code) in a read is masked so that it
+//! matches nothing, whichever classifier runs. Direct RNA reads and motifs may
+//! spell uracil as `U`; both sides are folded to `T` before matching.
+//!
+//! This module provides:
+//! - [`normalize_motif`]: uppercase, drop modification prefixes (`mG` → `G`), `U` → `T`
+//! - [`normalize_read`]: uppercase, `U` → `T`, anything else masked to [`READ_MASK`]
+//! - [`revcomp`]: reverse complement that maps codes to their complements (`V` ↔ `B`)
+//! - [`myers_builder`]: a `bio` Myers builder with every code registered as ambiguous
+//! - [`edlib_equalities`]: the same equalities in edlib's `additionalEqualities` form
+//! - [`concrete_seed`]: the longest code-free stretch of a motif, for exact prefilters
+
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use bio::pattern_matching::myers::MyersBuilder;
+use edlib_rs::edlibrs::EdlibEqualityPair;
+
+/// IUPAC nucleotide codes (other than A/C/G/T) and the bases they stand for.
+pub const IUPAC_CODES: &[(u8, &[u8])] = &[
+    (b'R', b"AG"),
+    (b'Y', b"CT"),
+    (b'S', b"CG"),
+    (b'W', b"AT"),
+    (b'K', b"GT"),
+    (b'M', b"AC"),
+    (b'B', b"CGT"),
+    (b'D', b"AGT"),
+    (b'H', b"ACT"),
+    (b'V', b"ACG"),
+    (b'N', b"ACGT"),
+];
+
+/// Bases matched by `code` (a concrete base matches only itself).
+pub fn expand(code: u8) -> &'static [u8] {
+    match code.to_ascii_uppercase() {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        c => IUPAC_CODES.iter().find(|(k, _)| *k == c).map(|(_, v)| *v).unwrap_or(b""),
+    }
+}
+
+/// Is `b` an ambiguity code rather than a concrete base?
+pub fn is_ambiguous(b: u8) -> bool {
+    IUPAC_CODES.iter().any(|(k, _)| *k == b.to_ascii_uppercase())
+}
+
+/// Complement of a nucleotide or IUPAC code (unknown symbols map to `N`).
+pub fn complement(b: u8) -> u8 {
+    match b.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' | b'U' => b'A',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'S' => b'S',
+        b'W' => b'W',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        _ => b'N',
+    }
+}
+
+/// Reverse complement, preserving ambiguity codes.
+pub fn revcomp(seq: &[u8]) -> Vec<u8> {
+    seq.iter().rev().map(|&b| complement(b)).collect()
+}
+
+/// Normalise a registry motif for matching.
+///
+/// Modified-base prefixes (`mG`, `mC`, ...: a lowercase `m` right before an
+/// uppercase A/C/G/T/U) are reduced to the base itself, `U` becomes `T` and
+/// everything else is uppercased, so a lowercase `m` elsewhere stays the IUPAC
+/// `M` (`acmgt` → `ACMGT`). Already-normalised input is borrowed.
+pub fn normalize_motif(seq: &[u8]) -> Cow<'_, [u8]> {
+    if !seq.iter().any(|b| b.is_ascii_lowercase() || *b == b'U') {
+        return Cow::Borrowed(seq);
+    }
+    let mut out = Vec::with_capacity(seq.len());
+    for (i, &b) in seq.iter().enumerate() {
+        if b == b'm' && matches!(seq.get(i + 1), Some(b'A' | b'C' | b'G' | b'T' | b'U')) {
+            continue;
+        }
+        out.push(fold_u(b.to_ascii_uppercase()));
+    }
+    Cow::Owned(out)
+}
+
+fn fold_u(b: u8) -> u8 { if b == b'U' { b'T' } else { b } }
+
+/// What [`normalize_read`] puts in place of read symbols other than A/C/G/T/U.
+///
+/// It is not a nucleotide code, so it matches no motif position on the edlib,
+/// Myers or Aho–Corasick paths alike (edlib's equalities are symmetric and
+/// would otherwise let a read `N` match any base).
+pub const READ_MASK: u8 = b'.';
+
+/// Normalise a read for matching: uppercase, `U` read as `T`, and `N` or any
+/// other non-base symbol replaced by [`READ_MASK`].
+///
+/// Direct RNA FASTQ may spell uracil as `U`; BAM cannot encode it, so dorado
+/// writes `T` there. Already-normalised input is borrowed.
+pub fn normalize_read(seq: &[u8]) -> Cow<'_, [u8]> {
+    if seq.iter().all(|b| matches!(b, b'A' | b'C' | b'G' | b'T')) {
+        return Cow::Borrowed(seq);
+    }
+    Cow::Owned(seq.iter().map(|b| match fold_u(b.to_ascii_uppercase()) {
+        b @ (b'A' | b'C' | b'G' | b'T') => b,
+        _ => READ_MASK,
+    }).collect())
+}
+
+/// Longest stretch of `motif` without ambiguity codes.
+///
+/// Exact-match prefilters (Aho–Corasick) cannot see through wobble positions, so
+/// they index this seed instead of the whole motif.
+pub fn concrete_seed(motif: &[u8]) -> &[u8] {
+    motif
+        .split(|&b| is_ambiguous(b))
+        .max_by_key(|s| s.len())
+        .unwrap_or(motif)
+}
+
+/// A `MyersBuilder` that treats every IUPAC code in the pattern as ambiguous.
+pub fn myers_builder() -> &'static MyersBuilder {
+    static BUILDER: OnceLock<MyersBuilder> = OnceLock::new();
+    BUILDER.get_or_init(|| {
+        let mut b = MyersBuilder::new();
+        for (code, bases) in IUPAC_CODES {
+            b.ambig(*code, bases.iter());
+        }
+        b
+    })
+}
+
+/// IUPAC equalities for edlib's `additionalEqualities` (code ↔ each base it covers).
+///
+/// edlib applies them in both directions, so reads must go through
+/// [`normalize_read`] first to keep codes on the motif side only.
+pub fn edlib_equalities() -> &'static [EdlibEqualityPair] {
+    static PAIRS: OnceLock<Vec<EdlibEqualityPair>> = OnceLock::new();
+    PAIRS.get_or_init(|| {
+        let mut v = Vec::new();
+        for (code, bases) in IUPAC_CODES {
+            for b in bases.iter() {
+                v.push(EdlibEqualityPair { first: *code as _, second: *b as _ });
+            }
+        }
+        v
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bio::pattern_matching::myers::Myers;
+
+    #[test]
+    fn normalize_motif_strips_only_modification_prefixes() {
+        assert_eq!(&*normalize_motif(b"ACGT"), b"ACGT");
+        assert_eq!(&*normalize_motif(b"AmGCmCU"), b"AGCCT");
+        assert_eq!(&*normalize_motif(b"acmgt"), b"ACMGT");
+        assert_eq!(&*normalize_motif(b"ACm"), b"ACM");
+        assert_eq!(&*normalize_motif(b"uuu"), b"TTT");
+    }
+
+    #[test]
+    fn normalize_read_folds_u_and_masks_codes() {
+        assert!(matches!(normalize_read(b"ACGT"), Cow::Borrowed(_)));
+        assert_eq!(&*normalize_read(b"acgu"), b"ACGT");
+        assert_eq!(&*normalize_read(b"ACNRT"), b"AC..T");
+    }
+
+    #[test]
+    fn revcomp_and_seed_keep_codes() {
+        assert_eq!(revcomp(b"ACGV"), b"BCGT");
+        assert_eq!(revcomp(b"RYKM"), b"KMRY");
+        assert_eq!(concrete_seed(b"ACNGTTA"), b"GTTA");
+        assert_eq!(expand(b'V'), b"ACG");
+        assert_eq!(expand(b'u'), b"T");
+        assert!(is_ambiguous(b'n') && !is_ambiguous(b'A'));
+    }
+
+    fn myers_distance(motif: &[u8], read: &[u8], k: u8) -> Option<u8> {
+        let mut m: Myers<u64> = myers_builder().build_64(motif.iter().copied());
+        m.find_all(read, k).map(|(_, _, d)| d).min()
+    }
+
+    fn edlib_distance(motif: &[u8], read: &[u8], k: i32) -> Option<i32> {
+        crate::edwrap::locate_all(motif, read, k).first().map(|h| h.edits)
+    }
+
+    #[test]
+    fn edlib_and_myers_agree_on_codes() {
+        for (motif, read, want) in [
+            // a wobble in the motif matches each base it covers
+            (&b"ACGVACGTAC"[..], &b"TTACGAACGTACTT"[..], Some(0)),
+            (b"ACGVACGTAC", b"TTACGTACGTACTT", Some(1)),
+            (b"ACGNACGTAC", b"TTACGTACGTACTT", Some(0)),
+            // an N in the read matches nothing
+            (b"ACGTACGTAC", b"TTACGTNCGTACTT", Some(1)),
+            (b"ACGNACGTAC", b"TTACGNACGTACTT", Some(1)),
+        ] {
+            let read = normalize_read(read);
+            assert_eq!(myers_distance(motif, &read, 2).map(i32::from), want, "{}", String::from_utf8_lossy(motif));
+            assert_eq!(edlib_distance(motif, &read, 2), want, "{}", String::from_utf8_lossy(motif));
+        }
+    }
+}
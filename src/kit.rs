@@ -1,8 +1,10 @@
 
 //! Core types for kits, sequences and provenance.
 
+use serde::{Serialize, Serializer};
+
 /// Where a sequence definition came from.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub struct Provenance {
     pub source: &'static str,
     pub appendix: Option<&'static str>,
@@ -10,7 +12,8 @@ pub struct Provenance {
 }
 
 /// High-level category of sequence.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SeqKind {
     AdapterTop,
     AdapterBottom,
@@ -20,8 +23,21 @@ pub enum SeqKind {
     Flank,
 }
 
+impl SeqKind {
+    /// Snake-case name used in exports and reports (`adapter_top`, `barcode`, ...).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeqKind::AdapterTop => "adapter_top",
+            SeqKind::AdapterBottom => "adapter_bottom",
+            SeqKind::Primer => "primer",
+            SeqKind::Barcode => "barcode",
+            SeqKind::Flank => "flank",
+        }
+    }
+}
+
 /// A named nucleotide sequence with kind and provenance.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub struct SequenceRecord {
     pub name: &'static str,
     pub kind: SeqKind,
@@ -30,20 +46,25 @@ pub struct SequenceRecord {
 }
 
 /// Newtype for kit identifiers (e.g., "LSK114", "PCS114", "NBD114.24").
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct KitId(pub &'static str);
 
 /// Base sequencing chemistry.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BaseChemistry {
     Rapid,
     Ligation,
+    #[serde(rename = "pcr-cdna")]
     PCRcDNA,
     Amplicon,
+    /// Native RNA, sequenced 3' to 5'; reads end in the poly(A) tail and RT adapter.
+    #[serde(rename = "direct-rna")]
+    DirectRNA,
 }
 
 /// A kit bundles known adapters/primers and optional barcodes.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub struct Kit {
     pub id: KitId,
     pub description: &'static str,
@@ -51,8 +72,304 @@ pub struct Kit {
     pub chemistry: BaseChemistry,
     pub adapters_and_primers: &'static [SequenceRecord],
     pub barcodes: &'static [SequenceRecord],
+    /// Expected read architecture, 5' to 3' on the template strand.
+    pub layout: ReadLayout,
+}
+
+/// What a layout position expects to find.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutSlot {
+    /// Any adapter record (top or bottom strand).
+    Adapter,
+    Primer,
+    Flank,
+    /// Any of the kit's barcodes.
+    Barcode,
+    /// The library insert; not matched, only separates 5' from 3' elements.
+    Insert,
+    /// One specific record, by name.
+    Named(&'static str),
+}
+
+impl LayoutSlot {
+    /// Does a record with `name`/`kind` fill this slot?
+    pub fn accepts(&self, name: &str, kind: SeqKind) -> bool {
+        match self {
+            LayoutSlot::Adapter => matches!(kind, SeqKind::AdapterTop | SeqKind::AdapterBottom),
+            LayoutSlot::Primer => kind == SeqKind::Primer,
+            LayoutSlot::Flank => kind == SeqKind::Flank,
+            LayoutSlot::Barcode => kind == SeqKind::Barcode,
+            LayoutSlot::Insert => false,
+            LayoutSlot::Named(n) => *n == name,
+        }
+    }
+}
+
+/// One position of a [`ReadLayout`]: a slot, read forward or reverse-complemented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutElement {
+    pub slot: LayoutSlot,
+    pub rc: bool,
+}
+
+/// Forward-strand layout element.
+pub const fn fwd(slot: LayoutSlot) -> LayoutElement { LayoutElement { slot, rc: false } }
+/// Reverse-complement layout element.
+pub const fn rc(slot: LayoutSlot) -> LayoutElement { LayoutElement { slot, rc: true } }
+
+/// Expected read architecture, e.g.
+/// `adapter > flank > barcode > flank > insert > rc(flank) > rc(barcode)`.
+///
+/// Elements before [`LayoutSlot::Insert`] belong to the 5' end of the template
+/// strand and elements after it to the 3' end. Reads sequenced from the other
+/// strand show the layout reversed, with every element reverse-complemented;
+/// [`ReadLayout::match_hits`] tries both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadLayout(pub &'static [LayoutElement]);
+
+/// A motif hit on a read, as input to [`ReadLayout::match_hits`].
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutHit<'a> {
+    pub name: &'a str,
+    pub kind: SeqKind,
+    /// Hit is to the reverse complement of the motif.
+    pub rc: bool,
+    pub start: usize,
+    pub end: usize,
+    pub edits: u32,
+}
+
+/// A hit assigned to a layout element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutAssignment {
+    /// Index into the layout's elements.
+    pub element: usize,
+    /// Index into the hits passed to [`ReadLayout::match_hits`].
+    pub hit: usize,
+    /// The element lies before the insert in read order (i.e. at the read's 5' end).
+    pub five_prime: bool,
+}
+
+/// Best placement of a read's hits onto a layout.
+#[derive(Debug, Clone, Default)]
+pub struct LayoutMatch {
+    /// The read is from the reverse strand of the layout.
+    pub reverse: bool,
+    /// Number of matchable (non-insert) elements in the layout.
+    pub expected: usize,
+    /// Assignments in read order.
+    pub assigned: Vec<LayoutAssignment>,
+}
+
+impl LayoutMatch {
+    /// Fraction of layout elements found, in order (0.0–1.0).
+    pub fn score(&self) -> f32 {
+        if self.expected == 0 { 0.0 } else { self.assigned.len() as f32 / self.expected as f32 }
+    }
+}
+
+impl ReadLayout {
+    /// Generic layout for a chemistry, used when a kit does not declare one.
+    pub fn generic(chemistry: BaseChemistry, barcoded: bool) -> ReadLayout {
+        use LayoutSlot::*;
+        const LIG: &[LayoutElement] = &[fwd(Adapter), fwd(Insert), rc(Adapter)];
+        const LIG_BC: &[LayoutElement] = &[fwd(Adapter), fwd(Flank), fwd(Barcode), fwd(Flank), fwd(Insert),
+                                           rc(Flank), rc(Barcode), rc(Flank), rc(Adapter)];
+        const RAPID: &[LayoutElement] = &[fwd(Adapter), fwd(Insert)];
+        const RAPID_BC: &[LayoutElement] = &[fwd(Adapter), fwd(Flank), fwd(Barcode), fwd(Flank), fwd(Insert)];
+        const CDNA: &[LayoutElement] = &[fwd(Adapter), fwd(Primer), fwd(Insert), rc(Primer)];
+        const CDNA_BC: &[LayoutElement] = &[fwd(Adapter), fwd(Flank), fwd(Barcode), fwd(Primer), fwd(Insert),
+                                            rc(Primer), rc(Barcode), rc(Flank)];
+        const AMP: &[LayoutElement] = &[fwd(Adapter), fwd(Insert)];
+        const AMP_BC: &[LayoutElement] = &[fwd(Adapter), fwd(Barcode), fwd(Insert), rc(Barcode)];
+        const RNA: &[LayoutElement] = &[fwd(Insert), fwd(Adapter)];
+        ReadLayout(match (chemistry, barcoded) {
+            (BaseChemistry::Ligation, false) => LIG,
+            (BaseChemistry::Ligation, true) => LIG_BC,
+            (BaseChemistry::Rapid, false) => RAPID,
+            (BaseChemistry::Rapid, true) => RAPID_BC,
+            (BaseChemistry::PCRcDNA, false) => CDNA,
+            (BaseChemistry::PCRcDNA, true) => CDNA_BC,
+            (BaseChemistry::Amplicon, false) => AMP,
+            (BaseChemistry::Amplicon, true) => AMP_BC,
+            (BaseChemistry::DirectRNA, _) => RNA,
+        })
+    }
+
+    /// Layout elements.
+    pub fn elements(&self) -> &'static [LayoutElement] { self.0 }
+
+    /// Does the layout expect a reverse-complemented barcode, i.e. one at each read end?
+    pub fn barcoded_both_ends(&self) -> bool {
+        self.0.iter().any(|e| e.slot == LayoutSlot::Barcode && e.rc)
+    }
+
+    /// Place `hits` on a read of `read_len` bases onto the layout, in whichever
+    /// read orientation explains more of them.
+    ///
+    /// Each element takes at most one hit and hits must follow the layout
+    /// order along the read. Among placements with the same number of elements,
+    /// fewer total edits wins, then the tightest one: neighbouring elements on the
+    /// same side of the insert should abut, and the outermost elements should sit
+    /// at the read ends. Hits that do not fit are left unassigned.
+    pub fn match_hits(&self, hits: &[LayoutHit], read_len: usize) -> LayoutMatch {
+        let mut order: Vec<usize> = (0..hits.len()).collect();
+        order.sort_by_key(|&i| (hits[i].start, hits[i].end));
+
+        let ins = self.0.iter().position(|e| e.slot == LayoutSlot::Insert).unwrap_or(self.0.len());
+        // (element index, expected element as seen on the read, before insert in read order)
+        let fwd_elems: Vec<(usize, LayoutElement, bool)> = self.0.iter().enumerate()
+            .filter(|(_, e)| e.slot != LayoutSlot::Insert)
+            .map(|(i, e)| (i, *e, i < ins))
+            .collect();
+        let rev_elems: Vec<(usize, LayoutElement, bool)> = fwd_elems.iter().rev()
+            .map(|&(i, e, five)| (i, LayoutElement { slot: e.slot, rc: !e.rc }, !five))
+            .collect();
+
+        let (fm, fs) = align_layout(&fwd_elems, hits, &order, read_len);
+        let (rm, rs) = align_layout(&rev_elems, hits, &order, read_len);
+        let (reverse, assigned) = if rs > fs { (true, rm) } else { (false, fm) };
+        LayoutMatch { reverse, expected: fwd_elems.len(), assigned }
+    }
 }
 
+/// Chain score: (elements matched, -total edits, -total gap).
+type ChainScore = (usize, i64, i64);
+/// Best chain ending at a cell, and the (element, hit) cell it extends.
+type ChainCell = Option<(ChainScore, Option<(usize, usize)>)>;
+
+/// Order-preserving chaining of layout elements onto position-sorted hits.
+fn align_layout(
+    elems: &[(usize, LayoutElement, bool)],
+    hits: &[LayoutHit],
+    order: &[usize],
+    read_len: usize,
+) -> (Vec<LayoutAssignment>, ChainScore) {
+    let (m, n) = (elems.len(), order.len());
+    let fits = |e: &LayoutElement, h: &LayoutHit| e.rc == h.rc && e.slot.accepts(h.name, h.kind);
+    // best[i][j]: best chain whose last assignment is element i -> order[j]
+    let mut best: Vec<Vec<ChainCell>> = vec![vec![None; n]; m];
+    for i in 0..m {
+        for j in 0..n {
+            let h = &hits[order[j]];
+            if !fits(&elems[i].1, h) { continue; }
+            // Outermost 5' element: distance from the read start.
+            let lead = if elems[i].2 { h.start as i64 } else { 0 };
+            let mut cell: (ChainScore, Option<(usize, usize)>) = ((1, -(h.edits as i64), -lead), None);
+            for pi in 0..i {
+                for pj in 0..j {
+                    let Some((ps, _)) = best[pi][pj] else { continue };
+                    let p = &hits[order[pj]];
+                    let gap = if elems[pi].2 == elems[i].2 {
+                        (h.start as i64 - p.end as i64 - 1).abs()
+                    } else {
+                        0
+                    };
+                    let cand = (ps.0 + 1, ps.1 - h.edits as i64, ps.2 - gap);
+                    if cand > cell.0 { cell = (cand, Some((pi, pj))); }
+                }
+            }
+            best[i][j] = Some(cell);
+        }
+    }
+    // Close the chain: the outermost 3' element should reach the read end.
+    let mut end: Option<(ChainScore, (usize, usize))> = None;
+    for i in 0..m {
+        for j in 0..n {
+            let Some((s, _)) = best[i][j] else { continue };
+            let h = &hits[order[j]];
+            let trail = if elems[i].2 { 0 } else { (read_len as i64 - h.end as i64 - 1).max(0) };
+            let total = (s.0, s.1, s.2 - trail);
+            if end.map(|(e, _)| total > e).unwrap_or(true) { end = Some((total, (i, j))); }
+        }
+    }
+    let Some((score, mut at)) = end else { return (Vec::new(), (0, 0, 0)) };
+    let mut out = Vec::new();
+    loop {
+        let (i, j) = at;
+        out.push(LayoutAssignment { element: elems[i].0, hit: order[j], five_prime: elems[i].2 });
+        match best[i][j].and_then(|(_, prev)| prev) {
+            Some(p) => at = p,
+            None => break,
+        }
+    }
+    out.reverse();
+    (out, score)
+}
+
+impl std::fmt::Display for LayoutElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self.slot {
+            LayoutSlot::Adapter => "adapter",
+            LayoutSlot::Primer => "primer",
+            LayoutSlot::Flank => "flank",
+            LayoutSlot::Barcode => "barcode",
+            LayoutSlot::Insert => "insert",
+            LayoutSlot::Named(n) => n,
+        };
+        if self.rc { write!(f, "rc({})", s) } else { write!(f, "{}", s) }
+    }
+}
+
+impl std::fmt::Display for ReadLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", parts.join(" > "))
+    }
+}
+
+/// Serialised in the same `a > b > rc(c)` form that registry files use.
+impl Serialize for ReadLayout {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl std::str::FromStr for ReadLayout {
+    type Err = String;
+    /// Parse `adapter > flank > barcode > insert > rc(barcode)`. Tokens other
+    /// than `adapter`, `primer`, `flank`, `barcode` and `insert` name a specific
+    /// record. The parsed elements are leaked, like other runtime registry data.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut out = Vec::new();
+        for tok in s.split('>').map(str::trim) {
+            if tok.is_empty() { return Err(format!("empty element in layout '{}'", s)); }
+            let (inner, is_rc) = match tok.strip_prefix("rc(").and_then(|t| t.strip_suffix(')')) {
+                Some(t) => (t.trim(), true),
+                None => (tok, false),
+            };
+            let slot = match inner.to_ascii_lowercase().as_str() {
+                "adapter" => LayoutSlot::Adapter,
+                "primer" => LayoutSlot::Primer,
+                "flank" => LayoutSlot::Flank,
+                "barcode" => LayoutSlot::Barcode,
+                "insert" => LayoutSlot::Insert,
+                "" => return Err(format!("empty element in layout '{}'", s)),
+                _ => LayoutSlot::Named(Box::leak(inner.to_string().into_boxed_str())),
+            };
+            if slot == LayoutSlot::Insert && is_rc { return Err("rc(insert) is not meaningful".to_string()); }
+            out.push(LayoutElement { slot, rc: is_rc });
+        }
+        if out.iter().filter(|e| e.slot == LayoutSlot::Insert).count() != 1 {
+            return Err(format!("layout '{}' must contain exactly one insert", s));
+        }
+        Ok(ReadLayout(Box::leak(out.into_boxed_slice())))
+    }
+}
+
+
+impl BaseChemistry {
+    /// Short name, as accepted by `FromStr` and used in registry files (`ligation`, `pcr-cdna`, ...).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BaseChemistry::Rapid => "rapid",
+            BaseChemistry::Ligation => "ligation",
+            BaseChemistry::PCRcDNA => "pcr-cdna",
+            BaseChemistry::Amplicon => "amplicon",
+            BaseChemistry::DirectRNA => "direct-rna",
+        }
+    }
+}
 
 impl std::fmt::Display for BaseChemistry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -61,7 +378,125 @@ impl std::fmt::Display for BaseChemistry {
             BaseChemistry::Ligation => "ligation sequencing",
             BaseChemistry::PCRcDNA => "pcr-cdna sequencing",
             BaseChemistry::Amplicon => "amplicon sequencing",
+            BaseChemistry::DirectRNA => "direct rna sequencing",
         };
         write!(f, "{}", s)
     }
 }
+
+impl std::str::FromStr for BaseChemistry {
+    type Err = String;
+    /// Accepts the short names (`rapid`, `ligation`, `pcr-cdna`, `amplicon`, `direct-rna`)
+    /// as well as the `Display` labels (e.g. "ligation sequencing").
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let t = s.trim().to_ascii_lowercase();
+        match t.strip_suffix(" sequencing").unwrap_or(&t) {
+            "rapid" => Ok(Self::Rapid),
+            "ligation" => Ok(Self::Ligation),
+            "pcr-cdna" | "pcr_cdna" | "pcrcdna" | "cdna" => Ok(Self::PCRcDNA),
+            "amplicon" => Ok(Self::Amplicon),
+            "direct-rna" | "direct_rna" | "direct rna" | "directrna" | "drna" | "rna" => Ok(Self::DirectRNA),
+            other => Err(format!("Unknown chemistry: {}", other)),
+        }
+    }
+}
+
+impl std::str::FromStr for SeqKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "adapter_top" | "adaptertop" => Ok(Self::AdapterTop),
+            "adapter_bottom" | "adapterbottom" => Ok(Self::AdapterBottom),
+            "primer" => Ok(Self::Primer),
+            "barcode" => Ok(Self::Barcode),
+            "flank" => Ok(Self::Flank),
+            other => Err(format!("Unknown sequence kind: {}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(name: &'static str, kind: SeqKind, rc: bool, start: usize, end: usize) -> LayoutHit<'static> {
+        LayoutHit { name, kind, rc, start, end, edits: 0 }
+    }
+
+    fn layout(s: &str) -> ReadLayout { s.parse().unwrap() }
+
+    #[test]
+    fn layout_parses_and_displays() {
+        let text = "adapter > NB_flank_fwd > barcode > insert > rc(barcode) > rc(adapter)";
+        let l = layout(text);
+        assert_eq!(l.to_string(), text);
+        assert_eq!(l.elements()[1].slot, LayoutSlot::Named("NB_flank_fwd"));
+        assert!(l.elements()[4].rc);
+        assert!(l.barcoded_both_ends());
+        assert!(!layout("adapter > barcode > insert").barcoded_both_ends());
+        assert_eq!(layout(" Adapter>INSERT ").to_string(), "adapter > insert");
+        assert!("adapter > barcode".parse::<ReadLayout>().unwrap_err().contains("exactly one insert"));
+        assert!("insert > insert".parse::<ReadLayout>().is_err());
+        assert!("adapter > > insert".parse::<ReadLayout>().unwrap_err().contains("empty element"));
+        assert!("rc(insert)".parse::<ReadLayout>().is_err());
+    }
+
+    #[test]
+    fn match_hits_places_forward_reads() {
+        let l = layout("adapter > barcode > insert > rc(barcode) > rc(adapter)");
+        let hits = [
+            hit("BC01", SeqKind::Barcode, true, 900, 923),
+            hit("LA_top", SeqKind::AdapterTop, false, 0, 29),
+            hit("BC01", SeqKind::Barcode, false, 30, 53),
+            hit("LA_top", SeqKind::AdapterTop, true, 924, 953),
+        ];
+        let m = l.match_hits(&hits, 954);
+        assert!(!m.reverse);
+        assert_eq!(m.expected, 4);
+        assert_eq!(m.score(), 1.0);
+        let placed: Vec<(usize, usize, bool)> = m.assigned.iter().map(|a| (a.element, a.hit, a.five_prime)).collect();
+        assert_eq!(placed, [(0, 1, true), (1, 2, true), (3, 0, false), (4, 3, false)]);
+    }
+
+    #[test]
+    fn match_hits_reads_reverse_strand_layouts_backwards() {
+        // the template's 3' end, reverse-complemented, starts the read
+        let l = layout("adapter > barcode > insert");
+        let hits = [
+            hit("BC01", SeqKind::Barcode, true, 500, 523),
+            hit("LA_top", SeqKind::AdapterTop, true, 524, 553),
+        ];
+        let m = l.match_hits(&hits, 554);
+        assert!(m.reverse);
+        assert_eq!(m.assigned.len(), 2);
+        assert!(m.assigned.iter().all(|a| !a.five_prime));
+    }
+
+    #[test]
+    fn match_hits_prefers_hits_at_the_read_ends() {
+        let l = layout("adapter > insert");
+        let hits = [
+            hit("LA_top", SeqKind::AdapterTop, false, 400, 429),
+            hit("LA_top", SeqKind::AdapterTop, false, 2, 31),
+        ];
+        let m = l.match_hits(&hits, 1000);
+        assert_eq!(m.assigned.len(), 1);
+        assert_eq!(m.assigned[0].hit, 1);
+        // a reverse-complemented adapter is the reverse strand's 3' end
+        let m = l.match_hits(&[hit("LA_top", SeqKind::AdapterTop, true, 970, 999)], 1000);
+        assert!(m.reverse && m.assigned.len() == 1 && !m.assigned[0].five_prime);
+        // a record the layout has no slot for fills nothing
+        let m = l.match_hits(&[hit("BC01", SeqKind::Barcode, false, 0, 23)], 1000);
+        assert!(m.assigned.is_empty());
+        assert_eq!(m.score(), 0.0);
+    }
+
+    #[test]
+    fn generic_layouts_cover_every_chemistry() {
+        for c in [BaseChemistry::Rapid, BaseChemistry::Ligation, BaseChemistry::PCRcDNA, BaseChemistry::Amplicon] {
+            assert!(!ReadLayout::generic(c, false).barcoded_both_ends());
+            assert!(ReadLayout::generic(c, true).elements().iter().any(|e| e.slot == LayoutSlot::Barcode));
+        }
+        assert_eq!(ReadLayout::generic(BaseChemistry::DirectRNA, true).elements()[0].slot, LayoutSlot::Insert);
+    }
+}
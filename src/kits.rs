@@ -2,12 +2,57 @@
 //!
 //! This includes current **Kit 14** families and selected legacy kits to help
 //! interpret older datasets.
-use crate::kit::{Kit, KitId};
+//!
+//! Additional kits can be loaded at runtime from a TOML or JSON registry file
+//! with [`load_registry`] and merged into the active registry with
+//! [`install_registry`]; see [`registry`].
+use std::path::Path;
+use std::sync::RwLock;
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+
+use crate::kit::{fwd, rc, Kit, KitId, LayoutSlot::*, Provenance, ReadLayout, SeqKind, SequenceRecord};
 use crate::BaseChemistry;
 use crate::data::adapters::{RA_TOP, RTP, SSPII, CRTA};
 use crate::data::cdna_legacy::{SSP, VNP};
 use crate::data::{adapters::*, barcodes::*, legacy::*};
 
+// === Read layouts (5' → 3' on the template strand) ===
+
+/// `adapter > insert > rc(adapter)`
+const LIGATION_LAYOUT: ReadLayout = ReadLayout(&[fwd(Adapter), fwd(Insert), rc(Adapter)]);
+/// Native barcodes: `AAGGTTAA - barcode - CAGCACC` at the 5' end, and the NB
+/// reverse flanks around the reverse-complemented barcode at the 3' end.
+const NATIVE_BARCODE_LAYOUT: ReadLayout = ReadLayout(&[
+    fwd(Adapter), fwd(Named("NB_flank_fwd")), fwd(Barcode), rc(Named("NB_flank_rev5")), fwd(Insert),
+    fwd(Named("NB_flank_rev5")), rc(Barcode), fwd(Named("NB_flank_rev3")), rc(Adapter),
+]);
+const RAPID_BARCODE_LAYOUT: ReadLayout = ReadLayout(&[
+    fwd(Adapter), fwd(Named("RB_flank_left")), fwd(Barcode), fwd(Named("RB_flank_right")), fwd(Insert),
+]);
+const RAPID_PCR_BARCODE_LAYOUT: ReadLayout = ReadLayout(&[
+    fwd(Adapter), fwd(Named("RPB_flank")), fwd(Barcode), fwd(Insert), rc(Barcode), rc(Named("RPB_flank")),
+]);
+const SIXTEENS_LAYOUT: ReadLayout = ReadLayout(&[
+    fwd(Adapter), fwd(Named("16S_flank")), fwd(Barcode), fwd(Named("16S_fwd_target")), fwd(Insert),
+    rc(Named("16S_rev_target")), rc(Barcode), rc(Named("16S_flank")),
+]);
+/// Strand-switch / RT primers around the cDNA insert.
+const PCR_CDNA_LAYOUT: ReadLayout = ReadLayout(&[fwd(Adapter), fwd(Primer), fwd(Insert), rc(Primer)]);
+const PCR_CDNA_BARCODE_LAYOUT: ReadLayout = ReadLayout(&[
+    fwd(Adapter), fwd(Named("PCB_flank_top")), fwd(Barcode), fwd(Flank), fwd(Insert),
+    rc(Flank), rc(Barcode), rc(Named("PCB_flank_top")),
+]);
+/// PCR barcoding expansions: barcoded primers on both ends of the amplicon.
+const PCR_BARCODE_LAYOUT: ReadLayout = ReadLayout(&[
+    fwd(Adapter), fwd(Named("PCB_flank_top")), fwd(Barcode), fwd(Flank), fwd(Insert),
+    rc(Flank), rc(Barcode), rc(Named("PCB_flank_top")), rc(Adapter),
+]);
+/// Direct RNA reads are 5' → 3' RNA; the poly(A) tail and RTA sit at the 3' end.
+const DIRECT_RNA_LAYOUT: ReadLayout = ReadLayout(&[fwd(Insert), fwd(Named("polyA")), fwd(Named("RTA_bottom"))]);
+const AMPLICON_BARCODE_LAYOUT: ReadLayout = ReadLayout(&[fwd(Adapter), fwd(Barcode), fwd(Insert), rc(Barcode)]);
+
 /// const `KITS` — auto‑generated rustdoc.
 pub const KITS: &[Kit] = &[
     // Current ligation chemistry (Kit 14)
@@ -18,6 +63,7 @@ pub const KITS: &[Kit] = &[
         chemistry: BaseChemistry::Ligation,
         legacy: false,
         barcodes: &[],
+        layout: LIGATION_LAYOUT,
     },
 
 
@@ -29,6 +75,7 @@ Kit{
     chemistry: BaseChemistry::Rapid,
         legacy: false,
         barcodes: &[],
+        layout: PCR_CDNA_LAYOUT,
 },
 
 // PCR‑cDNA Sequencing Kit V14
@@ -39,6 +86,27 @@ Kit{
     chemistry: BaseChemistry::PCRcDNA,
         legacy: false,
         barcodes: &[],
+        layout: PCR_CDNA_LAYOUT,
+},
+
+// Direct RNA
+Kit{
+    id: KitId("RNA004"),
+    description: "Direct RNA Sequencing Kit (SQK-RNA004). RTA ligated to the poly(A) tail; RLA motor adapter.",
+    adapters_and_primers: &[RTA_TOP, RTA_BOTTOM, POLY_A],
+    chemistry: BaseChemistry::DirectRNA,
+        legacy: false,
+        barcodes: &[],
+        layout: DIRECT_RNA_LAYOUT,
+},
+Kit{
+    id: KitId("RNA002"),
+    description: "Direct RNA Sequencing Kit (SQK-RNA002, legacy). RTA ligated to the poly(A) tail; RMX motor adapter.",
+    adapters_and_primers: &[RTA_TOP, RTA_BOTTOM, POLY_A],
+    chemistry: BaseChemistry::DirectRNA,
+        legacy: true,
+        barcodes: &[],
+        layout: DIRECT_RNA_LAYOUT,
 },
 
     Kit{
@@ -48,6 +116,7 @@ Kit{
         chemistry: BaseChemistry::Ligation,
         legacy: false,
         barcodes: &[],
+        layout: LIGATION_LAYOUT,
     },
 
     // Native barcoding (Kit 14)
@@ -57,7 +126,8 @@ Kit{
         adapters_and_primers: &[NA_TOP, NA_BOTTOM, NB_FLANK_FWD, NB_FLANK_REV5, NB_FLANK_REV3],
         chemistry: BaseChemistry::Ligation,
         legacy: false,
-        barcodes: &NB_BARCODES_24,
+        barcodes: NB_BARCODES_24,
+        layout: NATIVE_BARCODE_LAYOUT,
     },
     Kit{
         id: KitId("NBD114.96"),
@@ -66,6 +136,7 @@ Kit{
         chemistry: BaseChemistry::Ligation,
         legacy: false,
         barcodes: NB_BARCODES,
+        layout: NATIVE_BARCODE_LAYOUT,
     },
 
     // Rapid barcoding (Kit 14)
@@ -75,7 +146,8 @@ Kit{
         adapters_and_primers: &[RA_TOP, RB_FLANK_LEFT, RB_FLANK_RIGHT],
         chemistry: BaseChemistry::Rapid,
         legacy: false,
-        barcodes: &SHARED_1_TO_24,
+        barcodes: SHARED_1_TO_24,
+        layout: RAPID_BARCODE_LAYOUT,
     },
     Kit{
         id: KitId("RBK114.96"),
@@ -83,7 +155,8 @@ Kit{
         adapters_and_primers: &[RA_TOP, RB_FLANK_LEFT, RB_FLANK_RIGHT],
         chemistry: BaseChemistry::Rapid,
         legacy: false,
-        barcodes: &SHARED_BARCODE_SET,
+        barcodes: SHARED_BARCODE_SET,
+        layout: RAPID_BARCODE_LAYOUT,
     },
 
     // PCR‑cDNA barcoding (Kit 14)
@@ -96,7 +169,8 @@ Kit{
     adapters_and_primers: &[RA_TOP, RTP, CRTA, SSP, VNP, PCB_FLANK_TOP, PCB_FLANK_BOT_A, PCB_FLANK_BOT_B],
     chemistry: BaseChemistry::Rapid,
         legacy: false,
-        barcodes: &SHARED_1_TO_24,
+        barcodes: SHARED_1_TO_24,
+        layout: PCR_CDNA_BARCODE_LAYOUT,
 },
 Kit{
         id: KitId("PCB114.24"),
@@ -104,7 +178,8 @@ Kit{
         adapters_and_primers: &[RA_TOP, RTP, SSPII, CRTA, CPRM_FWD, CPRM_REV, PCB_FLANK_TOP, PCB_FLANK_BOT_A, PCB_FLANK_BOT_B],
         chemistry: BaseChemistry::PCRcDNA,
         legacy: false,
-        barcodes: &SHARED_1_TO_24,
+        barcodes: SHARED_1_TO_24,
+        layout: PCR_CDNA_BARCODE_LAYOUT,
     },
 
     // Rapid PCR barcoding (Kit 14)
@@ -114,7 +189,8 @@ Kit{
         adapters_and_primers: &[RA_TOP, RPB_FLANK],
         chemistry: BaseChemistry::Rapid,
         legacy: false,
-        barcodes: &SHARED_1_TO_24,
+        barcodes: SHARED_1_TO_24,
+        layout: RAPID_PCR_BARCODE_LAYOUT,
     },
 
     // 16S barcoding (Kit 14)
@@ -129,7 +205,8 @@ KitId("16S114.24"),
         adapters_and_primers: &[RA_TOP, SIXTEENS_FLANK, SIXTEENS_FWD_TARGET, SIXTEENS_REV_TARGET],
         chemistry: BaseChemistry::Rapid,
         legacy: false,
-        barcodes: &SHARED_1_TO_24,
+        barcodes: SHARED_1_TO_24,
+        layout: SIXTEENS_LAYOUT,
     },
 
     // Expansions (BC01–96)
@@ -139,7 +216,8 @@ KitId("16S114.24"),
         adapters_and_primers: &[PCB_FLANK_TOP, PCB_FLANK_BOT_A, PCB_FLANK_BOT_B],
         chemistry: BaseChemistry::Rapid,
         legacy: false,
-        barcodes: &SHARED_1_TO_12,
+        barcodes: SHARED_1_TO_12,
+        layout: PCR_BARCODE_LAYOUT,
     },
     Kit{
         id: KitId("PBC096"),
@@ -147,7 +225,8 @@ KitId("16S114.24"),
         adapters_and_primers: &[PCB_FLANK_TOP, PCB_FLANK_BOT_A, PCB_FLANK_BOT_B],
         chemistry: BaseChemistry::Rapid,
         legacy: false,
-        barcodes: &SHARED_BARCODE_SET,
+        barcodes: SHARED_BARCODE_SET,
+        layout: PCR_BARCODE_LAYOUT,
     },
 
     // Legacy rapid kits (for historic data)
@@ -156,16 +235,18 @@ KitId("16S114.24"),
         description: "Rapid Barcoding Kit (RBK004): RB01–RB12; RB flank.",
         adapters_and_primers: &[RA_TOP, RB_FLANK_LEFT, RB_FLANK_RIGHT],
         chemistry: BaseChemistry::Rapid,
-        legacy: false,
-        barcodes: &SHARED_1_TO_12,
+        legacy: true,
+        barcodes: SHARED_1_TO_12,
+        layout: RAPID_BARCODE_LAYOUT,
     },
     Kit{
         id: KitId("RBK110.96"),
         description: "Rapid Barcoding Kit 96 (RBK110.96): RB01–RB96; RB flank.",
         adapters_and_primers: &[RA_TOP, RB_FLANK_LEFT, RB_FLANK_RIGHT],
         chemistry: BaseChemistry::Rapid,
-        legacy: false,
-        barcodes: &SHARED_BARCODE_SET,
+        legacy: true,
+        barcodes: SHARED_BARCODE_SET,
+        layout: RAPID_BARCODE_LAYOUT,
     },
 
     // Legacy ligation adapters (informational)
@@ -174,24 +255,83 @@ KitId("16S114.24"),
         description: "Ligation Sequencing Kit LSK109 (legacy). Y‑adapter trunk per Porechop forks.",
         adapters_and_primers: &[NSK007_Y_TOP_TRUNK, NSK007_Y_BOTTOM],
         chemistry: BaseChemistry::Ligation,
-        legacy: false,
+        legacy: true,
         barcodes: &[],
+        layout: LIGATION_LAYOUT,
     },
     Kit{
         id: KitId("LSK108"),
         description: "Ligation Sequencing Kit LSK108 (legacy). Y‑adapter trunk per Porechop forks.",
         adapters_and_primers: &[NSK007_Y_TOP_TRUNK, NSK007_Y_BOTTOM],
         chemistry: BaseChemistry::Ligation,
-        legacy: false,
+        legacy: true,
         barcodes: &[],
+        layout: LIGATION_LAYOUT,
     },
     Kit{
         id: KitId("LSK308"),
         description: "1D^2 kit LSK308 (legacy). 1D^2 adapter fragments per Porechop forks.",
         adapters_and_primers: &[LSK308_1D2_TOP, LSK308_1D2_BOTTOM],
         chemistry: BaseChemistry::Ligation,
-        legacy: false,
+        legacy: true,
         barcodes: &[],
+        layout: LIGATION_LAYOUT,
+    },
+
+    // Legacy barcoding kits (Kit 9/10), for historic data
+    Kit{
+        id: KitId("NBD104"),
+        description: "Native Barcoding Expansion 1–12 (EXP‑NBD104, Kit 9/10): NB01–NB12; NB flanks. Use with LSK109.",
+        adapters_and_primers: &[NB_FLANK_FWD, NB_FLANK_REV5, NB_FLANK_REV3],
+        chemistry: BaseChemistry::Ligation,
+        legacy: true,
+        barcodes: NB_BARCODES_1_TO_12,
+        layout: NATIVE_BARCODE_LAYOUT,
+    },
+    Kit{
+        id: KitId("EXP-NBD114"),
+        description: "Native Barcoding Expansion 13–24 (EXP‑NBD114, Kit 9/10): NB13–NB24; NB flanks. Use with LSK109.",
+        adapters_and_primers: &[NB_FLANK_FWD, NB_FLANK_REV5, NB_FLANK_REV3],
+        chemistry: BaseChemistry::Ligation,
+        legacy: true,
+        barcodes: NB_BARCODES_13_TO_24,
+        layout: NATIVE_BARCODE_LAYOUT,
+    },
+    Kit{
+        id: KitId("PBK004"),
+        description: "PCR Barcoding Kit (SQK‑PBK004): BC01–BC12 on PCR flanks; RAP attached to the amplicons.",
+        adapters_and_primers: &[RA_TOP, PCB_FLANK_TOP, PCB_FLANK_BOT_A],
+        chemistry: BaseChemistry::Rapid,
+        legacy: true,
+        barcodes: SHARED_1_TO_12,
+        layout: PCR_CDNA_BARCODE_LAYOUT,
+    },
+    Kit{
+        id: KitId("RPB004"),
+        description: "Rapid PCR Barcoding Kit (SQK‑RPB004): RLB01–RLB12; RPB flank; RAP.",
+        adapters_and_primers: &[RA_TOP, RPB_FLANK],
+        chemistry: BaseChemistry::Rapid,
+        legacy: true,
+        barcodes: SHARED_1_TO_12,
+        layout: RAPID_PCR_BARCODE_LAYOUT,
+    },
+    Kit{
+        id: KitId("PCB109"),
+        description: "PCR‑cDNA Barcoding Kit (SQK‑PCB109): SSP/VNP (pychopper) + BP01–BP12 on PCB flanks; RAP.",
+        adapters_and_primers: &[RA_TOP, SSP, VNP, PCB_FLANK_TOP, PCB_FLANK_BOT_A, PCB_FLANK_BOT_B],
+        chemistry: BaseChemistry::PCRcDNA,
+        legacy: true,
+        barcodes: SHARED_1_TO_12,
+        layout: PCR_CDNA_BARCODE_LAYOUT,
+    },
+    Kit{
+        id: KitId("LWB001"),
+        description: "Low Input by PCR Barcoding Kit (SQK‑LWB001): BC01–BC12 on PCR flanks; RAP. Ligated PCR adapters not modelled.",
+        adapters_and_primers: &[RA_TOP, PCB_FLANK_TOP, PCB_FLANK_BOT_A],
+        chemistry: BaseChemistry::Rapid,
+        legacy: true,
+        barcodes: SHARED_1_TO_12,
+        layout: PCR_CDNA_BARCODE_LAYOUT,
     },
 
 
@@ -203,6 +343,427 @@ Kit{
     adapters_and_primers: &[RA_TOP],
     chemistry: BaseChemistry::Amplicon,
         legacy: false,
-        barcodes: &SHARED_1_TO_24,
+        barcodes: SHARED_1_TO_24,
+        layout: AMPLICON_BARCODE_LAYOUT,
 },
 ];
+
+/// Active registry: `KITS` merged with any kits installed at runtime.
+static REGISTRY: RwLock<Option<&'static [Kit]>> = RwLock::new(None);
+
+/// Return the active registry (built-in kits plus any installed custom kits).
+pub fn registry() -> &'static [Kit] {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).unwrap_or(KITS)
+}
+
+/// Merge `custom` kits into the active registry.
+///
+/// A custom kit whose id matches an existing kit replaces it in place; new ids
+/// are appended in file order.
+///
+/// Composite kits built from the previous registry are dropped, so later
+/// [`resolve_kit_expr`] calls see the new definitions.
+pub fn install_registry(custom: Vec<Kit>) {
+    let mut guard = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    let merged = merge_registry(guard.unwrap_or(KITS), &custom);
+    *guard = Some(Box::leak(merged.into_boxed_slice()));
+    COMPOSITES.write().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// `base` with `custom` merged in as [`install_registry`] does: matching ids are replaced
+/// in place, new ids appended in order.
+pub fn merge_registry(base: &[Kit], custom: &[Kit]) -> Vec<Kit> {
+    let mut merged: Vec<Kit> = base.to_vec();
+    for k in custom {
+        match merged.iter_mut().find(|m| m.id == k.id) {
+            Some(slot) => *slot = *k,
+            None => merged.push(*k),
+        }
+    }
+    merged
+}
+
+/// On-disk layout of a registry file.
+///
+/// TOML:
+/// ```toml
+/// [[kits]]
+/// id = "MYLAB.12"
+/// description = "In-house barcodes on the ligation adapter"
+/// chemistry = "ligation"
+/// adapters_and_primers = [ { name = "LA_top" }, { name = "LA_bottom" } ]
+/// barcodes = [ { name = "ML01", sequence = "ACGTACGTACGTACGTACGTACGT" } ]
+/// layout = "adapter > barcode > insert"   # optional
+/// ```
+///
+/// The JSON form uses the same field names (`{"kits": [ ... ]}`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryFile {
+    #[serde(default)]
+    pub kits: Vec<KitDef>,
+}
+
+/// A kit definition as read from a registry file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KitDef {
+    pub id: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub legacy: bool,
+    /// Parsed with `BaseChemistry::from_str` (e.g. "ligation", "pcr-cdna").
+    pub chemistry: String,
+    #[serde(default)]
+    pub adapters_and_primers: Vec<SequenceDef>,
+    #[serde(default)]
+    pub barcodes: Vec<SequenceDef>,
+    /// Read layout, e.g. "adapter > flank > barcode > flank > insert"; defaults to
+    /// `ReadLayout::generic` for the chemistry.
+    #[serde(default)]
+    pub layout: Option<String>,
+}
+
+/// A sequence definition in a registry file.
+///
+/// If `sequence` is omitted, `name` must refer to a record already present in
+/// the active registry (e.g. `"RA_top"`), which is reused as is.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SequenceDef {
+    pub name: String,
+    /// Parsed with `SeqKind::from_str`; defaults to `barcode` in `barcodes`.
+    pub kind: Option<String>,
+    pub sequence: Option<String>,
+    pub source: Option<String>,
+    pub appendix: Option<String>,
+    pub notes: Option<String>,
+}
+
+fn leak(s: String) -> &'static str { Box::leak(s.into_boxed_str()) }
+
+fn find_record(name: &str) -> Option<SequenceRecord> {
+    registry().iter()
+        .flat_map(|k| k.adapters_and_primers.iter().chain(k.barcodes.iter()))
+        .find(|r| r.name == name)
+        .copied()
+}
+
+fn record_from_def(d: &SequenceDef, default_kind: Option<SeqKind>, source: &'static str) -> anyhow::Result<SequenceRecord> {
+    let Some(seq) = d.sequence.as_deref().map(str::trim) else {
+        return find_record(&d.name)
+            .with_context(|| format!("sequence '{}' has no `sequence` and is not a known record", d.name));
+    };
+    if seq.is_empty() { bail!("sequence '{}' is empty", d.name); }
+    let kind = match (&d.kind, default_kind) {
+        (Some(k), _) => k.parse::<SeqKind>().map_err(anyhow::Error::msg)?,
+        (None, Some(k)) => k,
+        (None, None) => bail!("sequence '{}' needs a `kind`", d.name),
+    };
+    Ok(SequenceRecord {
+        name: leak(d.name.clone()),
+        kind,
+        sequence: leak(seq.to_string()),
+        provenance: Provenance {
+            source: d.source.clone().map(leak).unwrap_or(source),
+            appendix: d.appendix.clone().map(leak),
+            notes: d.notes.clone().map(leak),
+        },
+    })
+}
+
+fn kit_from_def(d: &KitDef, source: &'static str) -> anyhow::Result<Kit> {
+    let id = d.id.trim();
+    if id.is_empty() { bail!("kit with empty id"); }
+    let chemistry = d.chemistry.parse::<BaseChemistry>().map_err(anyhow::Error::msg)?;
+    let aps = d.adapters_and_primers.iter()
+        .map(|s| record_from_def(s, None, source))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let bcs = d.barcodes.iter()
+        .map(|s| record_from_def(s, Some(SeqKind::Barcode), source))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let layout = match &d.layout {
+        Some(l) => l.parse::<ReadLayout>().map_err(anyhow::Error::msg)?,
+        None => ReadLayout::generic(chemistry, !bcs.is_empty()),
+    };
+    Ok(Kit {
+        id: KitId(leak(id.to_string())),
+        description: leak(d.description.clone()),
+        legacy: d.legacy,
+        chemistry,
+        adapters_and_primers: Box::leak(aps.into_boxed_slice()),
+        barcodes: Box::leak(bcs.into_boxed_slice()),
+        layout,
+    })
+}
+
+/// Parse kit definitions from a TOML (`.toml`) or JSON (`.json`) registry file.
+///
+/// Files with another extension are tried as JSON, then TOML. The returned
+/// kits are not yet visible to lookups; pass them to [`install_registry`].
+pub fn load_registry<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Kit>> {
+    let p = path.as_ref();
+    let text = std::fs::read_to_string(p)
+        .with_context(|| format!("reading registry {}", p.display()))?;
+    let ext = p.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    let file: RegistryFile = match ext.as_str() {
+        "toml" => toml::from_str(&text)?,
+        "json" => serde_json::from_str(&text)?,
+        _ => match serde_json::from_str(&text) {
+            Ok(f) => f,
+            Err(_) => toml::from_str(&text)?,
+        },
+    };
+    let source = leak(p.display().to_string());
+    file.kits.iter()
+        .map(|d| kit_from_def(d, source).with_context(|| format!("kit '{}' in {}", d.id, p.display())))
+        .collect()
+}
+
+/// An expansion (add-on) kit and the base chemistries it can be combined with.
+#[derive(Debug, Clone, Copy)]
+pub struct Expansion {
+    pub id: &'static str,
+    pub bases: &'static [BaseChemistry],
+}
+
+/// Which expansion kits pair with which base chemistries in a composite
+/// expression such as `LSK114+NBD114.24`. Kits not listed here are base kits.
+pub const COMPATIBILITY: &[Expansion] = &[
+    // Native barcodes are ligated onto end-prepped DNA before the NA adapter.
+    Expansion { id: "NBD114.24", bases: &[BaseChemistry::Ligation] },
+    Expansion { id: "NBD114.96", bases: &[BaseChemistry::Ligation] },
+    Expansion { id: "NBD104", bases: &[BaseChemistry::Ligation] },
+    Expansion { id: "EXP-NBD114", bases: &[BaseChemistry::Ligation] },
+    // PCR barcodes are added by PCR, then sequenced with a ligation or PCR-cDNA kit.
+    Expansion { id: "PBC001", bases: &[BaseChemistry::Ligation, BaseChemistry::PCRcDNA] },
+    Expansion { id: "PBC096", bases: &[BaseChemistry::Ligation, BaseChemistry::PCRcDNA] },
+];
+
+/// Composite kits built from the active registry, so repeated lookups reuse one
+/// allocation; cleared by [`install_registry`].
+static COMPOSITES: RwLock<Vec<&'static Kit>> = RwLock::new(Vec::new());
+
+/// Resolve a kit expression: a single kit id, or a base kit combined with one or
+/// more expansion kits using `+` (e.g. `LSK114+NBD114.24`, `PCS114+PBC096`).
+/// Each term goes through [`KitResolver`](crate::resolver::KitResolver), so ONT
+/// product codes such as `SQK-LSK114+SQK-NBD114-24` are accepted.
+///
+/// Composite kits take their chemistry from the base kit, their read layout from
+/// the expansions (which all have to share one; the base kit's layout does not
+/// describe barcoded reads), and the union of all motifs, de-duplicated by name
+/// and sequence. Expressions with no base kit, two base kits, an expansion that
+/// does not pair with the base chemistry (see [`COMPATIBILITY`]) or expansions
+/// with different layouts (`LSK114+NBD114.24+PBC096`) are rejected.
+pub fn resolve_kit_expr(expr: &str) -> anyhow::Result<&'static Kit> {
+    let resolver = crate::resolver::KitResolver::new();
+    let mut kits: Vec<&'static Kit> = Vec::new();
+    for t in expr.split('+').map(str::trim) {
+        if t.is_empty() { bail!("empty kit in expression '{}'", expr); }
+        let k = resolver.resolve(t)?;
+        if !kits.iter().any(|o| o.id == k.id) { kits.push(k); }
+    }
+    if kits.len() == 1 {
+        return Ok(kits[0]);
+    }
+
+    let expansion_of = |k: &Kit| COMPATIBILITY.iter().find(|e| e.id == k.id.0);
+    let bases: Vec<&Kit> = kits.iter().copied().filter(|k| expansion_of(k).is_none()).collect();
+    let base = match bases.as_slice() {
+        [b] => *b,
+        [] => bail!("'{}' has no base kit; combine expansions with a sequencing kit (e.g. LSK114+{})", expr, kits[0].id.0),
+        _ => bail!("'{}' combines base kits {}; use one base kit plus expansions",
+            expr, bases.iter().map(|k| k.id.0).collect::<Vec<_>>().join(" and ")),
+    };
+    let mut ordered: Vec<&Kit> = vec![base];
+    for k in &kits {
+        let Some(exp) = expansion_of(k) else { continue };
+        if !exp.bases.contains(&base.chemistry) {
+            let allowed: Vec<String> = exp.bases.iter().map(|c| c.to_string()).collect();
+            bail!("{} cannot be combined with {} ({}); it pairs with {}",
+                k.id.0, base.id.0, base.chemistry, allowed.join(" or "));
+        }
+        ordered.push(k);
+    }
+    let layout = ordered[1..].iter().map(|k| k.layout).next().unwrap_or(base.layout);
+    if let Some(other) = ordered[1..].iter().find(|k| k.layout != layout) {
+        bail!("'{}' combines expansions with different read layouts ({} and {}); use one barcoding scheme",
+            expr, ordered[1].id.0, other.id.0);
+    }
+
+    let id = ordered.iter().map(|k| k.id.0).collect::<Vec<_>>().join("+");
+    if let Some(k) = COMPOSITES.read().unwrap_or_else(|e| e.into_inner()).iter().find(|k| k.id.0 == id) {
+        return Ok(k);
+    }
+    let union = |pick: fn(&Kit) -> &'static [SequenceRecord]| {
+        let mut out: Vec<SequenceRecord> = Vec::new();
+        for k in &ordered {
+            for r in pick(k) {
+                if !out.iter().any(|o| o.name == r.name && o.sequence == r.sequence) { out.push(*r); }
+            }
+        }
+        &*Box::leak(out.into_boxed_slice())
+    };
+    let kit: &'static Kit = Box::leak(Box::new(Kit {
+        id: KitId(leak(id)),
+        description: leak(ordered.iter().map(|k| k.description).collect::<Vec<_>>().join(" + ")),
+        legacy: ordered.iter().any(|k| k.legacy),
+        chemistry: base.chemistry,
+        adapters_and_primers: union(|k| k.adapters_and_primers),
+        barcodes: union(|k| k.barcodes),
+        layout,
+    }));
+    COMPOSITES.write().unwrap_or_else(|e| e.into_inner()).push(kit);
+    Ok(kit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn err(expr: &str) -> String { resolve_kit_expr(expr).unwrap_err().to_string() }
+
+    /// Write `text` to a fresh file under the temp dir and return its path.
+    fn temp_registry(name: &str, text: &str) -> std::path::PathBuf {
+        let p = std::env::temp_dir().join(format!("porkchop-{}-{}", std::process::id(), name));
+        std::fs::write(&p, text).unwrap();
+        p
+    }
+
+    fn load(name: &str, text: &str) -> anyhow::Result<Vec<Kit>> {
+        let p = temp_registry(name, text);
+        let kits = load_registry(&p);
+        std::fs::remove_file(&p).unwrap();
+        kits
+    }
+
+    #[test]
+    fn load_registry_reads_toml_and_reuses_known_records() {
+        let kits = load("lab.toml", r#"
+            [[kits]]
+            id = " MYLAB.12 "
+            description = "In-house barcodes"
+            chemistry = "ligation"
+            adapters_and_primers = [ { name = "LA_top" }, { name = "my_primer", kind = "primer", sequence = "ACGTTGCA" } ]
+            barcodes = [ { name = "ML01", sequence = " ACGTACGTACGT ", notes = "lot 7" } ]
+            layout = "adapter > barcode > insert"
+        "#).unwrap();
+        let [k] = kits.as_slice() else { panic!("{:?}", kits) };
+        assert_eq!(k.id.0, "MYLAB.12");
+        assert_eq!(k.chemistry, BaseChemistry::Ligation);
+        let la = KITS.iter().flat_map(|k| k.adapters_and_primers).find(|r| r.name == "LA_top").unwrap();
+        assert_eq!(k.adapters_and_primers[0].sequence, la.sequence);
+        assert_eq!(k.adapters_and_primers[1].kind, SeqKind::Primer);
+        assert_eq!(k.barcodes[0].kind, SeqKind::Barcode);
+        assert_eq!(k.barcodes[0].sequence, "ACGTACGTACGT");
+        assert_eq!(k.barcodes[0].provenance.notes, Some("lot 7"));
+        assert!(k.barcodes[0].provenance.source.ends_with("lab.toml"));
+        assert_eq!(k.layout.elements().len(), 3);
+    }
+
+    #[test]
+    fn load_registry_reads_json_and_sniffs_other_extensions() {
+        let json = r#"{"kits": [{"id": "J1", "chemistry": "rapid", "barcodes": [{"name": "B1", "sequence": "ACGT"}]}]}"#;
+        let k = load("lab.json", json).unwrap();
+        assert_eq!(k[0].chemistry, BaseChemistry::Rapid);
+        // no layout given: the generic barcoded layout of the chemistry
+        assert_eq!(k[0].layout, ReadLayout::generic(BaseChemistry::Rapid, true));
+        assert_eq!(load("lab.reg", json).unwrap()[0].id.0, "J1");
+        let toml = "[[kits]]\nid = \"T1\"\nchemistry = \"pcr-cdna\"\n";
+        assert_eq!(load("lab.txt", toml).unwrap()[0].chemistry, BaseChemistry::PCRcDNA);
+    }
+
+    #[test]
+    fn load_registry_errors_name_the_kit() {
+        let bad = |body: &str| {
+            let e = load("bad.toml", &format!("[[kits]]\nid = \"BAD1\"\n{}", body)).unwrap_err();
+            format!("{:#}", e)
+        };
+        assert!(bad("chemistry = \"nanopore\"").contains("Unknown chemistry"));
+        let e = bad("chemistry = \"ligation\"\nadapters_and_primers = [ { name = \"no_such_record\" } ]");
+        assert!(e.contains("kit 'BAD1'") && e.contains("no_such_record"), "{}", e);
+        assert!(bad("chemistry = \"ligation\"\nadapters_and_primers = [ { name = \"x\", sequence = \"ACGT\" } ]").contains("needs a `kind`"));
+        assert!(bad("chemistry = \"ligation\"\nbarcodes = [ { name = \"x\", sequence = \" \" } ]").contains("is empty"));
+        assert!(bad("chemistry = \"ligation\"\nlayout = \"adapter > barcode\"").contains("exactly one insert"));
+        assert!(load("bad.toml", "[[kits]]\nid = \" \"\nchemistry = \"rapid\"\n").is_err());
+        assert!(load_registry("/nonexistent/porkchop.toml").is_err());
+    }
+
+    #[test]
+    fn merge_registry_replaces_in_place_and_appends() {
+        let lsk = *KITS.iter().find(|k| k.id.0 == "LSK114").unwrap();
+        let replaced = Kit { description: "replaced", ..lsk };
+        let added = Kit { id: KitId("NEW1"), ..lsk };
+        let merged = merge_registry(KITS, &[added, replaced]);
+        assert_eq!(merged.len(), KITS.len() + 1);
+        let pos = KITS.iter().position(|k| k.id.0 == "LSK114").unwrap();
+        assert_eq!(merged[pos].description, "replaced");
+        assert_eq!(merged.last().unwrap().id.0, "NEW1");
+    }
+
+    #[test]
+    fn composite_takes_base_chemistry_expansion_layout_and_motif_union() {
+        let k = resolve_kit_expr("LSK114+NBD114.24").unwrap();
+        let nbd = KITS.iter().find(|k| k.id.0 == "NBD114.24").unwrap();
+        assert_eq!(k.id.0, "LSK114+NBD114.24");
+        assert_eq!(k.chemistry, BaseChemistry::Ligation);
+        assert_eq!(k.layout, nbd.layout);
+        assert_eq!(k.barcodes.len(), nbd.barcodes.len());
+        assert!(k.adapters_and_primers.iter().any(|r| r.name == "LA_top"));
+        // product codes resolve per term; the base kit always comes first
+        assert_eq!(resolve_kit_expr("SQK-NBD114-24 + sqk-lsk114").unwrap().id.0, "LSK114+NBD114.24");
+        // a single term is the kit itself
+        assert_eq!(resolve_kit_expr("NBD114.24").unwrap().id, nbd.id);
+        assert_eq!(resolve_kit_expr("LSK109+EXP-NBD114").unwrap().id.0, "LSK109+EXP-NBD114");
+    }
+
+    #[test]
+    fn composite_errors() {
+        assert!(err("LSK114+").contains("empty kit"));
+        assert!(err("NBD114.24+PBC096").contains("no base kit"));
+        assert!(err("LSK114+PCS114").contains("combines base kits"));
+        assert!(err("RBK114.24+PBC096").contains("cannot be combined"));
+        assert!(err("LSK114+NBD114.24+PBC096").contains("different read layouts"));
+        assert!(err("LSK114+NBD999").contains("Unknown kit: NBD999"));
+    }
+
+    #[test]
+    fn install_registry_drops_stale_composites() {
+        let base = |adapter: &'static str| Kit {
+            id: KitId("TEST-COMPOSITE-BASE"),
+            adapters_and_primers: Box::leak(Box::new([SequenceRecord {
+                name: adapter, kind: SeqKind::AdapterTop, sequence: "GATTACAGATTACAGATTACA",
+                provenance: Provenance { source: "test", appendix: None, notes: None },
+            }])),
+            ..*KITS.iter().find(|k| k.id.0 == "LSK114").unwrap()
+        };
+        install_registry(vec![base("test_adapter_v1")]);
+        let first = resolve_kit_expr("TEST-COMPOSITE-BASE+NBD114.24").unwrap();
+        assert!(first.adapters_and_primers.iter().any(|r| r.name == "test_adapter_v1"));
+        install_registry(vec![base("test_adapter_v2")]);
+        let second = resolve_kit_expr("TEST-COMPOSITE-BASE+NBD114.24").unwrap();
+        assert!(second.adapters_and_primers.iter().any(|r| r.name == "test_adapter_v2"));
+        assert!(!second.adapters_and_primers.iter().any(|r| r.name == "test_adapter_v1"));
+    }
+
+    #[test]
+    fn legacy_barcoding_families_are_flagged_with_their_sets() {
+        let names = |id: &str| -> Vec<&str> {
+            let kit = resolve_kit_expr(id).unwrap();
+            assert!(crate::kit_is_legacy(kit), "{} should be legacy", id);
+            kit.barcodes.iter().map(|b| b.name).collect()
+        };
+        assert_eq!(names("NBD104").first().copied(), Some("NB01"));
+        assert_eq!(names("EXP-NBD114"), (13..=24).map(|i| format!("NB{:02}", i)).collect::<Vec<_>>());
+        for id in ["PBK004", "RPB004", "PCB109", "LWB001", "RBK004"] {
+            assert_eq!(names(id).len(), 12, "{}", id);
+        }
+        for id in ["LSK108", "LSK109", "LSK308", "RBK110.96"] {
+            assert!(resolve_kit_expr(id).unwrap().legacy, "{}", id);
+        }
+        for id in ["LSK114", "NBD114.24", "RBK114.24", "PCB114.24"] {
+            assert!(!resolve_kit_expr(id).unwrap().legacy, "{}", id);
+        }
+        let native = resolve_kit_expr("NBD104").unwrap();
+        assert!(native.adapters_and_primers.iter().all(|r| r.kind == SeqKind::Flank));
+        assert!(native.layout.barcoded_both_ends());
+    }
+}
@@ -16,23 +16,31 @@
 
 pub mod kit;
 pub mod kits;
+/// Kit lookup by product code or alias, with suggestions.
+pub mod resolver;
+/// IUPAC ambiguity codes for wobble-aware motif matching.
+pub mod iupac;
+/// IUPAC-aware edlib search.
+pub mod edwrap;
 pub mod seqio;
+/// MinKNOW/dorado read metadata (run, channel, flow cell, model, barcode).
+pub mod readmeta;
 pub mod screen;
 /// Benchmarking framework.
 pub mod benchmark;
 
 
 pub mod data { pub mod adapters; pub mod barcodes; pub mod cdna_legacy; pub mod legacy; }
-pub use kit::{Kit, KitId, SeqKind, SequenceRecord, BaseChemistry};
+pub use kit::{Kit, KitId, SeqKind, SequenceRecord, BaseChemistry, ReadLayout, LayoutSlot, LayoutElement};
 
-/// Return static registry of supported kits.
+/// Return the registry of supported kits (built-in plus any loaded with `kits::install_registry`).
 /// fn `list_supported_kits` — auto‑generated rustdoc.
-pub fn list_supported_kits() -> &'static [kit::Kit] { kits::KITS }
+pub fn list_supported_kits() -> &'static [kit::Kit] { kits::registry() }
 
 /// Lookup a kit by id (case-sensitive).
 /// fn `get_sequences_for_kit` — auto‑generated rustdoc.
 pub fn get_sequences_for_kit(id: &str) -> Option<&'static kit::Kit> {
-    kits::KITS.iter().find(|k| k.id.0 == id)
+    list_supported_kits().iter().find(|k| k.id.0 == id)
 }
 
 /// Is a kit legacy?
@@ -44,3 +52,13 @@ pub fn kit_is_legacy(k: &kit::Kit) -> bool { k.legacy }
 pub fn base_chemistry_of(k: &kit::Kit) -> kit::BaseChemistry { k.chemistry }
 
 pub mod clean;
+/// Registry integrity checks.
+pub mod validate;
+/// Kit export for cutadapt, Porechop and dorado.
+pub mod export;
+/// Full kit descriptions (text, JSON, YAML, Markdown, FASTA).
+pub mod describe;
+/// Reverse lookup of a sequence in the registry.
+pub mod whois;
+/// Synthetic kit reads with a truth table for benchmarking.
+pub mod simulate;
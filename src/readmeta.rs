@@ -0,0 +1,338 @@
+//! Per-read acquisition metadata from MinKNOW/dorado read headers.
+//!
+//! MinKNOW and dorado FASTQ headers carry `key=value` fields after the read id:
+//! ```text
+//! @0a1b... runid=5c6d... ch=213 start_time=2024-03-01T10:22:11Z flow_cell_id=PAQ12345
+//!     basecall_model_version_id=dna_r10.4.1_e8.2_400bps_sup@v4.3.0 barcode=barcode07
+//! ```
+//! dorado BAM records hold the same facts in aux tags (`RG`, `ch`, `st`, `rn`, `BC`).
+//! [`ReadMeta::from_comment`] and [`ReadMeta::from_tags`] turn either form into a typed
+//! [`ReadMeta`]; SAM-text tags in a FASTQ comment (`samtools fastq -T`) are understood too.
+//! Fields that are absent stay `None`.
+//!
+//! A MinKNOW/dorado `sequencing_summary.txt` can be loaded with [`SequencingSummary::load`]
+//! and joined onto reads by id ([`SequencingSummary::annotate`]), adding MinKNOW's own
+//! duration, mean qscore and barcode call as [`ReadMeta::summary`].
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+
+use crate::seqio::{self, AuxTags, NARead, TagValue};
+
+/// Typed acquisition metadata of one read.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReadMeta {
+    /// Acquisition run (`runid=`; the `RG` prefix in dorado BAM).
+    pub run_id: Option<String>,
+    /// Pore channel (`ch=`, `ch:i`).
+    pub channel: Option<u32>,
+    /// Read start, ISO 8601 as written by MinKNOW (`start_time=`, `st:Z`).
+    pub start_time: Option<String>,
+    /// Read number within its channel (`read=`, `rn:i`).
+    pub read_number: Option<u64>,
+    /// Flow cell (`flow_cell_id=`).
+    pub flow_cell_id: Option<String>,
+    /// Sample name entered in MinKNOW (`sample_id=`).
+    pub sample_id: Option<String>,
+    /// Basecalling model (`basecall_model_version_id=`, `model_version_id=`; the `RG` suffix in dorado BAM).
+    pub model: Option<String>,
+    /// The basecaller's barcode call (`barcode=`, `BC:Z`).
+    pub barcode: Option<String>,
+    /// The read's row of a joined sequencing summary, if any.
+    pub summary: Option<SummaryRecord>,
+}
+
+impl ReadMeta {
+    /// Parse a FASTQ/FASTA header comment (the text after the read id).
+    pub fn from_comment(comment: &str) -> Self {
+        let mut m = ReadMeta::default();
+        let mut read_group = None;
+        for tok in comment.split_ascii_whitespace() {
+            if let Some((tag, value)) = sam_text_tag(tok) {
+                if tag == "RG" { read_group = Some(value); } else { m.set_tag(tag, value); }
+            } else if let Some((key, value)) = tok.split_once('=') {
+                m.set_field(key, value);
+            }
+        }
+        if let Some(rg) = read_group { m.set_read_group(rg); }
+        m
+    }
+
+    /// Collect metadata from dorado BAM aux tags.
+    pub fn from_tags(tags: &AuxTags) -> Self {
+        let mut m = ReadMeta::default();
+        for (tag, value) in &tags.0 {
+            if tag == b"RG" { continue; }
+            let tag = std::str::from_utf8(tag).unwrap_or("");
+            match value {
+                TagValue::String(s) => m.set_tag(tag, s),
+                v => if let Some(n) = v.as_int() { m.set_tag(tag, &n.to_string()) },
+            }
+        }
+        if let Some(TagValue::String(rg)) = tags.get("RG") { m.set_read_group(rg); }
+        m
+    }
+
+    /// Were no fields found?
+    pub fn is_empty(&self) -> bool { *self == ReadMeta::default() }
+
+    /// The fields reports are stratified by, as `(field, value)`: `run`, `flow_cell` and `model`.
+    pub fn strata(&self) -> Vec<(&'static str, &str)> {
+        [("run", &self.run_id), ("flow_cell", &self.flow_cell_id), ("model", &self.model)]
+            .into_iter()
+            .filter_map(|(k, v)| v.as_deref().map(|v| (k, v)))
+            .collect()
+    }
+
+    fn set_field(&mut self, key: &str, value: &str) {
+        let value = value.trim_matches('"');
+        if value.is_empty() { return; }
+        match key {
+            "runid" | "run_id" => self.run_id = Some(value.to_string()),
+            "ch" | "channel" => self.channel = value.parse().ok(),
+            "start_time" => self.start_time = Some(value.to_string()),
+            "read" | "read_number" => self.read_number = value.parse().ok(),
+            "flow_cell_id" => self.flow_cell_id = Some(value.to_string()),
+            "sample_id" => self.sample_id = Some(value.to_string()),
+            "basecall_model_version_id" | "model_version_id" => self.model = Some(value.to_string()),
+            "barcode" => self.barcode = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn set_tag(&mut self, tag: &str, value: &str) {
+        match tag {
+            "ch" => self.set_field("ch", value),
+            "st" => self.set_field("start_time", value),
+            "rn" => self.set_field("read", value),
+            "BC" => self.set_field("barcode", value),
+            _ => {}
+        }
+    }
+
+    /// dorado read groups are `<runid>_<model>`, with `_<barcode>` appended for barcoded reads.
+    fn set_read_group(&mut self, rg: &str) {
+        let Some((run, rest)) = rg.split_once('_') else { return };
+        if self.run_id.is_none() { self.run_id = Some(run.to_string()); }
+        let model = match &self.barcode {
+            Some(bc) => rest.strip_suffix(bc.as_str()).and_then(|r| r.strip_suffix('_')).unwrap_or(rest),
+            None => rest,
+        };
+        if self.model.is_none() && !model.is_empty() { self.model = Some(model.to_string()); }
+    }
+}
+
+/// `XX:T:value` (a SAM-text aux field), as `(XX, value)`.
+fn sam_text_tag(tok: &str) -> Option<(&str, &str)> {
+    let b = tok.as_bytes();
+    let ok = b.len() >= 5
+        && b[0].is_ascii_alphabetic() && b[1].is_ascii_alphanumeric()
+        && b[2] == b':' && b"AifZHB".contains(&b[3]) && b[4] == b':';
+    if ok { Some((&tok[..2], &tok[5..])) } else { None }
+}
+
+/// One read's row of a sequencing summary.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SummaryRecord {
+    pub run_id: Option<String>,
+    pub channel: Option<u32>,
+    /// Seconds since the start of the run.
+    pub start_time: Option<f64>,
+    /// Read duration in seconds.
+    pub duration: Option<f64>,
+    /// `mean_qscore_template` (MinKNOW) or `mean_qscore` (dorado).
+    pub mean_qscore: Option<f64>,
+    /// MinKNOW's barcode call, e.g. `barcode07` or `unclassified`.
+    pub barcode_arrangement: Option<String>,
+}
+
+/// A MinKNOW or dorado `sequencing_summary.txt`, keyed by read id.
+#[derive(Debug, Clone, Default)]
+pub struct SequencingSummary {
+    rows: HashMap<String, SummaryRecord>,
+}
+
+impl SequencingSummary {
+    /// Load a tab-separated summary (plain or compressed). Only `read_id` is required;
+    /// `run_id`, `channel`, `start_time`, `duration`, `mean_qscore_template`/`mean_qscore`
+    /// and `barcode_arrangement` are picked up when present.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let p = path.as_ref();
+        let mut lines = BufReader::new(seqio::open_text(p)?).lines();
+        let header = match lines.next() {
+            Some(h) => h.with_context(|| format!("reading {}", p.display()))?,
+            None => bail!("{} is empty", p.display()),
+        };
+        let cols: Vec<&str> = header.trim_end().split('\t').collect();
+        let col = |names: &[&str]| cols.iter().position(|c| names.contains(c));
+        let Some(id_col) = col(&["read_id"]) else {
+            bail!("{}: no read_id column; is this a sequencing summary?", p.display())
+        };
+        let run_col = col(&["run_id"]);
+        let ch_col = col(&["channel"]);
+        let start_col = col(&["start_time"]);
+        let dur_col = col(&["duration"]);
+        let qs_col = col(&["mean_qscore_template", "mean_qscore"]);
+        let bc_col = col(&["barcode_arrangement"]);
+
+        let mut rows = HashMap::new();
+        for (i, line) in lines.enumerate() {
+            let line = line.with_context(|| format!("reading {}", p.display()))?;
+            if line.is_empty() { continue; }
+            let f: Vec<&str> = line.split('\t').collect();
+            let get = |c: Option<usize>| c.and_then(|c| f.get(c)).map(|v| v.trim()).filter(|v| !v.is_empty());
+            let Some(id) = get(Some(id_col)) else {
+                bail!("{}: line {} has no read_id", p.display(), i + 2)
+            };
+            rows.insert(id.to_string(), SummaryRecord {
+                run_id: get(run_col).map(str::to_string),
+                channel: get(ch_col).and_then(|v| v.parse().ok()),
+                start_time: get(start_col).and_then(|v| v.parse().ok()),
+                duration: get(dur_col).and_then(|v| v.parse().ok()),
+                mean_qscore: get(qs_col).and_then(|v| v.parse().ok()),
+                barcode_arrangement: get(bc_col).map(str::to_string),
+            });
+        }
+        Ok(SequencingSummary { rows })
+    }
+
+    /// Number of reads in the summary.
+    pub fn len(&self) -> usize { self.rows.len() }
+
+    pub fn is_empty(&self) -> bool { self.rows.is_empty() }
+
+    /// The row for `read_id`.
+    pub fn get(&self, read_id: &str) -> Option<&SummaryRecord> { self.rows.get(read_id) }
+
+    /// Attach the read's summary row to `read.meta`, filling run id and channel if the
+    /// header did not carry them. Returns false if the read is not in the summary.
+    pub fn annotate(&self, read: &mut NARead) -> bool {
+        let Some(row) = self.rows.get(&read.id) else { return false };
+        let meta = &mut read.meta;
+        if meta.run_id.is_none() { meta.run_id = row.run_id.clone(); }
+        if meta.channel.is_none() { meta.channel = row.channel; }
+        meta.summary = Some(row.clone());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_comment_reads_minknow_fields() {
+        let m = ReadMeta::from_comment("runid=5c6d ch=213 start_time=2024-03-01T10:22:11Z read=88 \
+            flow_cell_id=PAQ12345 sample_id=\"s1\" basecall_model_version_id=dna_r10.4.1_e8.2_400bps_sup@v4.3.0 \
+            barcode=barcode07 other=ignored");
+        assert_eq!(m.run_id.as_deref(), Some("5c6d"));
+        assert_eq!(m.channel, Some(213));
+        assert_eq!(m.start_time.as_deref(), Some("2024-03-01T10:22:11Z"));
+        assert_eq!(m.read_number, Some(88));
+        assert_eq!(m.flow_cell_id.as_deref(), Some("PAQ12345"));
+        assert_eq!(m.sample_id.as_deref(), Some("s1"));
+        assert_eq!(m.model.as_deref(), Some("dna_r10.4.1_e8.2_400bps_sup@v4.3.0"));
+        assert_eq!(m.barcode.as_deref(), Some("barcode07"));
+        assert_eq!(m.strata(), [("run", "5c6d"), ("flow_cell", "PAQ12345"), ("model", "dna_r10.4.1_e8.2_400bps_sup@v4.3.0")]);
+    }
+
+    #[test]
+    fn from_comment_reads_sam_text_tags() {
+        // `samtools fastq -T` output
+        let m = ReadMeta::from_comment("ch:i:17\tst:Z:2024-01-01T00:00:00Z\trn:i:5\tBC:Z:barcode03\tRG:Z:abc_dna_r10.4.1_e8.2_400bps_hac@v4.2.0_barcode03");
+        assert_eq!(m.channel, Some(17));
+        assert_eq!(m.read_number, Some(5));
+        assert_eq!(m.barcode.as_deref(), Some("barcode03"));
+        assert_eq!(m.run_id.as_deref(), Some("abc"));
+        assert_eq!(m.model.as_deref(), Some("dna_r10.4.1_e8.2_400bps_hac@v4.2.0"));
+    }
+
+    #[test]
+    fn from_comment_ignores_junk() {
+        assert!(ReadMeta::from_comment("").is_empty());
+        assert!(ReadMeta::from_comment("some free text x=").is_empty());
+        // unparsable numbers stay unset
+        assert_eq!(ReadMeta::from_comment("ch=abc").channel, None);
+    }
+
+    #[test]
+    fn from_tags_reads_dorado_tags() {
+        let tags = AuxTags(vec![
+            (*b"RG", TagValue::String("run9_dna_r10.4.1_e8.2_400bps_sup@v5.0.0".into())),
+            (*b"ch", TagValue::U16(1024)),
+            (*b"rn", TagValue::I32(3)),
+            (*b"st", TagValue::String("2024-05-05T05:05:05Z".into())),
+            (*b"qs", TagValue::Float(12.5)),
+        ]);
+        let m = ReadMeta::from_tags(&tags);
+        assert_eq!(m.run_id.as_deref(), Some("run9"));
+        assert_eq!(m.model.as_deref(), Some("dna_r10.4.1_e8.2_400bps_sup@v5.0.0"));
+        assert_eq!(m.channel, Some(1024));
+        assert_eq!(m.read_number, Some(3));
+        assert_eq!(m.start_time.as_deref(), Some("2024-05-05T05:05:05Z"));
+        assert!(ReadMeta::from_tags(&AuxTags::default()).is_empty());
+    }
+
+    #[test]
+    fn set_read_group_splits_run_model_and_barcode() {
+        let rg = |m: &mut ReadMeta, rg: &str| { m.set_read_group(rg); m.clone() };
+        let m = rg(&mut ReadMeta::default(), "run1_model@v1");
+        assert_eq!((m.run_id.as_deref(), m.model.as_deref()), (Some("run1"), Some("model@v1")));
+        // the barcode suffix is dropped only when the barcode is known
+        let mut bc = ReadMeta { barcode: Some("SQK-NBD114-24_barcode07".into()), ..Default::default() };
+        let m = rg(&mut bc, "run1_model@v1_SQK-NBD114-24_barcode07");
+        assert_eq!(m.model.as_deref(), Some("model@v1"));
+        // header fields win over the read group
+        let mut set = ReadMeta { run_id: Some("r".into()), model: Some("m".into()), ..Default::default() };
+        let m = rg(&mut set, "run1_model@v1");
+        assert_eq!((m.run_id.as_deref(), m.model.as_deref()), (Some("r"), Some("m")));
+        // no separator: nothing to take
+        assert!(rg(&mut ReadMeta::default(), "plain").is_empty());
+    }
+
+    fn temp_summary(name: &str, text: &str) -> std::path::PathBuf {
+        let p = std::env::temp_dir().join(format!("porkchop-{}-{}", std::process::id(), name));
+        std::fs::write(&p, text).unwrap();
+        p
+    }
+
+    #[test]
+    fn summary_load_reads_minknow_and_dorado_columns() {
+        let minknow = temp_summary("minknow_summary.txt", "filename\tread_id\trun_id\tchannel\tstart_time\tduration\t\
+            mean_qscore_template\tbarcode_arrangement\n\
+            a.pod5\tr1\trunA\t12\t3.5\t1.25\t11.2\tbarcode07\n\
+            a.pod5\tr2\trunA\t\tx\t2\t9\tunclassified\n\n");
+        let s = SequencingSummary::load(&minknow).unwrap();
+        std::fs::remove_file(&minknow).unwrap();
+        assert_eq!(s.len(), 2);
+        assert_eq!(s.get("r1"), Some(&SummaryRecord {
+            run_id: Some("runA".into()), channel: Some(12), start_time: Some(3.5), duration: Some(1.25),
+            mean_qscore: Some(11.2), barcode_arrangement: Some("barcode07".into()),
+        }));
+        // empty and unparsable cells stay unset
+        let r2 = s.get("r2").unwrap();
+        assert_eq!((r2.channel, r2.start_time, r2.duration), (None, None, Some(2.0)));
+
+        // dorado: only read_id is required; `mean_qscore` is accepted too
+        let dorado = temp_summary("dorado_summary.txt", "read_id\tmean_qscore\nr9\t14\n");
+        let s = SequencingSummary::load(&dorado).unwrap();
+        std::fs::remove_file(&dorado).unwrap();
+        assert_eq!(s.get("r9").unwrap().mean_qscore, Some(14.0));
+        assert_eq!(s.get("r9").unwrap().run_id, None);
+    }
+
+    #[test]
+    fn summary_load_rejects_other_tables() {
+        let empty = temp_summary("empty_summary.txt", "");
+        let no_id = temp_summary("noid_summary.txt", "channel\tduration\n1\t2\n");
+        let blank_id = temp_summary("blankid_summary.txt", "read_id\tchannel\n\t2\n");
+        for p in [&empty, &no_id, &blank_id] {
+            assert!(SequencingSummary::load(p).is_err(), "{}", p.display());
+            std::fs::remove_file(p).unwrap();
+        }
+    }
+}
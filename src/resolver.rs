@@ -0,0 +1,211 @@
+//! Forgiving kit-id lookup.
+//!
+//! Users type ONT product codes rather than registry ids: `SQK-NBD114-24`,
+//! `sqk-lsk114`, `EXP-PBC096`. [`KitResolver`] reduces both sides to a
+//! normalised key (no `SQK-`/`EXP-`/`VSK-` prefix, uppercase, no `.`/`-`/`_`
+//! separators), consults a per-kit alias table, and on failure returns a
+//! [`ResolveError`] carrying suggestions ranked by edit distance. A key that
+//! also names the stem of a numbered kit set (`NBD114` for `EXP-NBD114`,
+//! `NBD114.24` and `NBD114.96`) is reported as ambiguous.
+
+use bio::alignment::distance::levenshtein;
+
+use crate::kit::Kit;
+use crate::kits;
+
+/// Extra names per kit id, beyond what normalisation already covers.
+pub const KIT_ALIASES: &[(&str, &[&str])] = &[
+    ("LSK114-XL", &["LSK114XL"]),
+    ("LSK308", &["1D2", "1D^2"]),
+    ("PBC001", &["PBC012", "PBC12"]),
+    ("PBC096", &["PBC96"]),
+    ("16S114.24", &["16S24"]),
+];
+
+const PREFIXES: &[&str] = &["SQK", "EXP", "VSK"];
+
+/// Reduce a kit name to its lookup key (`sqk-nbd114-24` → `NBD11424`).
+pub fn normalize_kit_id(s: &str) -> String {
+    let up = s.trim().to_ascii_uppercase();
+    let mut rest = up.as_str();
+    for p in PREFIXES {
+        if let Some(r) = rest.strip_prefix(p) {
+            if r.starts_with(['-', '_', '.', ' ']) {
+                rest = r;
+                break;
+            }
+        }
+    }
+    rest.chars().filter(|c| !matches!(c, '-' | '_' | '.' | ' ' | '/')).collect()
+}
+
+/// Uppercase `s` and drop separators, keeping any product prefix (`exp-nbd114` → `EXPNBD114`).
+fn compact(s: &str) -> String {
+    s.trim().to_ascii_uppercase().chars().filter(|c| !matches!(c, '-' | '_' | '.' | ' ' | '/')).collect()
+}
+
+/// Lookup key of a numbered set's stem (`NBD114.24` → `NBD114`); `None` for ids without one.
+fn stem_key(id: &str) -> Option<String> {
+    let (stem, n) = id.rsplit_once('.')?;
+    (!n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())).then(|| normalize_kit_id(stem))
+}
+
+/// Lookup failure with ranked did-you-mean suggestions.
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    pub query: String,
+    /// Kit ids, closest first.
+    pub suggestions: Vec<&'static str>,
+    /// The query names several kits; `suggestions` lists them all.
+    pub ambiguous: bool,
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ambiguous {
+            return write!(f, "Ambiguous kit: {} could be any of {}. Give the full kit id.",
+                self.query, self.suggestions.join(", "));
+        }
+        write!(f, "Unknown kit: {}.", self.query)?;
+        if !self.suggestions.is_empty() {
+            write!(f, " Did you mean: {}?", self.suggestions.join(", "))?;
+        }
+        write!(f, " Use \"porkchop list-kits --format table\" to see valid kit ids.")
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Resolves user-supplied kit names against a kit list.
+pub struct KitResolver {
+    kits: &'static [Kit],
+    /// (normalised key, kit id) for every id and alias.
+    keys: Vec<(String, &'static str)>,
+}
+
+impl KitResolver {
+    /// Resolver over the active registry (built-in plus installed custom kits).
+    pub fn new() -> Self { Self::with_kits(kits::registry()) }
+
+    /// Resolver over an explicit kit list.
+    pub fn with_kits(kits: &'static [Kit]) -> Self {
+        let mut keys: Vec<(String, &'static str)> = kits.iter()
+            .map(|k| (normalize_kit_id(k.id.0), k.id.0))
+            .collect();
+        for (id, aliases) in KIT_ALIASES {
+            if let Some(k) = kits.iter().find(|k| k.id.0 == *id) {
+                keys.extend(aliases.iter().map(|a| (normalize_kit_id(a), k.id.0)));
+            }
+        }
+        KitResolver { kits, keys }
+    }
+
+    /// Resolve `query` to a kit: exact id first, then the id ignoring case and
+    /// separators, then normalised id or alias. A normalised match that is also
+    /// the stem of a numbered set (`NBD114`) is ambiguous and lists every candidate.
+    pub fn resolve(&self, query: &str) -> Result<&'static Kit, ResolveError> {
+        if let Some(k) = self.kits.iter().find(|k| k.id.0 == query) {
+            return Ok(k);
+        }
+        let compact_query = compact(query);
+        if let Some(k) = self.kits.iter().find(|k| compact(k.id.0) == compact_query) {
+            return Ok(k);
+        }
+        let key = normalize_kit_id(query);
+        let mut candidates: Vec<&'static str> = Vec::new();
+        for (_, id) in self.keys.iter().filter(|(k, _)| *k == key) {
+            if !candidates.contains(id) { candidates.push(id); }
+        }
+        if candidates.is_empty() {
+            return Err(ResolveError { query: query.to_string(), suggestions: self.suggest(query, 5), ambiguous: false });
+        }
+        for k in self.kits.iter().filter(|k| stem_key(k.id.0).as_deref() == Some(key.as_str())) {
+            if !candidates.contains(&k.id.0) { candidates.push(k.id.0); }
+        }
+        match candidates.as_slice() {
+            [id] => Ok(self.kits.iter().find(|k| k.id.0 == *id).expect("candidate ids come from the kit list")),
+            _ => Err(ResolveError { query: query.to_string(), suggestions: candidates, ambiguous: true }),
+        }
+    }
+
+    /// Up to `n` kit ids close to `query`, closest first.
+    ///
+    /// A kit qualifies if its id or an alias is within an edit distance of
+    /// `max(2, len/3)` of the normalised query, or starts with it.
+    pub fn suggest(&self, query: &str, n: usize) -> Vec<&'static str> {
+        let q = normalize_kit_id(query);
+        if q.is_empty() { return Vec::new(); }
+        let limit = (q.len() / 3).max(2) as u32;
+        let mut best: Vec<(u32, &'static str)> = Vec::new();
+        for (key, id) in &self.keys {
+            let d = if key.starts_with(&q) { 0 } else { levenshtein(q.as_bytes(), key.as_bytes()) };
+            if d > limit { continue; }
+            match best.iter_mut().find(|(_, b)| b == id) {
+                Some(slot) => slot.0 = slot.0.min(d),
+                None => best.push((d, id)),
+            }
+        }
+        best.sort();
+        best.into_iter().take(n).map(|(_, id)| id).collect()
+    }
+}
+
+impl Default for KitResolver {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kit::KitId;
+
+    fn builtin() -> KitResolver { KitResolver::with_kits(kits::KITS) }
+
+    #[test]
+    fn every_registry_id_resolves_to_itself() {
+        let r = builtin();
+        for k in kits::KITS {
+            assert_eq!(r.resolve(k.id.0).unwrap().id, k.id);
+        }
+    }
+
+    #[test]
+    fn product_codes_and_aliases_resolve() {
+        let r = builtin();
+        for (query, id) in [
+            ("SQK-NBD114-24", "NBD114.24"), ("sqk-nbd114.96", "NBD114.96"), ("sqk-lsk114", "LSK114"),
+            ("EXP-PBC096", "PBC096"), ("exp-nbd114", "EXP-NBD114"), ("1D2", "LSK308"), ("PBC12", "PBC001"),
+        ] {
+            assert_eq!(r.resolve(query).unwrap().id.0, id, "{}", query);
+        }
+    }
+
+    #[test]
+    fn numbered_set_stem_is_ambiguous() {
+        let e = builtin().resolve("NBD114").unwrap_err();
+        assert!(e.ambiguous);
+        assert_eq!(e.suggestions, ["EXP-NBD114", "NBD114.24", "NBD114.96"]);
+        assert!(e.to_string().starts_with("Ambiguous kit: NBD114 could be any of EXP-NBD114, NBD114.24, NBD114.96."));
+    }
+
+    #[test]
+    fn exact_custom_id_wins_over_ambiguity() {
+        let mut list = kits::KITS.to_vec();
+        let mut custom = *kits::KITS.iter().find(|k| k.id.0 == "NBD114.24").unwrap();
+        custom.id = KitId("NBD114");
+        list.push(custom);
+        let r = KitResolver::with_kits(Box::leak(list.into_boxed_slice()));
+        assert_eq!(r.resolve("NBD114").unwrap().id.0, "NBD114");
+        assert_eq!(r.resolve("EXP-NBD114").unwrap().id.0, "EXP-NBD114");
+    }
+
+    #[test]
+    fn unknown_ids_get_ranked_suggestions() {
+        let e = builtin().resolve("NBD114.2").unwrap_err();
+        assert!(!e.ambiguous);
+        assert_eq!(e.suggestions[0], "NBD114.24");
+        assert!(e.suggestions.contains(&"NBD114.96"));
+        assert!(e.to_string().contains("Did you mean: NBD114.24"));
+        assert!(builtin().resolve("").is_err());
+    }
+}
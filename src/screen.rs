@@ -1,12 +1,13 @@
 //! Module `screen` — auto‑generated docs for porkchop.
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}};
 use std::time::{Duration, Instant};
 
 use crate::benchmark::{self, BenchmarkAlgo};
 use crate::kit::SeqKind;
 use crate::list_supported_kits;
-use crate::seqio::{for_each_parallel, NARead};
+use crate::readmeta::{SequencingSummary, SummaryRecord};
+use crate::seqio::{self, InputFile, NARead, Sampling};
 use polars::prelude::*;
 use rayon::ThreadPoolBuilder;
 use std::sync::mpsc;
@@ -129,18 +130,132 @@ use serde_json;
 #[derive(Debug, Clone)]
 /// struct `ScreenOpts` — auto‑generated rustdoc.
 pub struct ScreenOpts {
-    pub files: Vec<String>,
+    /// Expanded inputs; reads carry a source folder if expanded with `by_source`.
+    pub files: Vec<InputFile>,
     pub threads: Option<usize>,
-    pub fraction: f64,
+    /// Which reads are screened (`--sample`, or `--fraction` as a seeded Bernoulli sample).
+    pub sampling: Sampling,
     pub tick_secs: u64,
     pub algo: BenchmarkAlgo,
     pub max_dist: usize,
     pub json: Option<String>,
     pub kit_prob_min: f64,
     pub html: Option<String>,
+    /// Sequencing summary joined onto reads by id (`--summary`).
+    pub summary: Option<Arc<SequencingSummary>>,
 }
 
-fn collect_all_sequences() -> Vec<crate::kit::SequenceRecord> {
+/// Per-source-folder tallies, kept when inputs carry a source; also used per
+/// run, flow cell and model when reads carry [`ReadMeta`](crate::readmeta::ReadMeta).
+#[derive(Debug, Clone, Default)]
+pub struct SourceTally {
+    pub screened: usize,
+    pub reads_with_hits: usize,
+    /// Reads per (record name, kind), deduplicated per read.
+    pub units: HashMap<(String, SeqKind), usize>,
+    /// Reads per aggregate identifier.
+    pub contexts: HashMap<String, usize>,
+}
+
+impl SourceTally {
+    /// Count one screened read, with its deduplicated hits and context if it had any.
+    fn add(&mut self, hits: Option<(&HashSet<(String, SeqKind)>, &str)>) {
+        self.screened += 1;
+        if let Some((units, context)) = hits {
+            self.reads_with_hits += 1;
+            for key in units { *self.units.entry(key.clone()).or_insert(0) += 1; }
+            *self.contexts.entry(context.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// JSON summary: counts, top kit and contexts.
+    fn to_json(&self) -> serde_json::Value {
+        let (kit, prob) = match top_kit(&self.units) {
+            Some((k, p)) => (Some(k), Some(p)),
+            None => (None, None),
+        };
+        let ctx: Vec<serde_json::Value> = sorted_contexts(&self.contexts).into_iter()
+            .map(|(id, count)| serde_json::json!({"id": id, "count": count}))
+            .collect();
+        serde_json::json!({
+            "screened": self.screened,
+            "reads_with_hits": self.reads_with_hits,
+            "top_kit": kit,
+            "top_kit_probability": prob,
+            "contexts": ctx
+        })
+    }
+
+    /// HTML cells: screened, reads with hits, top kit, probability, top context.
+    fn html_cells(&self) -> [String; 5] {
+        let (kit, prob) = match top_kit(&self.units) {
+            Some((k, p)) => (k, format!("{:.3}", p)),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let ctx = sorted_contexts(&self.contexts).first()
+            .map(|(id, n)| format!("{} ({})", id, n))
+            .unwrap_or_else(|| "-".to_string());
+        [self.screened.to_string(), self.reads_with_hits.to_string(), kit, prob, ctx]
+    }
+}
+
+/// Reads joined with a sequencing summary, with porkchop's barcode calls next to MinKNOW's.
+#[derive(Debug, Clone, Default)]
+pub struct SummaryJoin {
+    /// Screened reads found in the summary.
+    pub joined: usize,
+    /// Screened reads not in the summary.
+    pub missing: usize,
+    /// (MinKNOW `barcode_arrangement`, porkchop barcode hits) -> (reads, qscore sum, reads with a qscore).
+    pub calls: BTreeMap<(String, String), (usize, f64, usize)>,
+}
+
+impl SummaryJoin {
+    fn add(&mut self, row: Option<&SummaryRecord>, porkchop: String) {
+        let Some(row) = row else { self.missing += 1; return };
+        self.joined += 1;
+        let minknow = row.barcode_arrangement.clone().unwrap_or_else(|| "-".to_string());
+        let e = self.calls.entry((minknow, porkchop)).or_insert((0, 0.0, 0));
+        e.0 += 1;
+        if let Some(q) = row.mean_qscore { e.1 += q; e.2 += 1; }
+    }
+
+    /// Calls sorted by read count, most frequent first, with their mean qscore.
+    fn sorted_calls(&self) -> Vec<(&str, &str, usize, Option<f64>)> {
+        let mut v: Vec<_> = self.calls.iter()
+            .map(|((m, p), (n, qs, qn))| (m.as_str(), p.as_str(), *n, if *qn > 0 { Some(qs / *qn as f64) } else { None }))
+            .collect();
+        v.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(b.0)).then_with(|| a.1.cmp(b.1)));
+        v
+    }
+}
+
+/// porkchop's barcode call for a read: its barcode hits, `/`-joined, or `none`.
+fn barcode_call(units: &HashSet<(String, SeqKind)>) -> String {
+    let mut names: Vec<&str> = units.iter().filter(|(_, k)| *k == SeqKind::Barcode).map(|(n, _)| n.as_str()).collect();
+    if names.is_empty() { return "none".to_string(); }
+    names.sort_unstable();
+    names.join("/")
+}
+
+/// Most likely kit for a set of unit tallies, with its probability.
+fn top_kit(units: &HashMap<(String, SeqKind), usize>) -> Option<(String, f64)> {
+    if units.is_empty() { return None; }
+    let df = infer_kits_df(units).ok()?;
+    let kit = df.column("kit").ok()?.str().ok()?.get(0)?.to_string();
+    let prob = df.column("probability").ok()?.f64().ok()?.get(0)?;
+    Some((kit, prob))
+}
+
+/// Contexts sorted by count, most frequent first.
+fn sorted_contexts(contexts: &HashMap<String, usize>) -> Vec<(&String, &usize)> {
+    let mut v: Vec<_> = contexts.iter().collect();
+    v.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    v
+}
+
+/// Every adapter, primer, flank and barcode record of the active registry, kit by kit.
+pub fn collect_all_sequences() -> Vec<crate::kit::SequenceRecord> {
     let mut v = Vec::new();
     for k in list_supported_kits() {
         v.extend_from_slice(k.adapters_and_primers);
@@ -165,6 +280,11 @@ pub fn run_screen(opts: ScreenOpts) -> anyhow::Result<()> {
     let unclassified = Arc::new(AtomicUsize::new(0));
     let skipped = Arc::new(AtomicUsize::new(0));
     let reads_with_hits = Arc::new(AtomicUsize::new(0));
+    let source_tally: Arc<Mutex<BTreeMap<String, SourceTally>>> = Arc::new(Mutex::new(BTreeMap::new()));
+    // keyed by (field, value), e.g. ("run", "5c6d..."), see ReadMeta::strata
+    let strata_tally: Arc<Mutex<BTreeMap<(String, String), SourceTally>>> = Arc::new(Mutex::new(BTreeMap::new()));
+    let summary_join: Arc<Mutex<SummaryJoin>> = Arc::new(Mutex::new(SummaryJoin::default()));
+    let use_summary = opts.summary.is_some();
 
     // Optional prebuilt for ACMyers
     let prebuilt = if let BenchmarkAlgo::ACMyers = opts.algo {
@@ -187,8 +307,6 @@ let rwh_ui = reads_with_hits.clone();
     let mut ui_handle_opt: Option<std::thread::JoinHandle<()>> = Some(std::thread::spawn(move || {
         let _ = tui_loop(unit_ui, fwd_ui, rev_ui, combo_ui, done_ui, screened_ui, unclassified_ui, skipped_ui, rwh_ui, tick);
 }));
-// Sampling params
-    let p = opts.fraction.clamp(0.0, 1.0);
     // Build a dedicated Rayon pool for classification
     let threads_n = opts.threads.unwrap_or_else(num_cpus::get).max(1);
     let pool = ThreadPoolBuilder::new().num_threads(threads_n).build()?;
@@ -196,34 +314,30 @@ let rwh_ui = reads_with_hits.clone();
     // Bounded work queue to decouple IO from CPU
     let (tx, rx) = mpsc::sync_channel::<NARead>(threads_n * 1024);
     let rx = Arc::new(Mutex::new(rx));
-    // Spawn a producer per file (reading thread); keep IO single-threaded inside each reader,
+    // A single producer reads the inputs in order, so head and reservoir samples span all files;
     // classification happens in the Rayon pool below.
-    let mut producers = Vec::new();
-    for file in &opts.files {
-        let file = file.clone();
-        let tx_p = tx.clone();
+    let producer = {
+        let files = opts.files.clone();
         let done_p = done.clone();
         let skipped_p = skipped.clone();
-        let p_sample = p;
-        producers.push(std::thread::spawn(move || {
-            let _ = for_each_parallel(file, Some(1), move |read| {
-                if done_p.load(Ordering::SeqCst) { return; }
-                // Bernoulli(p) sampling via deterministic hash of read id
-                let take = if p_sample >= 1.0 {
-                    true
-                } else {
-                    let mut h: u64 = 0xcbf29ce484222325;
-                    for b in read.id.as_bytes() { h = h.wrapping_mul(0x100000001b3) ^ (*b as u64); }
-                    (h as f64 / std::u64::MAX as f64) < p_sample
+        let summary_p = opts.summary.clone();
+        let mut reads = opts.sampling.apply(seqio::read_inputs(files));
+        std::thread::spawn(move || -> anyhow::Result<()> {
+            while let Some(read) = reads.next() {
+                skipped_p.store(reads.skipped() as usize, Ordering::Relaxed);
+                let mut read = match read {
+                    Ok(r) => r,
+                    Err(e) => { done_p.store(true, Ordering::SeqCst); return Err(e); }
                 };
-                if !take { skipped_p.fetch_add(1, Ordering::Relaxed); return; }
-                if tx_p.send(read).is_err() {
-                    // channel closed; stop producing
+                if done_p.load(Ordering::SeqCst) { break; }
+                if let Some(summary) = &summary_p { summary.annotate(&mut read); }
+                if tx.send(read).is_err() {
+                    break; // channel closed; stop producing
                 }
-            });
-        }));
-    }
-    drop(tx); // close when producers join
+            }
+            Ok(())
+        })
+    };
 
     // Start a minimal Tokio runtime for ticking in TUI (interval sleeps)
 
@@ -253,30 +367,46 @@ let rwh_ui = reads_with_hits.clone();
                 let screened_wc = screened_c.clone();
                 let unclassified_wc = unclassified_c.clone();
 let rwh = reads_with_hits.clone();
+                let source_wc = source_tally.clone();
+                let strata_wc = strata_tally.clone();
+                let join_wc = summary_join.clone();
                 s.spawn(move |_| {
                     loop {
                         let read = { let guard = rx_c.lock().unwrap(); guard.recv() };
                         let read = match read { Ok(r) => r, Err(_) => break };
                         if done_c.load(Ordering::SeqCst) { break; }
                         // Enumerate all motif hits for this read using requested algorithm
-                        let hits = benchmark::classify_all(algo, &read.seq, records_c.as_slice(), pre_c.as_deref(), max_dist);
+                        let seq = crate::iupac::normalize_read(&read.seq);
+                        let hits = benchmark::classify_all(algo, &seq, records_c.as_slice(), pre_c.as_deref(), max_dist);
 
                         if hits.is_empty() {
                             screened_wc.fetch_add(1, Ordering::Relaxed);
                             unclassified_wc.fetch_add(1, Ordering::Relaxed);
+                            if let Some(src) = &read.source {
+                                source_wc.lock().unwrap().entry(src.to_string()).or_default().add(None);
+                            }
+                            if !read.meta.is_empty() {
+                                let mut g = strata_wc.lock().unwrap();
+                                for (field, value) in read.meta.strata() {
+                                    g.entry((field.to_string(), value.to_string())).or_default().add(None);
+                                }
+                            }
+                            if use_summary {
+                                join_wc.lock().unwrap().add(read.meta.summary.as_ref(), "none".to_string());
+                            }
                             continue;
                         }
 
                         // Tally individual hits (dedupe per read by (name, kind))
                         rwh.fetch_add(1, Ordering::Relaxed);
+                        let mut uniq = std::collections::HashSet::new();
+                        for (name, kind, _is_rc, _pos) in &hits {
+                            uniq.insert((name.clone(), *kind));
+                        }
                         {
-                            let mut uniq = std::collections::HashSet::new();
-                            for (name, kind, _is_rc, _pos) in &hits {
-                                uniq.insert((name.clone(), *kind));
-                            }
                             let mut g = unit_wc.lock().unwrap();
-                            for key in uniq {
-                                *g.entry(key).or_insert(0) += 1;
+                            for key in &uniq {
+                                *g.entry(key.clone()).or_insert(0) += 1;
                             }
                         }
 
@@ -300,6 +430,18 @@ let rwh = reads_with_hits.clone();
                         }
                         labels_pos.sort_by_key(|(pos, _)| *pos);
                         let id = labels_pos.into_iter().map(|(_, nm)| nm).collect::<Vec<_>>().join(" + ");
+                        if let Some(src) = &read.source {
+                            source_wc.lock().unwrap().entry(src.to_string()).or_default().add(Some((&uniq, &id)));
+                        }
+                        if !read.meta.is_empty() {
+                            let mut g = strata_wc.lock().unwrap();
+                            for (field, value) in read.meta.strata() {
+                                g.entry((field.to_string(), value.to_string())).or_default().add(Some((&uniq, &id)));
+                            }
+                        }
+                        if use_summary {
+                            join_wc.lock().unwrap().add(read.meta.summary.as_ref(), barcode_call(&uniq));
+                        }
                         {
                             let mut g = combo_wc.lock().unwrap();
                             *g.entry(id).or_insert(0) += 1;
@@ -312,8 +454,17 @@ let rwh = reads_with_hits.clone();
         });
     });
 
-    // Ensure producers finished
-    for jh in producers { let _ = jh.join(); }
+    // Ensure the producer finished; a read error stops the screen rather than leaving a partial report
+    let produced = producer.join().unwrap_or_else(|_| Err(anyhow::anyhow!("input reader panicked")));
+    if let Err(e) = produced {
+        done.store(true, Ordering::SeqCst);
+        if let Some(h) = ui_handle_opt.take() {
+            let _ = h.join();
+        }
+        let _ = crossterm::terminal::disable_raw_mode();
+        let _ = crossterm::execute!(std::io::stdout(), crossterm::cursor::Show, crossterm::terminal::LeaveAlternateScreen);
+        return Err(e);
+    }
 
 
 
@@ -325,7 +476,7 @@ let rwh = reads_with_hits.clone();
         let entries: Vec<(String, usize)> = {
             let g = combo_tally.lock().unwrap();
             let mut v: Vec<(String, usize)> = g.iter().map(|(k,v)| (k.clone(), *v)).collect();
-            v.sort_by(|a,b| b.1.cmp(&a.1));
+            v.sort_by_key(|r| std::cmp::Reverse(r.1));
             v
         };
         let contexts: Vec<serde_json::Value> = entries.into_iter()
@@ -335,7 +486,7 @@ let rwh = reads_with_hits.clone();
         // predicted kits table -> JSON
         // predicted kits table -> JSON
         let kits_json: Vec<serde_json::Value> = if let Ok(unit_map) = unit_tally.lock() {
-            if let Ok(df) = infer_kits_df(&*unit_map) {
+            if let Ok(df) = infer_kits_df(&unit_map) {
                 use polars::prelude::AnyValue;
                 let c_kit = df.column("kit").ok();
                 let c_desc = df.column("description").ok();
@@ -402,11 +553,45 @@ let rwh = reads_with_hits.clone();
             } else { Vec::new() }
         } else { Vec::new() };
 
-        // write a single object combining both sections
-        let combined = serde_json::json!({
+        // per source folder, when inputs carry one
+        let sources_json: Vec<serde_json::Value> = source_tally.lock().map(|g| g.iter().map(|(src, t)| {
+            let mut v = serde_json::json!({"source": src});
+            if let (Some(o), serde_json::Value::Object(rest)) = (v.as_object_mut(), t.to_json()) { o.extend(rest); }
+            v
+        }).collect()).unwrap_or_default();
+        // per run / flow cell / model, when reads carry MinKNOW/dorado metadata
+        let strata_json: Vec<serde_json::Value> = strata_tally.lock().map(|g| g.iter().map(|((field, value), t)| {
+            let mut v = serde_json::json!({"field": field, "value": value});
+            if let (Some(o), serde_json::Value::Object(rest)) = (v.as_object_mut(), t.to_json()) { o.extend(rest); }
+            v
+        }).collect()).unwrap_or_default();
+
+        // write a single object combining all sections
+        let mut combined = serde_json::json!({
             "contexts": contexts,
-            "kits": kits_json
+            "kits": kits_json,
+            "sampling": {
+                "mode": opts.sampling.mode.to_string(),
+                "seed": opts.sampling.seed,
+                "skipped": skipped.load(Ordering::Relaxed)
+            }
         });
+        if !sources_json.is_empty() {
+            combined["sources"] = serde_json::Value::Array(sources_json);
+        }
+        if !strata_json.is_empty() {
+            combined["strata"] = serde_json::Value::Array(strata_json);
+        }
+        if use_summary {
+            if let Ok(j) = summary_join.lock() {
+                let calls: Vec<serde_json::Value> = j.sorted_calls().into_iter()
+                    .map(|(m, p, n, q)| serde_json::json!({
+                        "summary_barcode": m, "porkchop_barcode": p, "reads": n, "mean_qscore": q
+                    }))
+                    .collect();
+                combined["summary"] = serde_json::json!({"joined": j.joined, "missing": j.missing, "barcodes": calls});
+            }
+        }
         let mut f = std::fs::File::create(path)?;
 
     // Ensure the TUI is fully torn down before printing tables (idempotent)
@@ -421,7 +606,7 @@ serde_json::to_writer_pretty(&mut f, &combined)?;
 
     // Also print the kit-likelihood table to stdout as a wide Polars DataFrame
     if let Ok(unit_map) = unit_tally.lock() {
-        if let Ok(df) = infer_kits_df(&*unit_map) {
+        if let Ok(df) = infer_kits_df(&unit_map) {
             // Ensure full-width display and no truncation for Polars 0.42
             std::env::set_var("POLARS_FMT_TABLE_FORMATTING", "UTF8_FULL");
             std::env::set_var("POLARS_FMT_MAX_COLS", "100000");
@@ -461,23 +646,26 @@ println!("{}", df);
     
     // HTML report: write after TUI teardown (normal path)
     if let Some(html_path) = &opts.html {
-        if let (Ok(unit_map), Ok(fwd_map), Ok(rev_map), Ok(combo_map)) =
-            (unit_tally.lock(), fwd_tally.lock(), rev_tally.lock(), combo_tally.lock())
+        if let (Ok(unit_map), Ok(fwd_map), Ok(rev_map), Ok(combo_map), Ok(source_map), Ok(strata_map), Ok(join)) =
+            (unit_tally.lock(), fwd_tally.lock(), rev_tally.lock(), combo_tally.lock(), source_tally.lock(), strata_tally.lock(), summary_join.lock())
         {
-            if let Ok(mut kits_df) = infer_kits_df(&*unit_map) {
+            if let Ok(mut kits_df) = infer_kits_df(&unit_map) {
                 if let Ok(prob) = kits_df.column("probability").and_then(|c| c.f64()) {
                     let mask = prob.gt(opts.kit_prob_min);
                     if let Ok(fdf) = kits_df.filter(&mask) { kits_df = fdf; }
                 }
-                let scrn  = screened.load(Ordering::Relaxed) as usize;
-                let uncls = unclassified.load(Ordering::Relaxed) as usize;
-                let skipd = skipped.load(Ordering::Relaxed) as usize;
-                let rwh   = reads_with_hits.load(Ordering::Relaxed) as usize;
+                let scrn  = screened.load(Ordering::Relaxed);
+                let uncls = unclassified.load(Ordering::Relaxed);
+                let skipd = skipped.load(Ordering::Relaxed);
+                let rwh   = reads_with_hits.load(Ordering::Relaxed);
                 let _ = write_html_report(
                     html_path,
                     &opts,
-                    &*unit_map, &*fwd_map, &*rev_map, &*combo_map,
+                    &unit_map, &fwd_map, &rev_map, &combo_map,
                     &kits_df,
+                    &source_map,
+                    &strata_map,
+                    if use_summary { Some(&*join) } else { None },
                     scrn, uncls, skipd, rwh,
                 );
             }
@@ -489,6 +677,7 @@ Ok(())
 
 
 
+#[allow(clippy::too_many_arguments)]
 fn write_html_report(
     path: &str,
     opts: &ScreenOpts,
@@ -497,6 +686,9 @@ fn write_html_report(
     rev: &std::collections::HashMap<(String, SeqKind), usize>,
     combos: &std::collections::HashMap<String, usize>,
     kits_df: &polars::prelude::DataFrame,
+    sources: &BTreeMap<String, SourceTally>,
+    strata: &BTreeMap<(String, String), SourceTally>,
+    summary: Option<&SummaryJoin>,
     screened: usize,
     unclassified: usize,
     skipped: usize,
@@ -528,10 +720,11 @@ code{background:#f6f8fa;padding:1px 3px;border-radius:4px}
 </style></head><body>"#);
     html.push_str(r#"<h1>porkchop — Screen Report</h1>"#);
     html.push_str(r#"<div class='meta'><h2>Run Parameters</h2><table><tbody>"#);
-    let _ = write!(html, "<tr><th>Files</th><td>{}</td></tr>", esc(&format!("{:?}", &opts.files)));
+    let files: Vec<String> = opts.files.iter().map(|f| f.path.display().to_string()).collect();
+    let _ = write!(html, "<tr><th>Files</th><td>{} ({})</td></tr>", files.len(), esc(&files.join(", ")));
     let _ = write!(html, "<tr><th>Algorithm(s)</th><td>{}</td></tr>", esc(&format!("{:?}", &opts.algo)));
     let _ = write!(html, "<tr><th>Threads</th><td>{}</td></tr>", esc(&format!("{:?}", &opts.threads)));
-    let _ = write!(html, "<tr><th>Sampling fraction</th><td>{:.3}</td></tr>", opts.fraction);
+    let _ = write!(html, "<tr><th>Sampling</th><td>{}</td></tr>", esc(&opts.sampling.to_string()));
     let _ = write!(html, "<tr><th>TUI tick (s)</th><td>{}</td></tr>", opts.tick_secs);
     let _ = write!(html, "<tr><th>Max distance</th><td>{}</td></tr>", opts.max_dist);
     let _ = write!(html, "<tr><th>Kit prob min</th><td>{:.3}</td></tr>", opts.kit_prob_min);
@@ -561,6 +754,36 @@ code{background:#f6f8fa;padding:1px 3px;border-radius:4px}
         let _ = write!(html, "<tr><td>{}</td><td>{}</td></tr>", esc(k), v);
     }
     html.push_str("</tbody></table>");
+    if !sources.is_empty() {
+        html.push_str(r#"<h2>Per source folder</h2>
+<table><thead><tr><th>source</th><th>screened</th><th>reads with &ge;1 hit</th><th>top kit</th><th>probability</th><th>top context</th></tr></thead><tbody>"#);
+        for (src, t) in sources {
+            let _ = write!(html, "<tr><td>{}</td>", esc(src));
+            for c in t.html_cells() { let _ = write!(html, "<td>{}</td>", esc(&c)); }
+            html.push_str("</tr>");
+        }
+        html.push_str("</tbody></table>");
+    }
+    if !strata.is_empty() {
+        html.push_str(r#"<h2>Per run, flow cell and model</h2>
+<table><thead><tr><th>field</th><th>value</th><th>screened</th><th>reads with &ge;1 hit</th><th>top kit</th><th>probability</th><th>top context</th></tr></thead><tbody>"#);
+        for ((field, value), t) in strata {
+            let _ = write!(html, "<tr><td>{}</td><td>{}</td>", esc(field), esc(value));
+            for c in t.html_cells() { let _ = write!(html, "<td>{}</td>", esc(&c)); }
+            html.push_str("</tr>");
+        }
+        html.push_str("</tbody></table>");
+    }
+    if let Some(j) = summary {
+        let _ = write!(html, "<h2>Barcode calls vs sequencing summary</h2><p class='small'>{} screened reads found in the summary, {} not found. \
+<code>summary barcode</code> is MinKNOW's <code>barcode_arrangement</code>; <code>porkchop barcodes</code> are the barcode records hit in the read.</p>", j.joined, j.missing);
+        html.push_str(r#"<table><thead><tr><th>summary barcode</th><th>porkchop barcodes</th><th>reads</th><th>mean qscore</th></tr></thead><tbody>"#);
+        for (m, p, n, q) in j.sorted_calls() {
+            let q = q.map(|q| format!("{:.2}", q)).unwrap_or_else(|| "-".to_string());
+            let _ = write!(html, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>", esc(m), esc(p), n, q);
+        }
+        html.push_str("</tbody></table>");
+    }
     html.push_str(r#"<h2>Sequencing kit predictions (filtered)</h2><table><thead><tr>"#);
     for col in kits_df.get_columns() {
         let _ = write!(html, "<th>{}</th>", esc(col.name()));
@@ -578,6 +801,7 @@ code{background:#f6f8fa;padding:1px 3px;border-radius:4px}
     html.push_str("</tbody></table></body></html>");
     std::fs::write(path, html)
 }
+#[allow(clippy::too_many_arguments)]
 fn tui_loop(
     unit: Arc<Mutex<HashMap<(String, SeqKind), usize>>>,
     fwd: Arc<Mutex<HashMap<(String, SeqKind), usize>>>,
@@ -653,7 +877,7 @@ fn tui_loop(
                 let g = unit.lock().unwrap();
                 g.iter().map(|((name, kind), c)| (name.clone(), *kind, *c)).collect()
             };
-            unit_items.sort_by(|a, b| b.2.cmp(&a.2));
+            unit_items.sort_by_key(|r| std::cmp::Reverse(r.2));
 
             let mut unit_rows: Vec<Row> = Vec::new();
             for (name, kind, c) in unit_items.into_iter().take(12) {
@@ -692,7 +916,7 @@ fn tui_loop(
                 let g = combos.lock().unwrap();
                 g.iter().map(|(k, v)| (k.clone(), *v)).collect()
             };
-            combo_items.sort_by(|a, b| b.1.cmp(&a.1));
+            combo_items.sort_by_key(|r| std::cmp::Reverse(r.1));
 
             let mut combo_rows: Vec<Row> = Vec::new();
             for (id, c) in combo_items.into_iter().take(12) {
@@ -1,14 +1,35 @@
 
-//! High‑performance IO for **FASTQ / FASTQ.GZ / SAM / BAM**.
+//! High‑performance IO for **FASTQ / FASTA / FASTQ.GZ / SAM / BAM**.
 //!
 //! ### Design
-//! - **FASTQ/FASTQ.GZ** parsed with `needletail`
+//! - **FASTQ/FASTA** (plain or compressed) parsed with `needletail`; FASTA reads have `qual: None`
 //! - **SAM/BAM** parsed with `rust-htslib` (optionally multithreaded via `set_threads`)
-//! - **Parallelism**: uses a local Rayon pool; `threads = None` uses all logical cores.
+//! - **Detection** by content, not file name: [`sniff_path`] reads the magic bytes
+//!   (gzip/BGZF, zstd, bzip2, xz) and then the first decompressed bytes
+//!   (`BAM\1`, SAM header `@HD`/`@SQ`/..., FASTQ `@`, FASTA `>`).
+//!   FASTQ and FASTA in any of these compressions are decompressed transparently.
+//!   Empty inputs (no bytes, or only whitespace once decompressed) yield no reads and a warning.
+//! - **Streams**: `-` reads stdin; FIFOs and character devices are read once, front to
+//!   back. [`open_input`] sniffs a stream from its buffered first bytes and replays them, and
+//!   hands SAM/BAM streams to htslib through a pipe.
+//! - **Directories** are expanded recursively by [`expand_inputs`], with include/exclude
+//!   globs; each file can carry its source folder (e.g. `barcode07`) into [`NARead::source`].
+//! - **Parallelism**: a reader thread groups records into batches of [`BATCH_SIZE`] and sends
+//!   them over a bounded channel to a local Rayon pool, where workers decode them into
+//!   [`NARead`]s and run the callback; `threads = None` uses all logical cores.
+//!   [`for_each_ordered`] does the same but delivers results in input order.
+//! - **Pull API**: [`SeqReader`] is a plain `Iterator<Item = Result<NARead>>` (with
+//!   [`SeqReader::next_batch`]) for callers that drive their own loop or pipeline;
+//!   [`read_inputs`] chains several inputs into one such stream.
+//! - **Sampling**: [`Sampling::apply`] keeps a seeded Bernoulli fraction, an exact-size
+//!   reservoir sample, or the first N reads or bases of any read stream; the same seed
+//!   gives the same reads. [`for_each_sampled`] samples before decoding.
+//! - **Output**: [`Writer`] writes FASTQ/FASTA (plain or BGZF), SAM or unaligned BAM, chosen by
+//!   [`OutputFormat::from_path`] or explicitly; SAM/BAM get an [`OutputHeader`] with a porkchop `@PG` line.
 //!
 //! ### Callback contract
-//! The `on_record` callback must be `Fn(NARead) + Send + Sync + 'static` and should be
-//! **fast**; heavy work should batch/accumulate in outer scope to avoid blocking IO.
+//! The `on_record` callback must be `Fn(NARead) + Send + Sync + 'static`; it is called
+//! concurrently from the workers, in no particular order.
 //!
 //! ### Errors
 //! Parsing/IO errors are bubbled via `anyhow::Result` to the caller.
@@ -23,91 +44,2043 @@
 //! ```
 //!
 //!
-//! `for_each_parallel` detects format and iterates records, invoking a user callback.
-//! BAM/SAM via rust-htslib; FASTQ/FASTQ.GZ via needletail.
+//! `for_each_parallel` sniffs the format and iterates records, invoking a user callback.
+//! BAM/SAM via rust-htslib; FASTQ/FASTA (optionally compressed) via needletail.
 //!
 //! The callback must be `Fn(NARead) + Send + Sync + 'static`.
 //! Parallelism uses Rayon; `--threads` controls thread count.
 
-use std::path::Path;
-use anyhow::Result;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read as _, Write as _};
+use std::os::fd::FromRawFd;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use anyhow::{anyhow, bail, Context, Result};
+use rayon::iter::{ParallelBridge, ParallelIterator};
 use rayon::ThreadPoolBuilder;
-use needletail::parse_fastx_file;
+use needletail::{parse_fastx_reader, FastxReader};
 use rust_htslib::bam;
 use rust_htslib::bam::Read;
+use rust_htslib::bam::record::Aux;
+use rust_htslib::htslib;
 
-/// Input format detected from path.
+use crate::readmeta::ReadMeta;
+
+/// Input format detected from file content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat { Fastq, Fasta, Bam, Sam }
+
+/// Outer compression layer detected from the first bytes of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// Plain (non-blocked) gzip.
+    Gzip,
+    /// Blocked gzip as written by htslib/bgzip; a gzip member with a `BC` extra field.
+    Bgzf,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+/// Result of [`sniff_path`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum InputFormat { Fastq, Bam, Sam }
+pub struct Sniffed {
+    /// Record format; [`InputFormat::Fastq`] for an empty input.
+    pub format: InputFormat,
+    pub compression: Compression,
+    /// No content (or only whitespace) after decompression; the input has zero reads.
+    pub empty: bool,
+}
+
+/// Number of decompressed bytes inspected to classify the content.
+const SNIFF_LEN: usize = 4096;
+
+/// Input path that stands for standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// Is `p` read as a stream (`-` for stdin, a FIFO or a character device such as `/dev/stdin`)?
+///
+/// Streams can be read only once and have no size, so callers must not reopen them.
+/// Directories, sockets and other special files are not streams; opening them fails as usual.
+pub fn is_stream<P: AsRef<Path>>(p: P) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    let p = p.as_ref();
+    p.as_os_str() == STDIN_PATH
+        || std::fs::metadata(p).map(|m| m.file_type().is_fifo() || m.file_type().is_char_device()).unwrap_or(false)
+}
+
+/// Fail if `-` (stdin) is listed more than once; the second copy would read nothing.
+pub fn ensure_stdin_once<P: AsRef<Path>>(paths: &[P]) -> Result<()> {
+    if paths.iter().filter(|p| p.as_ref().as_os_str() == STDIN_PATH).count() > 1 {
+        bail!("standard input (-) can be given only once");
+    }
+    Ok(())
+}
+
+/// Identify the compression layer from leading magic bytes.
+pub fn sniff_compression(head: &[u8]) -> Compression {
+    match head {
+        [0x1f, 0x8b, 0x08, flg, ..] => {
+            // FEXTRA set and the first subfield is BGZF's `BC`
+            if flg & 0x04 != 0 && head.len() >= 14 && &head[12..14] == b"BC" {
+                Compression::Bgzf
+            } else {
+                Compression::Gzip
+            }
+        }
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Compression::Zstd,
+        [b'B', b'Z', b'h', ..] => Compression::Bzip2,
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Compression::Xz,
+        _ => Compression::None,
+    }
+}
+
+/// Classify decompressed content from its first bytes; `None` if there is no content.
+fn sniff_content(head: &[u8]) -> Result<Option<InputFormat>> {
+    if head.starts_with(b"BAM\x01") {
+        return Ok(Some(InputFormat::Bam));
+    }
+    let start = head.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(head.len());
+    let body = &head[start..];
+    match body.first() {
+        Some(b'@') => {
+            // SAM header lines are a two-letter record type followed by a tab
+            let is_sam = body.len() >= 4
+                && matches!(&body[1..3], b"HD" | b"SQ" | b"RG" | b"PG" | b"CO")
+                && body[3] == b'\t';
+            Ok(Some(if is_sam { InputFormat::Sam } else { InputFormat::Fastq }))
+        }
+        Some(b'>') => Ok(Some(InputFormat::Fasta)),
+        None => Ok(None),
+        Some(_) => {
+            // headerless SAM: 11 tab-separated mandatory columns
+            let line = body.split(|&b| b == b'\n').next().unwrap_or(body);
+            if line.iter().filter(|&&b| b == b'\t').count() >= 10 {
+                Ok(Some(InputFormat::Sam))
+            } else {
+                bail!("unrecognised content (not FASTQ, FASTA, SAM or BAM)")
+            }
+        }
+    }
+}
+
+/// Open a text file (e.g. a sequencing summary) in any supported compression.
+pub(crate) fn open_text(p: &Path) -> Result<Box<dyn std::io::Read + Send>> {
+    let mut head = Vec::with_capacity(18);
+    File::open(p).with_context(|| format!("opening {}", p.display()))?
+        .take(18).read_to_end(&mut head)
+        .with_context(|| format!("reading {}", p.display()))?;
+    open_decompressed(p, sniff_compression(&head))
+}
+
+/// Open `p` and undo the given compression layer.
+fn open_decompressed(p: &Path, compression: Compression) -> Result<Box<dyn std::io::Read + Send>> {
+    let f = File::open(p).with_context(|| format!("opening {}", p.display()))?;
+    decompress(Box::new(f), compression)
+}
+
+/// Undo the given compression layer of `src`.
+fn decompress(src: Box<dyn std::io::Read + Send>, compression: Compression) -> Result<Box<dyn std::io::Read + Send>> {
+    let f = BufReader::new(src);
+    Ok(match compression {
+        Compression::None => Box::new(f),
+        Compression::Gzip | Compression::Bgzf => Box::new(flate2::bufread::MultiGzDecoder::new(f)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(f)?),
+        Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(f)),
+        Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(f)),
+    })
+}
+
+/// Detect the compression and record format of the regular file `p` from its content.
+///
+/// SAM and BAM files are read through htslib, so they must be uncompressed or BGZF/gzip;
+/// other compressions are rejected here with a readable error. Streams are refused, since
+/// sniffing would consume them; [`open_input`] handles those.
+pub fn sniff_path<P: AsRef<Path>>(path: P) -> Result<Sniffed> {
+    let p = path.as_ref();
+    if is_stream(p) {
+        bail!("{}: cannot sniff a stream without consuming it", p.display());
+    }
+    let mut head = Vec::with_capacity(18);
+    File::open(p)
+        .with_context(|| format!("opening {}", p.display()))?
+        .take(18)
+        .read_to_end(&mut head)
+        .with_context(|| format!("reading {}", p.display()))?;
+    let compression = sniff_compression(&head);
+
+    let mut content = Vec::with_capacity(SNIFF_LEN);
+    open_decompressed(p, compression)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut content)
+        .with_context(|| format!("decompressing {}", p.display()))?;
+    let sniffed = sniff_content(&content).with_context(|| format!("{}", p.display()))?;
+    let format = sniffed.unwrap_or(InputFormat::Fastq);
+
+    if matches!(format, InputFormat::Sam | InputFormat::Bam) && !matches!(compression, Compression::None | Compression::Gzip | Compression::Bgzf) {
+        bail!("{}: {:?}-compressed SAM/BAM is not supported; use BGZF", p.display(), compression);
+    }
+    Ok(Sniffed { format, compression, empty: sniffed.is_none() })
+}
+
+/// Open a FASTQ or FASTA file with the compression found by [`sniff_path`].
+pub fn open_fastx(p: &Path, compression: Compression) -> Result<Box<dyn FastxReader>> {
+    Ok(parse_fastx_reader(open_decompressed(p, compression)?)?)
+}
+
+/// Record reader of an opened input.
+pub enum Records {
+    /// FASTQ or FASTA, via needletail.
+    Fastx(Box<dyn FastxReader>),
+    /// SAM or BAM, via htslib, with the thread feeding it when the input is a stream
+    /// (see [`StreamFeed`]).
+    Hts(bam::Reader, Option<StreamFeed>),
+    /// An empty input; it has no records.
+    Empty,
+}
+
+/// Background copy of a stream into the pipe htslib reads from.
+pub struct StreamFeed(std::thread::JoinHandle<std::io::Result<u64>>);
+
+impl StreamFeed {
+    /// Wait for the copy and report a read or decompression error it hit; call this once
+    /// htslib has reached the end of the pipe, since a failed copy looks like a clean EOF there.
+    pub fn finish(self) -> Result<()> {
+        match self.0.join() {
+            Ok(r) => r.map(|_| ()).context("reading SAM/BAM stream"),
+            Err(_) => bail!("SAM/BAM stream reader panicked"),
+        }
+    }
+}
+
+/// An input opened by [`open_input`], positioned at its first record.
+pub struct Input {
+    pub sniffed: Sniffed,
+    pub records: Records,
+}
+
+/// Sniff and open a file, FIFO or `-` (stdin).
+///
+/// Regular files are sniffed with [`sniff_path`] and reopened. Streams are sniffed from
+/// their first bytes, which are then replayed; they are decompressed here, so a stream
+/// may be SAM/BAM in any supported compression.
+pub fn open_input<P: AsRef<Path>>(path: P) -> Result<Input> {
+    let p = path.as_ref();
+    if !is_stream(p) {
+        let sniffed = sniff_path(p)?;
+        let records = match sniffed.format {
+            _ if sniffed.empty => Records::Empty,
+            InputFormat::Fastq | InputFormat::Fasta => Records::Fastx(open_fastx(p, sniffed.compression)?),
+            InputFormat::Bam | InputFormat::Sam => Records::Hts(bam::Reader::from_path(p)?, None),
+        };
+        return Ok(Input { sniffed, records });
+    }
+
+    let mut src: Box<dyn std::io::Read + Send> = if p.as_os_str() == STDIN_PATH {
+        Box::new(std::io::stdin())
+    } else {
+        Box::new(File::open(p).with_context(|| format!("opening {}", p.display()))?)
+    };
+    let mut head = Vec::with_capacity(18);
+    (&mut src).take(18).read_to_end(&mut head).with_context(|| format!("reading {}", p.display()))?;
+    let compression = sniff_compression(&head);
+
+    let mut decoded = decompress(Box::new(Cursor::new(head).chain(src)), compression)?;
+    let mut content = Vec::with_capacity(SNIFF_LEN);
+    (&mut decoded)
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut content)
+        .with_context(|| format!("decompressing {}", p.display()))?;
+    let Some(format) = sniff_content(&content).with_context(|| format!("{}", p.display()))? else {
+        return Ok(Input { sniffed: Sniffed { format: InputFormat::Fastq, compression, empty: true }, records: Records::Empty });
+    };
+
+    let stream = Cursor::new(content).chain(decoded);
+    let records = match format {
+        InputFormat::Fastq | InputFormat::Fasta => Records::Fastx(parse_fastx_reader(stream)?),
+        InputFormat::Bam | InputFormat::Sam => {
+            let (reader, feed) = hts_from_stream(Box::new(stream))?;
+            Records::Hts(reader, Some(feed))
+        }
+    };
+    Ok(Input { sniffed: Sniffed { format, compression, empty: false }, records })
+}
+
+/// Feed a (decompressed) SAM/BAM stream to htslib, which reads file descriptors rather
+/// than Rust readers, through an OS pipe filled by a background thread.
+fn hts_from_stream(mut stream: Box<dyn std::io::Read + Send>) -> Result<(bam::Reader, StreamFeed)> {
+    let mut fds = [0 as libc::c_int; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    // SAFETY: both descriptors were just created by pipe(2) and are owned here.
+    let read_end = unsafe { File::from_raw_fd(fds[0]) };
+    let mut write_end = unsafe { File::from_raw_fd(fds[1]) };
+    // the copy ends with EPIPE if htslib stops reading early; that result is never collected
+    let feed = StreamFeed(std::thread::spawn(move || std::io::copy(&mut stream, &mut write_end)));
+    let reader = bam::Reader::from_path(format!("/dev/fd/{}", fds[0]))?;
+    drop(read_end);
+    Ok((reader, feed))
+}
 
 /// A normalized read passed to callbacks.
 #[derive(Debug, Clone)]
 pub struct NARead {
     pub id: String,
+    /// Bases as stored in the file; direct RNA FASTQ may use `U`
+    /// (see [`iupac::normalize_read`](crate::iupac::normalize_read)).
     pub seq: Vec<u8>,
+    /// Phred+33, as in FASTQ, whatever the input format; `None` for FASTA and SAM `*`.
     pub qual: Option<Vec<u8>>,
+    /// Source folder of the file the read came from, when requested (see [`InputOptions::by_source`]).
+    pub source: Option<Arc<str>>,
+    /// FASTQ/FASTA header text after the read id (e.g. dorado's `runid=... ch=...`), if any.
+    pub comment: Option<String>,
+    /// SAM/BAM aux tags in file order (`MM`, `ML`, `mv`, `ch`, `RG`, ...); `None` for FASTQ/FASTA.
+    pub tags: Option<AuxTags>,
+    /// Run, channel, flow cell, model, ... parsed from `comment` or `tags`.
+    pub meta: ReadMeta,
+}
+
+/// An owned SAM/BAM aux value, keeping the on-disk type so it can be written back unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagValue {
+    Char(u8),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Hex(String),
+    ArrayI8(Vec<i8>),
+    ArrayU8(Vec<u8>),
+    ArrayI16(Vec<i16>),
+    ArrayU16(Vec<u16>),
+    ArrayI32(Vec<i32>),
+    ArrayU32(Vec<u32>),
+    ArrayFloat(Vec<f32>),
+}
+
+impl TagValue {
+    /// Copy an htslib aux value.
+    pub fn from_aux(a: &Aux<'_>) -> Self {
+        match a {
+            Aux::Char(v) => TagValue::Char(*v),
+            Aux::I8(v) => TagValue::I8(*v),
+            Aux::U8(v) => TagValue::U8(*v),
+            Aux::I16(v) => TagValue::I16(*v),
+            Aux::U16(v) => TagValue::U16(*v),
+            Aux::I32(v) => TagValue::I32(*v),
+            Aux::U32(v) => TagValue::U32(*v),
+            Aux::Float(v) => TagValue::Float(*v),
+            Aux::Double(v) => TagValue::Double(*v),
+            Aux::String(v) => TagValue::String(v.to_string()),
+            Aux::HexByteArray(v) => TagValue::Hex(v.to_string()),
+            Aux::ArrayI8(v) => TagValue::ArrayI8(v.iter().collect()),
+            Aux::ArrayU8(v) => TagValue::ArrayU8(v.iter().collect()),
+            Aux::ArrayI16(v) => TagValue::ArrayI16(v.iter().collect()),
+            Aux::ArrayU16(v) => TagValue::ArrayU16(v.iter().collect()),
+            Aux::ArrayI32(v) => TagValue::ArrayI32(v.iter().collect()),
+            Aux::ArrayU32(v) => TagValue::ArrayU32(v.iter().collect()),
+            Aux::ArrayFloat(v) => TagValue::ArrayFloat(v.iter().collect()),
+        }
+    }
+
+    /// The value of an integer tag, whatever its width.
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            TagValue::I8(v) => Some(v as i64),
+            TagValue::U8(v) => Some(v as i64),
+            TagValue::I16(v) => Some(v as i64),
+            TagValue::U16(v) => Some(v as i64),
+            TagValue::I32(v) => Some(v as i64),
+            TagValue::U32(v) => Some(v as i64),
+            _ => None,
+        }
+    }
+
+    /// Borrow as an htslib aux value, e.g. for `bam::Record::push_aux`.
+    pub fn to_aux(&self) -> Aux<'_> {
+        match self {
+            TagValue::Char(v) => Aux::Char(*v),
+            TagValue::I8(v) => Aux::I8(*v),
+            TagValue::U8(v) => Aux::U8(*v),
+            TagValue::I16(v) => Aux::I16(*v),
+            TagValue::U16(v) => Aux::U16(*v),
+            TagValue::I32(v) => Aux::I32(*v),
+            TagValue::U32(v) => Aux::U32(*v),
+            TagValue::Float(v) => Aux::Float(*v),
+            TagValue::Double(v) => Aux::Double(*v),
+            TagValue::String(v) => Aux::String(v),
+            TagValue::Hex(v) => Aux::HexByteArray(v),
+            TagValue::ArrayI8(v) => Aux::ArrayI8(v.into()),
+            TagValue::ArrayU8(v) => Aux::ArrayU8(v.into()),
+            TagValue::ArrayI16(v) => Aux::ArrayI16(v.into()),
+            TagValue::ArrayU16(v) => Aux::ArrayU16(v.into()),
+            TagValue::ArrayI32(v) => Aux::ArrayI32(v.into()),
+            TagValue::ArrayU32(v) => Aux::ArrayU32(v.into()),
+            TagValue::ArrayFloat(v) => Aux::ArrayFloat(v.into()),
+        }
+    }
+}
+
+/// `,a,b,c`: array elements each after a comma, so an empty array is just its type (`B:C`).
+fn join<T: std::fmt::Display>(v: &[T]) -> String {
+    v.iter().map(|x| format!(",{}", x)).collect()
+}
+
+/// SAM text form without the tag name: `i:5`, `Z:abc`, `B:C,1,2`, ...
+impl std::fmt::Display for TagValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagValue::Char(v) => write!(f, "A:{}", *v as char),
+            TagValue::I8(v) => write!(f, "i:{}", v),
+            TagValue::U8(v) => write!(f, "i:{}", v),
+            TagValue::I16(v) => write!(f, "i:{}", v),
+            TagValue::U16(v) => write!(f, "i:{}", v),
+            TagValue::I32(v) => write!(f, "i:{}", v),
+            TagValue::U32(v) => write!(f, "i:{}", v),
+            TagValue::Float(v) => write!(f, "f:{}", v),
+            TagValue::Double(v) => write!(f, "f:{}", v),
+            TagValue::String(v) => write!(f, "Z:{}", v),
+            TagValue::Hex(v) => write!(f, "H:{}", v),
+            TagValue::ArrayI8(v) => write!(f, "B:c{}", join(v)),
+            TagValue::ArrayU8(v) => write!(f, "B:C{}", join(v)),
+            TagValue::ArrayI16(v) => write!(f, "B:s{}", join(v)),
+            TagValue::ArrayU16(v) => write!(f, "B:S{}", join(v)),
+            TagValue::ArrayI32(v) => write!(f, "B:i{}", join(v)),
+            TagValue::ArrayU32(v) => write!(f, "B:I{}", join(v)),
+            TagValue::ArrayFloat(v) => write!(f, "B:f{}", join(v)),
+        }
+    }
+}
+
+/// SAM/BAM aux tags of one record, in file order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuxTags(pub Vec<([u8; 2], TagValue)>);
+
+impl AuxTags {
+    /// Tags whose value depends on base positions in the untrimmed read
+    /// (base modifications, their length check, and the move table).
+    pub const POSITIONAL: &'static [&'static [u8; 2]] = &[b"MM", b"ML", b"MN", b"mv"];
+
+    /// Copy every aux field of `rec`; unreadable trailing fields are dropped.
+    pub fn from_record(rec: &bam::Record) -> Self {
+        let tags = rec.aux_iter()
+            .map_while(|a| a.ok())
+            .filter_map(|(t, v)| Some(([*t.first()?, *t.get(1)?], TagValue::from_aux(&v))))
+            .collect();
+        AuxTags(tags)
+    }
+
+    /// The value of `tag` (e.g. `"ch"`), if present.
+    pub fn get(&self, tag: &str) -> Option<&TagValue> {
+        self.0.iter().find(|(t, _)| t.as_slice() == tag.as_bytes()).map(|(_, v)| v)
+    }
+
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    /// Drop [`POSITIONAL`](Self::POSITIONAL) tags, which become wrong once a read is trimmed.
+    pub fn without_positional(&self) -> Self {
+        AuxTags(self.0.iter().filter(|(t, _)| !Self::POSITIONAL.contains(&t)).cloned().collect())
+    }
+
+    /// The tags of a read trimmed to `seq[start..end]`, where `seq` is the untrimmed sequence
+    /// as sequenced. Base modifications (`MM`/`ML`) are cut to the kept bases and `MN` set to the
+    /// new length; `mv` is dropped. `MM`/`ML` that do not match `seq` are dropped too.
+    pub fn trimmed(&self, seq: &[u8], start: usize, end: usize) -> Self {
+        if start == 0 && end >= seq.len() { return self.clone(); }
+        let ml = match self.get("ML") {
+            Some(TagValue::ArrayU8(v)) => Some(v.as_slice()),
+            _ => None,
+        };
+        let mods = match self.get("MM") {
+            Some(TagValue::String(mm)) => trim_base_mods(mm, ml, seq, start, end),
+            _ => None,
+        };
+        let mut out = Vec::with_capacity(self.0.len());
+        for (t, v) in &self.0 {
+            match (t, &mods) {
+                (b"MM", Some((mm, _))) => out.push((*t, TagValue::String(mm.clone()))),
+                (b"ML", Some((_, Some(ml)))) => out.push((*t, TagValue::ArrayU8(ml.clone()))),
+                (b"MN", Some(_)) => out.push((*t, TagValue::I32((end - start) as i32))),
+                _ if Self::POSITIONAL.contains(&t) => {}
+                _ => out.push((*t, v.clone())),
+            }
+        }
+        AuxTags(out)
+    }
+}
+
+/// `MM` (and `ML`) restricted to the calls on `seq[start..end]`, with skip counts relative to the
+/// kept bases; `None` if `MM` is malformed or has more calls than `seq` has bases for.
+fn trim_base_mods(mm: &str, ml: Option<&[u8]>, seq: &[u8], start: usize, end: usize) -> Option<(String, Option<Vec<u8>>)> {
+    let mut out_mm = String::new();
+    let mut out_ml = Vec::new();
+    let mut ml_pos = 0usize;
+    for entry in mm.split(';').filter(|e| !e.is_empty()) {
+        let mut fields = entry.split(',');
+        let head = fields.next()?;
+        let hb = head.as_bytes();
+        if hb.len() < 3 || !matches!(hb[1], b'+' | b'-') { return None; }
+        let codes = head[2..].trim_end_matches(['.', '?']);
+        // one ML value per modification code and call: `C+mh` has two, a ChEBI number one
+        let n_codes = if codes.bytes().all(|b| b.is_ascii_digit()) { 1 } else { codes.len() };
+        // the base as written in SEQ, on either strand; skip counts refer to its positions
+        let base = match hb[0].to_ascii_uppercase() { b'U' => b'T', b => b };
+        let sites: Vec<usize> = seq.iter().enumerate()
+            .filter(|(_, &b)| {
+                let b = match b.to_ascii_uppercase() { b'U' => b'T', b => b };
+                base == b'N' || b == base
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let before = sites.iter().take_while(|&&i| i < start).count();
+        let mut kept = Vec::new();
+        let mut rank = 0usize;
+        let mut last = None::<usize>;
+        for (k, f) in fields.enumerate() {
+            rank += f.trim().parse::<usize>().ok()?;
+            let pos = *sites.get(rank)?;
+            if pos >= start && pos < end {
+                let r = rank - before;
+                kept.push(r - last.map_or(0, |l| l + 1));
+                last = Some(r);
+                if let Some(ml) = ml {
+                    out_ml.extend_from_slice(ml.get(ml_pos + k * n_codes..ml_pos + (k + 1) * n_codes)?);
+                }
+            }
+            rank += 1;
+        }
+        let calls = entry.matches(',').count();
+        ml_pos += calls * n_codes;
+        out_mm.push_str(head);
+        for d in kept { out_mm.push(','); out_mm.push_str(&d.to_string()); }
+        out_mm.push(';');
+    }
+    match ml {
+        Some(ml) if ml.len() != ml_pos => None,
+        Some(_) => Some((out_mm, Some(out_ml))),
+        None => Some((out_mm, None)),
+    }
+}
+
+/// Tab-separated SAM text (`ch:i:123\tRG:Z:...`), as `samtools fastq -T` writes into FASTQ comments.
+impl std::fmt::Display for AuxTags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (t, v)) in self.0.iter().enumerate() {
+            if i > 0 { f.write_str("\t")?; }
+            write!(f, "{}{}:{}", t[0] as char, t[1] as char, v)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a FASTQ/FASTA header into the read id and the comment after the first whitespace.
+pub fn split_header(header: &[u8]) -> (String, Option<String>) {
+    let text = String::from_utf8_lossy(header);
+    match text.split_once(|c: char| c.is_ascii_whitespace()) {
+        Some((id, rest)) => {
+            let rest = rest.trim_start();
+            (id.to_string(), if rest.is_empty() { None } else { Some(rest.to_string()) })
+        }
+        None => (text.into_owned(), None),
+    }
+}
+
+/// File-name globs taken from a directory when no `include` is given: reads in any compression.
+pub const DEFAULT_INCLUDE: &[&str] = &[
+    "*.fastq", "*.fq", "*.fasta", "*.fa", "*.fna", "*.sam", "*.bam",
+    "*.fastq.*", "*.fq.*", "*.fasta.*", "*.fa.*", "*.fna.*",
+];
+
+/// How input arguments are expanded by [`expand_inputs`].
+#[derive(Debug, Clone, Default)]
+pub struct InputOptions {
+    /// Globs a file under a directory must match (file name or path below the directory);
+    /// empty means [`DEFAULT_INCLUDE`].
+    pub include: Vec<String>,
+    /// Globs that drop a file under a directory, checked after `include`.
+    pub exclude: Vec<String>,
+    /// Record each file's source folder on its reads.
+    pub by_source: bool,
+    /// Keep secondary and supplementary alignments of aligned SAM/BAM input.
+    pub secondary: bool,
+}
+
+/// One input file after expansion.
+#[derive(Debug, Clone)]
+pub struct InputFile {
+    pub path: PathBuf,
+    /// Name of the folder holding the file (`-` for stdin); `None` unless
+    /// [`InputOptions::by_source`] is set.
+    pub source: Option<Arc<str>>,
+    /// Keep secondary and supplementary alignments (see [`InputOptions::secondary`]).
+    pub secondary: bool,
+}
+
+fn compile_globs(globs: &[String]) -> Result<Vec<glob::Pattern>> {
+    globs.iter()
+        .map(|g| glob::Pattern::new(g).with_context(|| format!("invalid glob {:?}", g)))
+        .collect()
+}
+
+/// Name of the folder holding `p` (`-` for stdin).
+fn source_of(p: &Path) -> Arc<str> {
+    if p.as_os_str() == STDIN_PATH { return Arc::from(STDIN_PATH); }
+    let dir = match p.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    // canonicalise so `.` and `..` resolve to a real folder name
+    let dir = std::fs::canonicalize(&dir).unwrap_or(dir);
+    Arc::from(dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "/".to_string()))
+}
+
+/// Collect the regular files below `dir`, sorted; symlinked directories are not followed.
+fn walk_dir(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)
+        .with_context(|| format!("reading directory {}", dir.display()))?
+        .collect::<std::io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for e in entries {
+        let path = e.path();
+        let ft = e.file_type()?;
+        if ft.is_dir() {
+            walk_dir(&path, out)?;
+        } else if ft.is_file() || (ft.is_symlink() && path.is_file()) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Expand input arguments: directories are walked recursively and filtered with the
+/// include/exclude globs; files, FIFOs and `-` are taken as given.
+///
+/// Fails if `-` is listed twice or a directory yields no files.
+pub fn expand_inputs<P: AsRef<Path>>(paths: &[P], opts: &InputOptions) -> Result<Vec<InputFile>> {
+    ensure_stdin_once(paths)?;
+    let include = if opts.include.is_empty() {
+        compile_globs(&DEFAULT_INCLUDE.iter().map(|g| g.to_string()).collect::<Vec<_>>())?
+    } else {
+        compile_globs(&opts.include)?
+    };
+    let exclude = compile_globs(&opts.exclude)?;
+    let mut out = Vec::new();
+    for p in paths {
+        let p = p.as_ref();
+        if p.as_os_str() == STDIN_PATH || !p.is_dir() {
+            out.push(p.to_path_buf());
+            continue;
+        }
+        let mut found = Vec::new();
+        walk_dir(p, &mut found)?;
+        let before = out.len();
+        for f in found {
+            let rel = f.strip_prefix(p).unwrap_or(&f);
+            let name = f.file_name().map(Path::new).unwrap_or(rel);
+            let hit = |g: &glob::Pattern| g.matches_path(rel) || g.matches_path(name);
+            if include.iter().any(hit) && !exclude.iter().any(hit) {
+                out.push(f);
+            }
+        }
+        if out.len() == before {
+            bail!("no input files under {} (check --include/--exclude)", p.display());
+        }
+    }
+    Ok(out.into_iter()
+        .map(|path| {
+            let source = if opts.by_source { Some(source_of(&path)) } else { None };
+            InputFile { path, source, secondary: opts.secondary }
+        })
+        .collect())
 }
 
 /// Core driver: parse and iterate records, potentially in parallel (rayon pool size).
+/// Secondary and supplementary alignments are skipped.
 /// fn `for_each_parallel` — auto‑generated rustdoc.
 pub fn for_each_parallel<P, F>(path: P, threads: Option<usize>, on_record: F) -> Result<(InputFormat, usize)>
 where
     P: AsRef<Path>,
     F: Fn(NARead) + Send + Sync + 'static,
 {
-    let p = path.as_ref();
-    let fmt = if let Some(ext) = p.extension().and_then(|s| s.to_str()) {
-        match ext.to_ascii_lowercase().as_str() {
-            "fq" | "fastq" | "gz" => InputFormat::Fastq,
-            "bam" => InputFormat::Bam,
-            "sam" => InputFormat::Sam,
-            _ => {
-                if p.to_string_lossy().contains("fastq") || p.to_string_lossy().contains(".fq.") {
-                    InputFormat::Fastq
-                } else { InputFormat::Bam }
-            }
-        }
-    } else { InputFormat::Fastq };
+    for_each_sampled(path, threads, Sampling::default(), on_record)
+}
+
+/// [`for_each_parallel`] over the reads kept by `sampling`; skipped reads are not decoded.
+/// The returned count is of reads kept.
+pub fn for_each_sampled<P, F>(path: P, threads: Option<usize>, sampling: Sampling, on_record: F) -> Result<(InputFormat, usize)>
+where
+    P: AsRef<Path>,
+    F: Fn(NARead) + Send + Sync + 'static,
+{
+    let input = InputFile { path: path.as_ref().to_path_buf(), source: None, secondary: false };
+    for_each_record(std::slice::from_ref(&input), sampling, threads, on_record)
+}
+
+/// [`for_each_parallel`] over an expanded [`InputFile`]; reads carry its `source`.
+pub fn for_each_input<F>(input: &InputFile, threads: Option<usize>, on_record: F) -> Result<(InputFormat, usize)>
+where
+    F: Fn(NARead) + Send + Sync + 'static,
+{
+    for_each_record(std::slice::from_ref(input), Sampling::default(), threads, on_record)
+}
+
+/// [`for_each_sampled`] over all of `inputs` in order as one stream; reads carry their
+/// input's `source` and secondary alignments are kept per input. Head and reservoir samples
+/// span the files, as with [`read_inputs`]. Returns the first input's format and the number
+/// of reads kept.
+pub fn for_each_inputs<F>(inputs: &[InputFile], threads: Option<usize>, sampling: Sampling, on_record: F) -> Result<(InputFormat, usize)>
+where
+    F: Fn(NARead) + Send + Sync + 'static,
+{
+    for_each_record(inputs, sampling, threads, on_record)
+}
+
+/// A raw record and the source folder of the file it came from.
+struct SourcedRecord(RawRecord, Option<Arc<str>>);
+
+impl SampleKey for SourcedRecord {
+    fn sample_id(&self) -> &[u8] { self.0.sample_id() }
+    fn sample_len(&self) -> usize { self.0.sample_len() }
+}
+
+fn for_each_record<F>(inputs: &[InputFile], sampling: Sampling, threads: Option<usize>, on_record: F) -> Result<(InputFormat, usize)>
+where
+    F: Fn(NARead) + Send + Sync + 'static,
+{
+    let Some(first) = inputs.first() else { bail!("no input files") };
+    // the first input is opened here so its format can be returned; later ones when reached
+    let input = open_input(&first.path)?;
+    let fmt = input.sniffed.format;
+    let mut opened = Some(input);
+
+    let n = threads.unwrap_or_else(num_cpus::get).max(1);
+    let pool = ThreadPoolBuilder::new().num_threads(n).build()?;
+
+    let counter = AtomicUsize::new(0);
+    // bounded: the reader blocks once the workers fall `2 * n` batches behind
+    let (tx, rx) = mpsc::sync_channel::<Vec<SourcedRecord>>(2 * n);
+    let several = inputs.len() > 1;
+    let files = inputs.to_vec();
+    let mut records: Box<dyn Iterator<Item = Result<SourcedRecord>> + Send> = Box::new(files.into_iter().flat_map(
+        move |file| -> Box<dyn Iterator<Item = Result<SourcedRecord>> + Send> {
+            let path = file.path.clone();
+            let input = match opened.take() {
+                Some(input) => input,
+                None => match open_input(&file.path) {
+                    Ok(input) => input,
+                    Err(e) => return Box::new(std::iter::once(Err(e.context(format!("opening {}", path.display()))))),
+                },
+            };
+            let source = file.source.clone();
+            Box::new(raw_records(&path, input.records, n, file.secondary).map(move |r| match r {
+                Ok(raw) => Ok(SourcedRecord(raw, source.clone())),
+                Err(e) if several => Err(e.context(format!("reading {}", path.display()))),
+                Err(e) => Err(e),
+            }))
+        },
+    ));
+    if !sampling.is_all() { records = Box::new(sampling.apply(records)); }
+
+    std::thread::scope(|s| -> Result<()> {
+        let reader = s.spawn(move || read_batches(records, |_, batch| tx.send(batch).is_ok()));
+        pool.install(|| {
+            rx.into_iter().par_bridge().for_each(|batch| {
+                let k = batch.len();
+                for SourcedRecord(raw, source) in batch { on_record(raw.decode(&source)); }
+                counter.fetch_add(k, Ordering::Relaxed);
+            });
+        });
+        reader.join().map_err(|_| anyhow!("reader thread panicked"))?
+    })?;
+
+    Ok((fmt, counter.load(Ordering::Relaxed)))
+}
+
+/// Like [`for_each_parallel`], but results arrive in input order.
+///
+/// `map` runs on the worker pool, in parallel; `on_result` runs on the calling thread,
+/// once per read, in file order. Secondary and supplementary alignments are skipped. At most `2 * threads + 2` batches are in flight
+/// between the reader and `on_result`.
+pub fn for_each_ordered<P, M, T, F>(path: P, threads: Option<usize>, map: M, mut on_result: F) -> Result<(InputFormat, usize)>
+where
+    P: AsRef<Path>,
+    M: Fn(NARead) -> T + Send + Sync,
+    T: Send,
+    F: FnMut(T),
+{
+    let input = open_input(path.as_ref())?;
+    let fmt = input.sniffed.format;
 
     let n = threads.unwrap_or_else(num_cpus::get).max(1);
     let pool = ThreadPoolBuilder::new().num_threads(n).build()?;
 
-    let counter = std::sync::atomic::AtomicUsize::new(0);
-    let cb = &on_record;
-
-    pool.install(|| -> Result<()> {
-        match fmt {
-            InputFormat::Fastq => {
-                let mut reader = parse_fastx_file(p)?;
-                while let Some(record) = reader.next() {
-                    let rec = record?;
-                    let id = String::from_utf8_lossy(rec.id()).to_string();
-                    let seq = rec.seq().to_vec();
-                    let qual = rec.qual().map(|q| q.to_vec());
-                    let naread = NARead { id, seq, qual };
-                    cb(naread);
-                    counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let in_flight = 2 * n + 2;
+    let (tx, rx) = mpsc::sync_channel::<(usize, Vec<RawRecord>)>(n);
+    let (done_tx, done_rx) = mpsc::sync_channel::<(usize, Vec<T>)>(in_flight);
+    // one token per batch in flight; returned when the batch has been delivered
+    let (token_tx, token_rx) = mpsc::sync_channel::<()>(in_flight);
+    for _ in 0..in_flight { let _ = token_tx.send(()); }
+    let records = raw_records(path.as_ref(), input.records, n, false);
+    let mut count = 0usize;
+
+    std::thread::scope(|s| -> Result<()> {
+        let reader = s.spawn(move || {
+            read_batches(records, |i, batch| token_rx.recv().is_ok() && tx.send((i, batch)).is_ok())
+        });
+        let (map, pool) = (&map, &pool);
+        s.spawn(move || {
+            pool.install(|| {
+                rx.into_iter().par_bridge().for_each(|(i, batch)| {
+                    let out: Vec<T> = batch.into_iter().map(|raw| map(raw.decode(&None))).collect();
+                    let _ = done_tx.send((i, out));
+                });
+            });
+        });
+
+        let mut pending: BTreeMap<usize, Vec<T>> = BTreeMap::new();
+        let mut next = 0usize;
+        for (i, out) in done_rx {
+            pending.insert(i, out);
+            while let Some(out) = pending.remove(&next) {
+                count += out.len();
+                for t in out { on_result(t); }
+                next += 1;
+                let _ = token_tx.send(());
+            }
+        }
+        reader.join().map_err(|_| anyhow!("reader thread panicked"))?
+    })?;
+
+    Ok((fmt, count))
+}
+
+/// Pull-based reader: one input, format detected from content, reads in file order.
+///
+/// Unlike the `for_each_*` drivers it runs on the caller's thread and needs no
+/// `'static` closure; parallelise by handing [`next_batch`](Self::next_batch) results to a pool.
+/// ```no_run
+/// use porkchop::seqio::SeqReader;
+/// let mut reader = SeqReader::open("reads.bam")?;
+/// loop {
+///     let batch = reader.next_batch(1024)?;
+///     if batch.is_empty() { break; }
+///     // batch.par_iter()...
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct SeqReader {
+    format: InputFormat,
+    source: Option<Arc<str>>,
+    records: RawIter,
+}
+
+impl SeqReader {
+    /// Open `path` (a file, `-` or a named pipe); secondary and supplementary alignments are skipped.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_input(&InputFile { path: path.as_ref().to_path_buf(), source: None, secondary: false })
+    }
+
+    /// Open an expanded [`InputFile`]; reads carry its `source`.
+    pub fn from_input(input: &InputFile) -> Result<Self> {
+        let opened = open_input(&input.path)?;
+        Ok(SeqReader {
+            format: opened.sniffed.format,
+            source: input.source.clone(),
+            records: raw_records(&input.path, opened.records, 1, input.secondary),
+        })
+    }
+
+    /// Detected record format; qualities are Phred+33 whatever it is.
+    pub fn format(&self) -> InputFormat { self.format }
+
+    /// Up to `n` further reads; empty once the input is exhausted.
+    pub fn next_batch(&mut self, n: usize) -> Result<Vec<NARead>> {
+        let mut batch = Vec::with_capacity(n);
+        for r in self.by_ref().take(n) { batch.push(r?); }
+        Ok(batch)
+    }
+}
+
+impl Iterator for SeqReader {
+    type Item = Result<NARead>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let source = &self.source;
+        self.records.next().map(|r| r.map(|raw| raw.decode(source)))
+    }
+}
+
+/// Read all of `files` in order as one stream; each file is opened when reached.
+/// Errors carry the path of the file they came from.
+pub fn read_inputs(files: Vec<InputFile>) -> impl Iterator<Item = Result<NARead>> + Send {
+    files.into_iter().flat_map(|file| -> Box<dyn Iterator<Item = Result<NARead>> + Send> {
+        let path = file.path.clone();
+        match SeqReader::from_input(&file) {
+            Ok(reader) => Box::new(reader.map(move |r| r.with_context(|| format!("reading {}", path.display())))),
+            Err(e) => Box::new(std::iter::once(Err(e.context(format!("opening {}", path.display()))))),
+        }
+    })
+}
+
+/// Which reads a [`Sampling`] keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SampleMode {
+    /// Every read.
+    #[default]
+    All,
+    /// Each read with probability `p`, decided by a seeded hash of its id, so the
+    /// same reads are kept whatever their order or the thread count.
+    Fraction(f64),
+    /// Exactly `n` reads drawn uniformly (reservoir sampling), in input order.
+    /// Holds up to `n` reads in memory and reads the whole input before yielding.
+    Reservoir(usize),
+    /// The first `n` reads; reading stops there.
+    HeadReads(u64),
+    /// The first reads up to `n` bases (the read crossing the limit is kept); reading stops there.
+    HeadBases(u64),
+}
+
+/// `12`, `5k`, `2.5M`, `1G` (decimal suffixes).
+fn parse_count(s: &str) -> Result<u64, String> {
+    let (num, mult) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1e3),
+        Some((i, 'm' | 'M')) => (&s[..i], 1e6),
+        Some((i, 'g' | 'G')) => (&s[..i], 1e9),
+        _ => (s, 1.0),
+    };
+    match num.parse::<f64>() {
+        Ok(v) if v >= 0.0 && v.is_finite() => Ok((v * mult).round() as u64),
+        _ => Err(format!("invalid count: {}", s)),
+    }
+}
+
+impl std::str::FromStr for SampleMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (kind, arg) = s.split_once(':').unwrap_or((s, ""));
+        match kind.to_ascii_lowercase().as_str() {
+            "all" if arg.is_empty() => Ok(Self::All),
+            "fraction" | "bernoulli" => match arg.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => Ok(Self::Fraction(p)),
+                _ => Err(format!("fraction must be between 0 and 1: {}", s)),
+            },
+            "reservoir" => Ok(Self::Reservoir(parse_count(arg)? as usize)),
+            "head" | "reads" => Ok(Self::HeadReads(parse_count(arg)?)),
+            "bases" => Ok(Self::HeadBases(parse_count(arg)?)),
+            _ => match s.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => Ok(Self::Fraction(p)),
+                _ => Err(format!("Unknown sampling mode: {} (all | fraction:P | reservoir:N | head:N | bases:N)", s)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for SampleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SampleMode::All => f.write_str("all"),
+            SampleMode::Fraction(p) => write!(f, "fraction:{}", p),
+            SampleMode::Reservoir(n) => write!(f, "reservoir:{}", n),
+            SampleMode::HeadReads(n) => write!(f, "head:{}", n),
+            SampleMode::HeadBases(n) => write!(f, "bases:{}", n),
+        }
+    }
+}
+
+/// A sampling mode and the seed that makes it reproducible.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    pub mode: SampleMode,
+    /// Seeds the [`SampleMode::Fraction`] hash and the [`SampleMode::Reservoir`] draws.
+    pub seed: u64,
+}
+
+impl Default for Sampling {
+    fn default() -> Self { Sampling { mode: SampleMode::All, seed: 1 } }
+}
+
+impl Sampling {
+    pub fn is_all(&self) -> bool { self.mode == SampleMode::All }
+
+    /// Is each read kept or dropped on its own (all or fraction)? Then sampling inputs one
+    /// at a time keeps the same reads as sampling them as one stream.
+    pub fn is_per_read(&self) -> bool { matches!(self.mode, SampleMode::All | SampleMode::Fraction(_)) }
+
+    /// Keep only the sampled reads of `reads`; errors are passed through.
+    pub fn apply<I, T>(self, reads: I) -> Sampled<I, T>
+    where
+        I: Iterator<Item = Result<T>>,
+        T: SampleKey,
+    {
+        Sampled { inner: reads, sampling: self, seen: 0, taken: 0, bases: 0, reservoir: None, done: false }
+    }
+
+    /// Is the read `id` in a [`SampleMode::Fraction`] sample of probability `p`?
+    pub fn hash_keeps(&self, id: &[u8], p: f64) -> bool {
+        // FNV-1a over the id, then a splitmix64 finaliser so nearby seeds give unrelated samples
+        let mut h: u64 = 0xcbf29ce484222325 ^ self.seed.wrapping_mul(0x9e3779b97f4a7c15);
+        for &b in id { h = (h ^ b as u64).wrapping_mul(0x100000001b3); }
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d049bb133111eb);
+        h ^= h >> 31;
+        ((h >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
+
+/// `all`, or the mode and its seed, e.g. `reservoir:1000 (seed 7)`.
+impl std::fmt::Display for Sampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mode {
+            SampleMode::All | SampleMode::HeadReads(_) | SampleMode::HeadBases(_) => write!(f, "{}", self.mode),
+            _ => write!(f, "{} (seed {})", self.mode, self.seed),
+        }
+    }
+}
+
+/// What sampling needs to know about a read.
+pub trait SampleKey {
+    /// The read id (without header comment).
+    fn sample_id(&self) -> &[u8];
+    /// Number of bases.
+    fn sample_len(&self) -> usize;
+}
+
+impl SampleKey for NARead {
+    fn sample_id(&self) -> &[u8] { self.id.as_bytes() }
+    fn sample_len(&self) -> usize { self.seq.len() }
+}
+
+/// Iterator returned by [`Sampling::apply`].
+pub struct Sampled<I, T> {
+    inner: I,
+    sampling: Sampling,
+    seen: u64,
+    taken: u64,
+    bases: u64,
+    reservoir: Option<std::vec::IntoIter<T>>,
+    done: bool,
+}
+
+impl<I, T> Sampled<I, T>
+where
+    I: Iterator<Item = Result<T>>,
+    T: SampleKey,
+{
+    /// Reads looked at but not kept so far (head modes stop reading, so the rest are not counted).
+    pub fn skipped(&self) -> u64 { self.seen - self.taken }
+
+    /// Draw `n` reads with Algorithm R, returned in input order.
+    fn fill_reservoir(&mut self, n: usize) -> Result<Vec<T>> {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(self.sampling.seed);
+        let mut kept: Vec<(u64, T)> = Vec::with_capacity(n.min(1 << 20));
+        for r in self.inner.by_ref() {
+            let read = r?;
+            let i = self.seen;
+            self.seen += 1;
+            if kept.len() < n {
+                kept.push((i, read));
+            } else {
+                let j = rng.gen_range(0..=i);
+                if (j as usize) < n { kept[j as usize] = (i, read); }
+            }
+        }
+        kept.sort_by_key(|(i, _)| *i);
+        self.taken = kept.len() as u64;
+        Ok(kept.into_iter().map(|(_, r)| r).collect())
+    }
+}
+
+impl<I, T> Iterator for Sampled<I, T>
+where
+    I: Iterator<Item = Result<T>>,
+    T: SampleKey,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done { return None; }
+        let mode = self.sampling.mode;
+        match mode {
+            SampleMode::Reservoir(n) => {
+                if self.reservoir.is_none() {
+                    match self.fill_reservoir(n) {
+                        Ok(kept) => self.reservoir = Some(kept.into_iter()),
+                        Err(e) => { self.done = true; return Some(Err(e)); }
+                    }
+                }
+                return self.reservoir.as_mut()?.next().map(Ok);
+            }
+            SampleMode::HeadReads(n) if self.taken >= n => { self.done = true; return None; }
+            SampleMode::HeadBases(n) if self.bases >= n => { self.done = true; return None; }
+            _ => {}
+        }
+        loop {
+            let read = match self.inner.next()? {
+                Ok(r) => r,
+                Err(e) => return Some(Err(e)),
+            };
+            self.seen += 1;
+            if let SampleMode::Fraction(p) = mode {
+                if !self.sampling.hash_keeps(read.sample_id(), p) { continue; }
+            }
+            self.taken += 1;
+            self.bases += read.sample_len() as u64;
+            return Some(Ok(read));
+        }
+    }
+}
+
+/// Records per batch handed from the reader thread to the workers.
+pub const BATCH_SIZE: usize = 1024;
+
+/// A record as read, decoded into an [`NARead`] on a worker.
+enum RawRecord {
+    Fastx { id: Vec<u8>, seq: Vec<u8>, qual: Option<Vec<u8>> },
+    Hts(bam::Record),
+}
+
+impl RawRecord {
+    fn decode(self, source: &Option<Arc<str>>) -> NARead {
+        match self {
+            RawRecord::Fastx { id, seq, qual } => {
+                let (id, comment) = split_header(&id);
+                let meta = comment.as_deref().map(ReadMeta::from_comment).unwrap_or_default();
+                NARead { id, seq, qual, source: source.clone(), comment, tags: None, meta }
+            }
+            RawRecord::Hts(rec) => {
+                let (seq, qual) = as_sequenced(&rec);
+                // BAM holds raw Phred; cap at 93 so every score prints as FASTQ text
+                let qual = qual.map(|q| q.into_iter().map(|q| q.min(93) + 33).collect());
+                let tags = AuxTags::from_record(&rec);
+                NARead {
+                    id: String::from_utf8_lossy(rec.qname()).into_owned(),
+                    seq,
+                    qual,
+                    source: source.clone(),
+                    comment: None,
+                    meta: ReadMeta::from_tags(&tags),
+                    tags: Some(tags),
+                }
+            }
+        }
+    }
+}
+
+impl SampleKey for RawRecord {
+    fn sample_id(&self) -> &[u8] {
+        match self {
+            RawRecord::Fastx { id, .. } => id.split(|b| b.is_ascii_whitespace()).next().unwrap_or(id),
+            RawRecord::Hts(rec) => rec.qname(),
+        }
+    }
+
+    fn sample_len(&self) -> usize {
+        match self {
+            RawRecord::Fastx { seq, .. } => seq.len(),
+            RawRecord::Hts(rec) => rec.seq_len(),
+        }
+    }
+}
+
+/// Sequence and raw qualities of `rec` in the orientation the sequencer read them:
+/// reverse-strand alignments store both reverse-complemented, so they are flipped back.
+/// Aux tags such as `MM`/`ML` and `mv` already refer to this orientation.
+pub fn as_sequenced(rec: &bam::Record) -> (Vec<u8>, Option<Vec<u8>>) {
+    let q = rec.qual();
+    // htslib stores a missing quality string (`*`) as 0xff
+    let mut qual = if q.is_empty() || q[0] == 0xff { None } else { Some(q.to_vec()) };
+    let mut seq = rec.seq().as_bytes();
+    if rec.is_reverse() {
+        seq = crate::iupac::revcomp(&seq);
+        if let Some(q) = qual.as_mut() { q.reverse(); }
+    }
+    (seq, qual)
+}
+
+/// Is `rec` a secondary or supplementary alignment (another copy of a read already seen)?
+pub fn is_extra_alignment(rec: &bam::Record) -> bool {
+    rec.is_secondary() || rec.is_supplementary()
+}
+
+type RawIter = Box<dyn Iterator<Item = Result<RawRecord>> + Send>;
+
+/// Iterate the opened input `path` as raw records; htslib decompresses with `hts_threads`.
+/// Secondary and supplementary alignments are dropped unless `secondary` is set.
+/// An empty input yields nothing and is reported on stderr.
+fn raw_records(path: &Path, records: Records, hts_threads: usize, secondary: bool) -> RawIter {
+    match records {
+        Records::Empty => {
+            eprintln!("warning: {} is empty; no reads", path.display());
+            Box::new(std::iter::empty())
+        }
+        Records::Fastx(mut reader) => Box::new(std::iter::from_fn(move || {
+            reader.next().map(|r| {
+                let rec = r?;
+                Ok(RawRecord::Fastx { id: rec.id().to_vec(), seq: rec.seq().to_vec(), qual: rec.qual().map(|q| q.to_vec()) })
+            })
+        })),
+        Records::Hts(mut reader, mut feed) => {
+            if hts_threads > 1 { let _ = reader.set_threads(hts_threads); }
+            Box::new(std::iter::from_fn(move || loop {
+                let mut rec = bam::Record::new();
+                let Some(r) = reader.read(&mut rec) else {
+                    return feed.take().and_then(|f| f.finish().err()).map(Err);
+                };
+                match r {
+                    Ok(()) if !secondary && is_extra_alignment(&rec) => continue,
+                    r => return Some(r.map(|_| RawRecord::Hts(rec)).map_err(Into::into)),
+                }
+            }))
+        }
+    }
+}
+
+/// Group `records` into numbered batches of [`BATCH_SIZE`] and pass them to `send`,
+/// stopping early once `send` returns false (the consumer has gone away).
+fn read_batches<T>(records: impl Iterator<Item = Result<T>>, mut send: impl FnMut(usize, Vec<T>) -> bool) -> Result<()> {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut i = 0usize;
+    for raw in records {
+        batch.push(raw?);
+        if batch.len() == BATCH_SIZE {
+            if !send(i, std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE))) { return Ok(()); }
+            i += 1;
+        }
+    }
+    if !batch.is_empty() { send(i, batch); }
+    Ok(())
+}
+
+/// Output format of a [`Writer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat { Fastq, FastqGz, Fasta, FastaGz, Sam, Bam }
+
+impl OutputFormat {
+    /// Return the CLI name of the format.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Fastq => "fastq",
+            OutputFormat::FastqGz => "fastq.gz",
+            OutputFormat::Fasta => "fasta",
+            OutputFormat::FastaGz => "fasta.gz",
+            OutputFormat::Sam => "sam",
+            OutputFormat::Bam => "bam",
+        }
+    }
+
+    /// Format implied by the file name: `.fastq`/`.fq`, `.fasta`/`.fa`/`.fna` (either with
+    /// `.gz`/`.bgz` for BGZF), `.sam` or `.bam`; `None` for anything else, including `-`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let name = path.as_ref().file_name()?.to_string_lossy().to_ascii_lowercase();
+        let (stem, gz) = match name.rsplit_once('.')? {
+            (stem, "gz" | "bgz" | "bgzf") => (stem, true),
+            _ => (name.as_str(), false),
+        };
+        match (stem.rsplit_once('.')?.1, gz) {
+            ("fastq" | "fq", false) => Some(OutputFormat::Fastq),
+            ("fastq" | "fq", true) => Some(OutputFormat::FastqGz),
+            ("fasta" | "fa" | "fna", false) => Some(OutputFormat::Fasta),
+            ("fasta" | "fa" | "fna", true) => Some(OutputFormat::FastaGz),
+            ("sam", false) => Some(OutputFormat::Sam),
+            ("bam", false) => Some(OutputFormat::Bam),
+            _ => None,
+        }
+    }
+
+    /// Does every record need base qualities (FASTQ)? SAM/BAM write `*` for reads without.
+    pub fn requires_qual(&self) -> bool {
+        matches!(self, OutputFormat::Fastq | OutputFormat::FastqGz)
+    }
+
+    /// The FASTA format with the same compression; SAM/BAM are returned unchanged.
+    pub fn to_fasta(self) -> Self {
+        match self {
+            OutputFormat::Fastq => OutputFormat::Fasta,
+            OutputFormat::FastqGz => OutputFormat::FastaGz,
+            other => other,
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fastq" | "fq" => Ok(Self::Fastq),
+            "fastq.gz" | "fq.gz" | "bgzf" => Ok(Self::FastqGz),
+            "fasta" | "fa" => Ok(Self::Fasta),
+            "fasta.gz" | "fa.gz" => Ok(Self::FastaGz),
+            "sam" => Ok(Self::Sam),
+            "bam" | "ubam" => Ok(Self::Bam),
+            other => Err(format!("Unknown output format: {} (fastq | fastq.gz | fasta | fasta.gz | sam | bam)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The running program's arguments joined by spaces, for `@PG CL:`.
+pub fn command_line() -> String {
+    std::env::args().collect::<Vec<_>>().join(" ")
+}
+
+/// SAM/BAM header of a [`Writer`]: `@HD`, header lines copied from the inputs, and
+/// porkchop's own `@PG` line. Unused for FASTQ/FASTA.
+#[derive(Debug, Clone, Default)]
+pub struct OutputHeader {
+    /// `@RG`, `@PG` and `@CO` lines from the inputs, in order.
+    pub lines: Vec<String>,
+    /// Recorded as `CL:` in porkchop's `@PG` line.
+    pub command_line: String,
+}
+
+impl OutputHeader {
+    pub fn new(command_line: impl Into<String>) -> Self {
+        OutputHeader { lines: Vec::new(), command_line: command_line.into() }
+    }
+
+    /// Copy the `@RG`, `@PG` and `@CO` lines of SAM/BAM `inputs`; a read group or program
+    /// seen in an earlier input is not repeated. Streams are skipped, as they can be read only once.
+    pub fn with_inputs(mut self, inputs: &[InputFile]) -> Result<Self> {
+        for input in inputs.iter().filter(|f| !is_stream(&f.path)) {
+            let opened = open_input(&input.path)?;
+            let Records::Hts(reader, _) = opened.records else { continue };
+            let text = String::from_utf8_lossy(reader.header().as_bytes()).into_owned();
+            for line in text.lines().filter(|l| l.starts_with("@RG\t") || l.starts_with("@PG\t") || l.starts_with("@CO\t")) {
+                let dup = match header_id(line) {
+                    Some(id) => self.lines.iter().any(|l| l[..3] == line[..3] && header_id(l) == Some(id)),
+                    None => self.lines.iter().any(|l| l == line),
+                };
+                if !dup { self.lines.push(line.to_string()); }
+            }
+        }
+        Ok(self)
+    }
+
+    /// Full header text; porkchop's `@PG` gets a unique `ID` and follows the last copied program (`PP`).
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("@HD\tVN:1.6\tSO:unknown\n");
+        let programs: Vec<&str> = self.lines.iter().filter(|l| l.starts_with("@PG")).filter_map(|l| header_id(l)).collect();
+        for line in &self.lines { text.push_str(line); text.push('\n'); }
+        let mut id = "porkchop".to_string();
+        let mut n = 0;
+        while programs.contains(&id.as_str()) { n += 1; id = format!("porkchop.{}", n); }
+        text.push_str(&format!("@PG\tID:{}\tPN:porkchop\tVN:{}", id, env!("CARGO_PKG_VERSION")));
+        if let Some(pp) = programs.last() { text.push_str(&format!("\tPP:{}", pp)); }
+        // tabs and newlines would end the field or the line
+        let cl = self.command_line.replace(['\t', '\n'], " ");
+        if !cl.is_empty() { text.push_str(&format!("\tCL:{}", cl)); }
+        text.push('\n');
+        text
+    }
+}
+
+/// `ID:` of a header line.
+fn header_id(line: &str) -> Option<&str> {
+    line.split('\t').find_map(|f| f.strip_prefix("ID:"))
+}
+
+/// One read for a [`Writer`].
+#[derive(Debug, Clone, Copy)]
+pub struct OutRecord<'a> {
+    pub id: &'a str,
+    /// Header text after the id in FASTQ/FASTA; a `CO:Z` tag in SAM/BAM, placed ahead of
+    /// the text of a `CO` in `tags`.
+    pub comment: Option<&'a str>,
+    pub seq: &'a [u8],
+    /// Phred+33, as in FASTQ; `None` writes `*` to SAM/BAM and is an error for FASTQ.
+    pub qual: Option<&'a [u8]>,
+    /// Native aux fields in SAM/BAM; appended to FASTQ/FASTA headers as tab-separated SAM text.
+    pub tags: Option<&'a AuxTags>,
+}
+
+/// Write `rec` as a FASTA or FASTQ entry; aux tags follow the comment, tab-separated.
+fn write_fastx(w: &mut impl std::io::Write, fasta: bool, rec: &OutRecord<'_>) -> Result<()> {
+    let qual = match rec.qual {
+        Some(q) if q.len() == rec.seq.len() => q,
+        Some(_) => bail!("read {}: sequence and quality lengths differ", rec.id),
+        None if fasta || rec.seq.is_empty() => &[],
+        None => bail!("read {} has no base qualities, which FASTQ output needs", rec.id),
+    };
+    w.write_all(if fasta { b">" } else { b"@" })?;
+    w.write_all(rec.id.as_bytes())?;
+    if let Some(c) = rec.comment { write!(w, " {}", c)?; }
+    if let Some(t) = rec.tags.filter(|t| !t.is_empty()) { write!(w, "\t{}", t)?; }
+    w.write_all(b"\n")?;
+    w.write_all(rec.seq)?;
+    if !fasta {
+        w.write_all(b"\n+\n")?;
+        w.write_all(qual)?;
+    }
+    w.write_all(b"\n")?;
+    Ok(())
+}
+
+enum Sink {
+    Text(Box<dyn std::io::Write>),
+    Bgzf(HtsOut<htslib::BGZF>),
+    Hts(HtsOut<htslib::htsFile>, bam::HeaderView),
+}
+
+/// An htslib output handle that is closed by [`HtsOut::close`], which reports a failed final
+/// flush or EOF block; rust-htslib's writers close on drop and discard that result.
+struct HtsOut<T: HtsClose>(*mut T);
+
+trait HtsClose { unsafe fn close(p: *mut Self) -> libc::c_int; }
+impl HtsClose for htslib::BGZF { unsafe fn close(p: *mut Self) -> libc::c_int { unsafe { htslib::bgzf_close(p) } } }
+impl HtsClose for htslib::htsFile { unsafe fn close(p: *mut Self) -> libc::c_int { unsafe { htslib::hts_close(p) } } }
+
+impl<T: HtsClose> HtsOut<T> {
+    fn new(p: *mut T, path: &Path) -> Result<Self> {
+        if p.is_null() { return Err(std::io::Error::last_os_error()).with_context(|| format!("creating {}", path.display())); }
+        Ok(HtsOut(p))
+    }
+
+    fn close(mut self) -> Result<()> {
+        let p = std::mem::replace(&mut self.0, std::ptr::null_mut());
+        // SAFETY: `p` came from htslib's open and is closed exactly once.
+        if unsafe { T::close(p) } != 0 { bail!("closing output: {}", std::io::Error::last_os_error()); }
+        Ok(())
+    }
+}
+
+impl<T: HtsClose> Drop for HtsOut<T> {
+    // only reached on an error path; the write error is already being reported
+    fn drop(&mut self) {
+        if !self.0.is_null() { unsafe { T::close(self.0); } }
+    }
+}
+
+impl std::io::Write for HtsOut<htslib::BGZF> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match unsafe { htslib::bgzf_write(self.0, buf.as_ptr().cast(), buf.len() as _) } {
+            n if n < 0 => Err(std::io::Error::other("BGZF write failed")),
+            n => Ok(n as usize),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if unsafe { htslib::bgzf_flush(self.0) } != 0 { return Err(std::io::Error::other("BGZF flush failed")); }
+        Ok(())
+    }
+}
+
+/// Writes reads as FASTQ, FASTA (plain or BGZF), SAM or unaligned BAM.
+///
+/// BGZF output uses htslib's thread pool, which cannot move between threads, so create the
+/// writer on the thread that writes.
+/// ```no_run
+/// use porkchop::seqio::{OutputFormat, OutputHeader, OutRecord, Writer};
+/// let fmt = OutputFormat::from_path("out.bam").unwrap_or(OutputFormat::FastqGz);
+/// let mut w = Writer::create("out.bam", fmt, &OutputHeader::new("porkchop clean ..."), 4)?;
+/// w.write(&OutRecord { id: "read1", comment: None, seq: b"ACGT", qual: Some(b"IIII"), tags: None })?;
+/// w.finish()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct Writer {
+    format: OutputFormat,
+    sink: Sink,
+    written: u64,
+}
+
+impl Writer {
+    /// Create `path` (`-` for stdout); `threads` compress BGZF and BAM output.
+    pub fn create<P: AsRef<Path>>(path: P, format: OutputFormat, header: &OutputHeader, threads: usize) -> Result<Self> {
+        let p = path.as_ref();
+        let stdout = p.as_os_str() == STDIN_PATH;
+        let ctx = || format!("creating {}", p.display());
+        let c_path = std::ffi::CString::new(if stdout { b"-".to_vec() } else { p.as_os_str().as_encoded_bytes().to_vec() })
+            .with_context(ctx)?;
+        let sink = match format {
+            OutputFormat::Fastq | OutputFormat::Fasta => {
+                let w: Box<dyn std::io::Write> = if stdout {
+                    Box::new(std::io::BufWriter::new(std::io::stdout()))
+                } else {
+                    Box::new(std::io::BufWriter::new(File::create(p).with_context(ctx)?))
+                };
+                Sink::Text(w)
+            }
+            OutputFormat::FastqGz | OutputFormat::FastaGz => {
+                let w = HtsOut::new(unsafe { htslib::bgzf_open(c_path.as_ptr(), c"w".as_ptr()) }, p)?;
+                if threads > 1 && unsafe { htslib::bgzf_mt(w.0, threads as i32, 256) } != 0 {
+                    bail!("{}: cannot start {} compression threads", p.display(), threads);
                 }
+                Sink::Bgzf(w)
             }
-            InputFormat::Bam | InputFormat::Sam => {
-                let mut reader = bam::Reader::from_path(p)?;
-                if n > 1 { let _ = reader.set_threads(n); }
-for result in reader.records() {
-                let rec = result?;
-                    let id = String::from_utf8_lossy(rec.qname()).to_string();
-                    let seq = rec.seq().as_bytes();
-                    let qual = {
-                        let q = rec.qual();
-                        if q.is_empty() { None } else { Some(q.to_vec()) }
-                    };
-                    let naread = NARead { id, seq, qual };
-                    cb(naread);
-                    counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            OutputFormat::Sam | OutputFormat::Bam => {
+                let mode = if format == OutputFormat::Sam { c"w" } else { c"wb" };
+                let w = HtsOut::new(unsafe { htslib::hts_open(c_path.as_ptr(), mode.as_ptr()) }, p)?;
+                if threads > 1 && unsafe { htslib::hts_set_threads(w.0, threads as i32) } != 0 {
+                    bail!("{}: cannot start {} compression threads", p.display(), threads);
+                }
+                let view = bam::HeaderView::from_bytes(header.to_text().as_bytes());
+                if unsafe { htslib::sam_hdr_write(w.0, view.inner_ptr()) } != 0 {
+                    bail!("{}: writing the SAM/BAM header failed", p.display());
+                }
+                Sink::Hts(w, view)
+            }
+        };
+        Ok(Writer { format, sink, written: 0 })
+    }
+
+    pub fn format(&self) -> OutputFormat { self.format }
+
+    /// Reads written so far.
+    pub fn written(&self) -> u64 { self.written }
+
+    pub fn write(&mut self, rec: &OutRecord<'_>) -> Result<()> {
+        let fasta = matches!(self.format, OutputFormat::Fasta | OutputFormat::FastaGz);
+        match &mut self.sink {
+            Sink::Text(w) => write_fastx(w, fasta, rec)?,
+            Sink::Bgzf(w) => write_fastx(w, fasta, rec)?,
+            Sink::Hts(w, view) => {
+                if rec.id.len() > 251 { bail!("read id longer than 251 characters: {}", rec.id); }
+                let qual: Vec<u8> = match rec.qual {
+                    Some(q) if q.len() == rec.seq.len() => q.iter().map(|q| q.saturating_sub(33)).collect(),
+                    Some(_) => bail!("read {}: sequence and quality lengths differ", rec.id),
+                    // htslib's marker for a missing quality string
+                    None => vec![0xff; rec.seq.len()],
+                };
+                let mut out = bam::Record::new();
+                out.set(rec.id.as_bytes(), None, rec.seq, &qual);
+                out.set_flags(0x4);
+                out.set_tid(-1);
+                out.set_pos(-1);
+                out.set_mtid(-1);
+                out.set_mpos(-1);
+                out.set_bin(4680); // reg2bin(-1, 0), the bin of unplaced reads
+                let mut comment = rec.comment;
+                if let Some(tags) = rec.tags {
+                    for (t, v) in &tags.0 {
+                        // the comment goes ahead of an input CO rather than replacing it
+                        if let (b"CO", TagValue::String(co), Some(c)) = (t, v, comment) {
+                            out.push_aux(b"CO", Aux::String(&format!("{} {}", c, co)))?;
+                            comment = None;
+                            continue;
+                        }
+                        out.push_aux(t, v.to_aux()).with_context(|| format!("read {}: writing tag {}{}", rec.id, t[0] as char, t[1] as char))?;
+                    }
+                }
+                if let Some(c) = comment { out.push_aux(b"CO", Aux::String(c))?; }
+                if unsafe { htslib::sam_write1(w.0, view.inner_ptr(), out.inner()) } < 0 {
+                    bail!("writing read {}", rec.id);
                 }
             }
         }
+        self.written += 1;
         Ok(())
-    })?;
+    }
+
+    /// Flush and close the output, writing the BGZF EOF block of compressed formats;
+    /// returns the number of reads written.
+    pub fn finish(self) -> Result<u64> {
+        let Writer { sink, written, .. } = self;
+        match sink {
+            Sink::Text(mut w) => w.flush()?,
+            Sink::Bgzf(w) => w.close()?,
+            Sink::Hts(w, _view) => w.close()?,
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniff_compression_reads_magic_bytes() {
+        assert_eq!(sniff_compression(&[0x1f, 0x8b, 0x08, 0x00, 0, 0, 0, 0, 0, 0xff]), Compression::Gzip);
+        // FEXTRA with a `BC` subfield: BGZF
+        let bgzf = [0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0];
+        assert_eq!(sniff_compression(&bgzf), Compression::Bgzf);
+        // FEXTRA with another subfield is still plain gzip
+        let extra = [0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 6, 0, b'X', b'Y', 2, 0];
+        assert_eq!(sniff_compression(&extra), Compression::Gzip);
+        assert_eq!(sniff_compression(&[0x28, 0xb5, 0x2f, 0xfd, 0]), Compression::Zstd);
+        assert_eq!(sniff_compression(b"BZh91AY&SY"), Compression::Bzip2);
+        assert_eq!(sniff_compression(&[0xfd, b'7', b'z', b'X', b'Z', 0x00, 0]), Compression::Xz);
+        assert_eq!(sniff_compression(b"@read1\nACGT\n"), Compression::None);
+        assert_eq!(sniff_compression(b""), Compression::None);
+        assert_eq!(sniff_compression(&[0x1f, 0x8b]), Compression::None);
+    }
+
+    #[test]
+    fn sniff_content_tells_formats_apart() {
+        assert_eq!(sniff_content(b"BAM\x01\0\0\0\0").unwrap(), Some(InputFormat::Bam));
+        assert_eq!(sniff_content(b"@HD\tVN:1.6\n").unwrap(), Some(InputFormat::Sam));
+        assert_eq!(sniff_content(b"@SQ\tSN:chr1\tLN:10\n").unwrap(), Some(InputFormat::Sam));
+        // `@HD` without a tab is a FASTQ read named HD
+        assert_eq!(sniff_content(b"@HD\nACGT\n+\nIIII\n").unwrap(), Some(InputFormat::Fastq));
+        assert_eq!(sniff_content(b"@read1 runid=x\nACGT\n+\nIIII\n").unwrap(), Some(InputFormat::Fastq));
+        assert_eq!(sniff_content(b"\n\n>read1\nACGT\n").unwrap(), Some(InputFormat::Fasta));
+        let headerless = b"r1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tIIII\n";
+        assert_eq!(sniff_content(headerless).unwrap(), Some(InputFormat::Sam));
+    }
+
+    #[test]
+    fn sniff_content_reports_empty_and_unknown_input() {
+        assert_eq!(sniff_content(b"").unwrap(), None);
+        assert_eq!(sniff_content(b" \n\t\n").unwrap(), None);
+        assert!(sniff_content(b"just some text\n").is_err());
+        assert!(sniff_content(b"a\tb\tc\n").is_err());
+    }
+
+    /// A read for sampling tests: id `r<i>` and `len` bases.
+    struct Key(String, usize);
+
+    impl SampleKey for Key {
+        fn sample_id(&self) -> &[u8] { self.0.as_bytes() }
+        fn sample_len(&self) -> usize { self.1 }
+    }
+
+    fn keys(n: usize) -> impl Iterator<Item = Result<Key>> {
+        (0..n).map(|i| Ok(Key(format!("r{}", i), 100)))
+    }
+
+    fn sampled_ids(sampling: Sampling, n: usize) -> Vec<String> {
+        sampling.apply(keys(n)).map(|r| r.unwrap().0).collect()
+    }
+
+    #[test]
+    fn parse_count_accepts_decimal_suffixes() {
+        assert_eq!(parse_count("12"), Ok(12));
+        assert_eq!(parse_count("5k"), Ok(5_000));
+        assert_eq!(parse_count("2.5M"), Ok(2_500_000));
+        assert_eq!(parse_count("1G"), Ok(1_000_000_000));
+        assert_eq!(parse_count("0"), Ok(0));
+        for bad in ["", "k", "-3", "1.5x", "inf", "NaN"] {
+            assert!(parse_count(bad).is_err(), "{:?} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn sample_mode_from_str() {
+        assert_eq!("all".parse::<SampleMode>(), Ok(SampleMode::All));
+        assert_eq!("fraction:0.25".parse::<SampleMode>(), Ok(SampleMode::Fraction(0.25)));
+        assert_eq!("bernoulli:1".parse::<SampleMode>(), Ok(SampleMode::Fraction(1.0)));
+        assert_eq!("0.1".parse::<SampleMode>(), Ok(SampleMode::Fraction(0.1)));
+        assert_eq!("reservoir:10k".parse::<SampleMode>(), Ok(SampleMode::Reservoir(10_000)));
+        assert_eq!("Head:500".parse::<SampleMode>(), Ok(SampleMode::HeadReads(500)));
+        assert_eq!("bases:1.5G".parse::<SampleMode>(), Ok(SampleMode::HeadBases(1_500_000_000)));
+        for bad in ["fraction:1.5", "fraction:", "reservoir:x", "all:3", "2", "sometimes"] {
+            assert!(bad.parse::<SampleMode>().is_err(), "{:?} should not parse", bad);
+        }
+        // Display round-trips
+        for m in [SampleMode::Fraction(0.5), SampleMode::Reservoir(7), SampleMode::HeadReads(3), SampleMode::HeadBases(9)] {
+            assert_eq!(m.to_string().parse::<SampleMode>(), Ok(m));
+        }
+    }
+
+    #[test]
+    fn fraction_sample_is_fixed_by_seed() {
+        let s = |seed| Sampling { mode: SampleMode::Fraction(0.3), seed };
+        let a = sampled_ids(s(7), 2000);
+        assert_eq!(a, sampled_ids(s(7), 2000));
+        assert_ne!(a, sampled_ids(s(8), 2000));
+        assert!((450..750).contains(&a.len()), "kept {} of 2000", a.len());
+        // the decision depends on the id only, not on the position in the stream
+        let reversed: Vec<String> = s(7).apply((0..2000).rev().map(|i| Ok(Key(format!("r{}", i), 100))))
+            .map(|r| r.unwrap().0).collect();
+        assert_eq!(a.len(), reversed.len());
+        assert!(reversed.iter().all(|id| a.contains(id)));
+    }
+
+    #[test]
+    fn reservoir_returns_exactly_n_in_input_order() {
+        let s = |seed| Sampling { mode: SampleMode::Reservoir(50), seed };
+        let a = sampled_ids(s(1), 1000);
+        assert_eq!(a.len(), 50);
+        let idx: Vec<usize> = a.iter().map(|id| id[1..].parse().unwrap()).collect();
+        assert!(idx.windows(2).all(|w| w[0] < w[1]), "not in input order: {:?}", idx);
+        assert_eq!(a, sampled_ids(s(1), 1000));
+        assert_ne!(a, sampled_ids(s(2), 1000));
+        // fewer reads than requested: all of them
+        assert_eq!(sampled_ids(s(1), 20).len(), 20);
+    }
+
+    #[test]
+    fn head_modes_stop_early() {
+        let head = Sampling { mode: SampleMode::HeadReads(3), seed: 1 };
+        assert_eq!(sampled_ids(head, 10), ["r0", "r1", "r2"]);
+        // the read crossing the limit is kept
+        let bases = Sampling { mode: SampleMode::HeadBases(250), seed: 1 };
+        assert_eq!(sampled_ids(bases, 10), ["r0", "r1", "r2"]);
+        assert_eq!(sampled_ids(Sampling::default(), 4).len(), 4);
+    }
+
+    #[test]
+    fn sampling_passes_errors_through() {
+        let reads = vec![Ok(Key("a".into(), 1)), Err(anyhow!("bad record")), Ok(Key("b".into(), 1))];
+        let out: Vec<Result<Key>> = Sampling::default().apply(reads.into_iter()).collect();
+        assert!(out[1].is_err());
+        let reads = vec![Ok(Key("a".into(), 1)), Err(anyhow!("bad record"))];
+        let mut res = Sampling { mode: SampleMode::Reservoir(5), seed: 1 }.apply(reads.into_iter());
+        assert!(res.next().unwrap().is_err());
+        assert!(res.next().is_none());
+    }
+
+    /// Write `text` to a fresh file under the temp dir and return its path.
+    fn temp_file(name: &str, text: &[u8]) -> PathBuf {
+        let p = std::env::temp_dir().join(format!("porkchop-{}-{}", std::process::id(), name));
+        std::fs::write(&p, text).unwrap();
+        p
+    }
+
+    #[test]
+    fn for_each_ordered_delivers_in_input_order() {
+        let n = 3 * BATCH_SIZE + 17;
+        let mut fq = String::new();
+        for i in 0..n { fq.push_str(&format!("@r{}\n{}\n+\n{}\n", i, "ACGT".repeat(1 + i % 5), "I".repeat(4 * (1 + i % 5)))); }
+        let p = temp_file("ordered.fastq", fq.as_bytes());
+        let mut ids = Vec::new();
+        let (fmt, count) = for_each_ordered(&p, Some(4), |r| {
+            // uneven work so batches finish out of order
+            let i: usize = r.id[1..].parse().unwrap();
+            if i % BATCH_SIZE == 0 && i / BATCH_SIZE % 2 == 0 { std::thread::sleep(std::time::Duration::from_millis(20)); }
+            (r.id, r.seq.len())
+        }, |res| ids.push(res)).unwrap();
+        std::fs::remove_file(&p).unwrap();
+        assert_eq!(fmt, InputFormat::Fastq);
+        assert_eq!(count, n);
+        assert_eq!(ids.len(), n);
+        for (i, (id, len)) in ids.iter().enumerate() {
+            assert_eq!(id, &format!("r{}", i));
+            assert_eq!(*len, 4 * (1 + i % 5));
+        }
+    }
+
+    fn trim_mods(mm: &str, ml: Option<&[u8]>, seq: &str, start: usize, end: usize) -> Option<(String, Option<Vec<u8>>)> {
+        trim_base_mods(mm, ml, seq.as_bytes(), start, end)
+    }
+
+    #[test]
+    fn trim_base_mods_rebases_skip_counts() {
+        // C at 0, 2, 3, 5, 7; calls on the 1st, 3rd and 5th C
+        let seq = "CACCACAC";
+        let (mm, ml) = trim_mods("C+m,0,1,1;", Some(&[10, 20, 30]), seq, 2, 7).unwrap();
+        assert_eq!(mm, "C+m,1;");
+        assert_eq!(ml.unwrap(), [20]);
+        // nothing trimmed: unchanged
+        let (mm, ml) = trim_mods("C+m?,0,1,1;", Some(&[10, 20, 30]), seq, 0, seq.len()).unwrap();
+        assert_eq!(mm, "C+m?,0,1,1;");
+        assert_eq!(ml.unwrap(), [10, 20, 30]);
+        // every call trimmed away: the entry stays, without calls
+        let (mm, ml) = trim_mods("C+m.,0,1,1;", Some(&[10, 20, 30]), seq, 1, 3).unwrap();
+        assert_eq!(mm, "C+m.;");
+        assert_eq!(ml.unwrap(), Vec::<u8>::new());
+        // U is matched as T
+        let (mm, _) = trim_mods("T+17802,0,0;", None, "UAUU", 1, 4).unwrap();
+        assert_eq!(mm, "T+17802,0;");
+    }
+
+    #[test]
+    fn trim_base_mods_keeps_ml_groups_of_multi_code_entries() {
+        // `C+mh` has two ML values per call; a ChEBI code one
+        let seq = "CACCACAC";
+        let (mm, ml) = trim_mods("C+mh,0,1;", Some(&[1, 2, 3, 4]), seq, 1, 8).unwrap();
+        assert_eq!(mm, "C+mh,1;");
+        assert_eq!(ml.unwrap(), [3, 4]);
+        let (mm, ml) = trim_mods("C+76792,0,1;", Some(&[1, 2]), seq, 1, 8).unwrap();
+        assert_eq!(mm, "C+76792,1;");
+        assert_eq!(ml.unwrap(), [2]);
+    }
+
+    #[test]
+    fn trim_base_mods_tracks_ml_across_entries() {
+        // C at 0, 3; A at 1, 2
+        let (mm, ml) = trim_mods("C+m,1;A+a,1;", Some(&[5, 6]), "CAAC", 1, 4).unwrap();
+        assert_eq!(mm, "C+m,0;A+a,1;");
+        assert_eq!(ml.unwrap(), [5, 6]);
+        let (mm, ml) = trim_mods("C+m,1;A+a,1;", Some(&[5, 6]), "CAAC", 3, 4).unwrap();
+        assert_eq!(mm, "C+m,0;A+a;");
+        assert_eq!(ml.unwrap(), [5]);
+    }
+
+    #[test]
+    fn trim_base_mods_minus_strand_counts_seq_bases() {
+        // `G-m` counts the G of SEQ (a C on the other strand): G at 0, 2, 3, 5
+        let (mm, ml) = trim_mods("G-m,1,1;", Some(&[7, 8]), "GAGGTG", 1, 6).unwrap();
+        assert_eq!(mm, "G-m,0,1;");
+        assert_eq!(ml.unwrap(), [7, 8]);
+        let (mm, ml) = trim_mods("G-m,1,1;", Some(&[7, 8]), "GAGGTG", 4, 6).unwrap();
+        assert_eq!(mm, "G-m,0;");
+        assert_eq!(ml.unwrap(), [8]);
+        // `C-m` counts the C of SEQ, not the G: C at 1, 4
+        let (mm, ml) = trim_mods("C-m,0,0;", Some(&[7, 8]), "GCGGCG", 2, 6).unwrap();
+        assert_eq!(mm, "C-m,0;");
+        assert_eq!(ml.unwrap(), [8]);
+    }
+
+    #[test]
+    fn trim_base_mods_rejects_inconsistent_tags() {
+        // more calls than C bases
+        assert!(trim_mods("C+m,0,5;", None, "CACCACAC", 0, 8).is_none());
+        // ML shorter or longer than the calls
+        assert!(trim_mods("C+m,0,1;", Some(&[1]), "CACCACAC", 0, 8).is_none());
+        assert!(trim_mods("C+m,0;", Some(&[1, 2]), "CACCACAC", 0, 8).is_none());
+        // malformed heads and skip counts
+        assert!(trim_mods("Cm,0;", None, "CAC", 0, 3).is_none());
+        assert!(trim_mods("C+m,x;", None, "CAC", 0, 3).is_none());
+    }
+
+    #[test]
+    fn output_format_from_path() {
+        let f = |p: &str| OutputFormat::from_path(p);
+        assert_eq!(f("out.fastq"), Some(OutputFormat::Fastq));
+        assert_eq!(f("dir.v2/out.FQ"), Some(OutputFormat::Fastq));
+        assert_eq!(f("out.fastq.gz"), Some(OutputFormat::FastqGz));
+        assert_eq!(f("out.fq.bgz"), Some(OutputFormat::FastqGz));
+        assert_eq!(f("out.fa"), Some(OutputFormat::Fasta));
+        assert_eq!(f("out.fna.gz"), Some(OutputFormat::FastaGz));
+        assert_eq!(f("out.sam"), Some(OutputFormat::Sam));
+        assert_eq!(f("out.bam"), Some(OutputFormat::Bam));
+        for none in ["out.sam.gz", "out.bam.gz", "out.txt", "out.gz", "out", "-"] {
+            assert_eq!(f(none), None, "{}", none);
+        }
+    }
+
+    #[test]
+    fn output_header_gives_porkchop_a_unique_pg_id() {
+        let plain = OutputHeader::new("porkchop clean -k LSK114\tx").to_text();
+        let lines: Vec<&str> = plain.lines().collect();
+        assert_eq!(lines[0], "@HD\tVN:1.6\tSO:unknown");
+        assert!(lines[1].starts_with("@PG\tID:porkchop\tPN:porkchop\tVN:"));
+        assert!(!lines[1].contains("PP:"));
+        assert!(lines[1].ends_with("\tCL:porkchop clean -k LSK114 x"));
+
+        let mut h = OutputHeader::new("porkchop clean");
+        h.lines = vec![
+            "@RG\tID:run1".to_string(),
+            "@PG\tID:basecaller\tPN:dorado".to_string(),
+            "@PG\tID:porkchop\tPN:porkchop\tPP:basecaller".to_string(),
+            "@PG\tID:porkchop.1\tPN:porkchop\tPP:porkchop".to_string(),
+        ];
+        let text = h.to_text();
+        let last = text.lines().last().unwrap();
+        assert!(last.starts_with("@PG\tID:porkchop.2\t"), "{}", last);
+        assert!(last.contains("\tPP:porkchop.1\t"), "{}", last);
+        assert_eq!(text.lines().count(), 6);
+    }
+
+    #[test]
+    fn output_header_with_inputs_skips_repeated_ids() {
+        let sam = |pg: &str| format!("@HD\tVN:1.6\n@RG\tID:run1\tSM:s\n@PG\tID:basecaller\tPN:{}\n@CO\tnote\n", pg);
+        let a = temp_file("hdr-a.sam", sam("dorado").as_bytes());
+        let b = temp_file("hdr-b.sam", sam("guppy").as_bytes());
+        let inputs: Vec<InputFile> = [&a, &b].iter()
+            .map(|p| InputFile { path: p.to_path_buf(), source: None, secondary: false })
+            .collect();
+        let h = OutputHeader::new("porkchop").with_inputs(&inputs).unwrap();
+        std::fs::remove_file(&a).unwrap();
+        std::fs::remove_file(&b).unwrap();
+        assert_eq!(h.lines, ["@RG\tID:run1\tSM:s", "@PG\tID:basecaller\tPN:dorado", "@CO\tnote"]);
+        assert!(h.to_text().contains("\tPP:basecaller"));
+    }
+
+    #[test]
+    fn writer_round_trips_sam() {
+        let p = std::env::temp_dir().join(format!("porkchop-{}-writer.sam", std::process::id()));
+        let tags = AuxTags(vec![(*b"ch", TagValue::I32(7)), (*b"CO", TagValue::String("upstream".into()))]);
+        let mut w = Writer::create(&p, OutputFormat::Sam, &OutputHeader::new("porkchop test"), 1).unwrap();
+        w.write(&OutRecord { id: "r1", comment: Some("source=x"), seq: b"ACGT", qual: Some(b"I5+!"), tags: Some(&tags) }).unwrap();
+        w.write(&OutRecord { id: "r2", comment: None, seq: b"GG", qual: None, tags: None }).unwrap();
+        assert!(w.write(&OutRecord { id: "r3", comment: None, seq: b"GG", qual: Some(b"I"), tags: None }).is_err());
+        assert_eq!(w.finish().unwrap(), 2);
+        let text = std::fs::read_to_string(&p).unwrap();
+        std::fs::remove_file(&p).unwrap();
+        let records: Vec<&str> = text.lines().filter(|l| !l.starts_with('@')).collect();
+        assert_eq!(records, [
+            "r1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tI5+!\tch:i:7\tCO:Z:source=x upstream",
+            "r2\t4\t*\t0\t0\t*\t*\t0\t0\tGG\t*",
+        ]);
+    }
+
+    #[test]
+    fn sam_and_fastq_qualities_decode_to_phred33() {
+        let sam = temp_file("qual.sam", b"@HD\tVN:1.6\nr1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tI5+!\nr2\t4\t*\t0\t0\t*\t*\t0\t0\tGG\t*\n");
+        let fq = temp_file("qual.fq", b"@r1\nACGT\n+\nI5+!\n");
+        let sam_reads: Vec<NARead> = SeqReader::open(&sam).unwrap().collect::<Result<_>>().unwrap();
+        let fq_reads: Vec<NARead> = SeqReader::open(&fq).unwrap().collect::<Result<_>>().unwrap();
+        std::fs::remove_file(&sam).unwrap();
+        std::fs::remove_file(&fq).unwrap();
+        assert_eq!(sam_reads[0].qual.as_deref(), Some(&b"I5+!"[..]));
+        assert_eq!(sam_reads[0].qual, fq_reads[0].qual);
+        assert_eq!(sam_reads[1].qual, None);
+    }
+
+    #[test]
+    fn fasta_reads_have_comments_and_no_qualities() {
+        let fa = temp_file("reads.fa", b">r1 runid=abc ch=7\nACGT\nTTGA\n>r2\nGG\n");
+        let mut reader = SeqReader::open(&fa).unwrap();
+        assert_eq!(reader.format(), InputFormat::Fasta);
+        let reads: Vec<NARead> = reader.by_ref().collect::<Result<_>>().unwrap();
+        std::fs::remove_file(&fa).unwrap();
+        assert_eq!(reads.len(), 2);
+        assert_eq!((reads[0].id.as_str(), reads[0].seq.as_slice()), ("r1", &b"ACGTTTGA"[..]));
+        assert_eq!(reads[0].comment.as_deref(), Some("runid=abc ch=7"));
+        assert!(reads.iter().all(|r| r.qual.is_none() && r.tags.is_none()));
+        assert_eq!(reads[1].comment, None);
+        assert_eq!(split_header(b"r3 \t x"), ("r3".to_string(), Some("x".to_string())));
+        assert_eq!(split_header(b"r4 "), ("r4".to_string(), None));
+    }
+
+    /// Serve `data` once through a fresh named pipe.
+    fn fifo(name: &str, data: Vec<u8>) -> (PathBuf, std::thread::JoinHandle<()>) {
+        let p = std::env::temp_dir().join(format!("porkchop-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&p);
+        let c = std::ffi::CString::new(p.as_os_str().as_encoded_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c.as_ptr(), 0o600) }, 0);
+        let path = p.clone();
+        (p, std::thread::spawn(move || File::create(path).unwrap().write_all(&data).unwrap()))
+    }
+
+    #[test]
+    fn streams_are_sniffed_and_read_once() {
+        assert!(is_stream("-") && is_stream("/dev/null"));
+        assert!(!is_stream(std::env::temp_dir()));
+        assert!(ensure_stdin_once(&["-", "a.fq"]).is_ok());
+        assert!(ensure_stdin_once(&["-", "a.fq", "-"]).is_err());
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(b"@r1\nACGT\n+\nIIII\n@r2\nGG\n+\n##\n").unwrap();
+        let (p, writer) = fifo("stream.fq.gz", gz.finish().unwrap());
+        assert!(is_stream(&p));
+        let reader = SeqReader::open(&p).unwrap();
+        assert_eq!(reader.format(), InputFormat::Fastq);
+        let ids: Vec<String> = reader.map(|r| r.unwrap().id).collect();
+        writer.join().unwrap();
+        std::fs::remove_file(&p).unwrap();
+        assert_eq!(ids, ["r1", "r2"]);
+
+        let (p, writer) = fifo("stream.sam", b"@HD\tVN:1.6\nr1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tI5+!\tch:i:3\n".to_vec());
+        let reads: Vec<NARead> = SeqReader::open(&p).unwrap().collect::<Result<_>>().unwrap();
+        writer.join().unwrap();
+        std::fs::remove_file(&p).unwrap();
+        assert_eq!(reads.len(), 1);
+        assert_eq!((reads[0].seq.as_slice(), reads[0].qual.as_deref()), (&b"ACGT"[..], Some(&b"I5+!"[..])));
+    }
+
+    #[test]
+    fn directories_expand_recursively_with_globs_and_sources() {
+        let root = std::env::temp_dir().join(format!("porkchop-{}-run", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (f, text) in [
+            ("fastq_pass/barcode01/a.fastq.gz", ""), ("fastq_pass/barcode01/b.fq", ""),
+            ("fastq_pass/barcode02/c.fastq", ""), ("fastq_fail/barcode01/d.fastq", ""),
+            ("bam_pass/e.bam", ""), ("sequencing_summary.txt", ""), ("report.html", ""),
+        ] {
+            let p = root.join(f);
+            std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+            std::fs::write(p, text).unwrap();
+        }
+        let names = |files: &[InputFile]| -> Vec<String> {
+            files.iter().map(|f| f.path.strip_prefix(&root).unwrap().to_string_lossy().into_owned()).collect()
+        };
+
+        let all = expand_inputs(&[&root], &InputOptions::default()).unwrap();
+        assert_eq!(names(&all), ["bam_pass/e.bam", "fastq_fail/barcode01/d.fastq", "fastq_pass/barcode01/a.fastq.gz",
+            "fastq_pass/barcode01/b.fq", "fastq_pass/barcode02/c.fastq"]);
+        assert!(all.iter().all(|f| f.source.is_none() && !f.secondary));
+
+        let opts = InputOptions {
+            include: vec!["fastq_pass/**/*.fastq*".into()],
+            exclude: vec!["c.fastq".into()],
+            by_source: true,
+            secondary: true,
+        };
+        let pass = expand_inputs(&[&root], &opts).unwrap();
+        assert_eq!(names(&pass), ["fastq_pass/barcode01/a.fastq.gz"]);
+        assert_eq!(pass[0].source.as_deref(), Some("barcode01"));
+        assert!(pass[0].secondary);
+
+        // files and `-` are taken as given, in argument order
+        let given = expand_inputs(&[root.join("report.html"), PathBuf::from("-")], &opts).unwrap();
+        assert_eq!(given[1].path, Path::new("-"));
+        assert_eq!(given[1].source.as_deref(), Some("-"));
+
+        let none = InputOptions { include: vec!["*.pod5".into()], ..Default::default() };
+        assert!(expand_inputs(&[&root], &none).unwrap_err().to_string().contains("no input files"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn sam_aux_tags_are_kept_in_file_order() {
+        let sam = temp_file("tags.sam", b"@HD\tVN:1.6\n\
+            r1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tIIII\tch:i:7\tRG:Z:run1_model\tMM:Z:C+m?,0;\tML:B:C,200\tqs:f:12.5\tmv:B:c,5,1,0\n");
+        let fq = temp_file("tags.fq", b"@r1 runid=abc ch=7\nACGT\n+\nIIII\n");
+        let sam_reads: Vec<NARead> = SeqReader::open(&sam).unwrap().collect::<Result<_>>().unwrap();
+        let fq_reads: Vec<NARead> = SeqReader::open(&fq).unwrap().collect::<Result<_>>().unwrap();
+        std::fs::remove_file(&sam).unwrap();
+        std::fs::remove_file(&fq).unwrap();
+
+        let tags = sam_reads[0].tags.as_ref().unwrap();
+        assert_eq!(tags.to_string(), "ch:i:7\tRG:Z:run1_model\tMM:Z:C+m?,0;\tML:B:C,200\tqs:f:12.5\tmv:B:c,5,1,0");
+        assert_eq!(tags.get("ch").and_then(TagValue::as_int), Some(7));
+        assert_eq!(tags.get("ML"), Some(&TagValue::ArrayU8(vec![200])));
+        assert_eq!(tags.without_positional().to_string(), "ch:i:7\tRG:Z:run1_model\tqs:f:12.5");
+        assert_eq!(sam_reads[0].comment, None);
+        assert_eq!(sam_reads[0].meta.channel, Some(7));
+
+        assert_eq!(fq_reads[0].tags, None);
+        assert_eq!(fq_reads[0].comment.as_deref(), Some("runid=abc ch=7"));
+        assert_eq!((fq_reads[0].meta.run_id.as_deref(), fq_reads[0].meta.channel), (Some("abc"), Some(7)));
+    }
+
+    #[test]
+    fn reverse_strand_records_are_restored_and_extra_alignments_skipped() {
+        let sam = temp_file("strand.sam", b"@HD\tVN:1.6\n@SQ\tSN:chr1\tLN:100\n\
+            r1\t16\tchr1\t1\t60\t5M\t*\t0\t0\tAACGN\tABCDE\n\
+            r1\t272\tchr1\t20\t0\t5M\t*\t0\t0\tAACGN\tABCDE\n\
+            r1\t2064\tchr1\t40\t0\t5M\t*\t0\t0\tAACGN\tABCDE\n\
+            r2\t0\tchr1\t1\t60\t3M\t*\t0\t0\tGGA\t*\n");
+        let reads: Vec<NARead> = SeqReader::open(&sam).unwrap().collect::<Result<_>>().unwrap();
+        let all: Vec<NARead> = SeqReader::from_input(&InputFile { path: sam.clone(), source: None, secondary: true })
+            .unwrap().collect::<Result<_>>().unwrap();
+        std::fs::remove_file(&sam).unwrap();
+        assert_eq!(reads.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), ["r1", "r2"]);
+        assert_eq!((reads[0].seq.as_slice(), reads[0].qual.as_deref()), (&b"NCGTT"[..], Some(&b"EDCBA"[..])));
+        assert_eq!((reads[1].seq.as_slice(), reads[1].qual.as_deref()), (&b"GGA"[..], None));
+        assert_eq!(all.len(), 4);
+        assert!(all[..3].iter().all(|r| r.seq == b"NCGTT"));
+    }
+
+    #[test]
+    fn seq_reader_batches_and_read_inputs_chain_files() {
+        let fq = temp_file("pull.fq", b"@r1\nA\n+\nI\n@r2\nC\n+\nI\n@r3\nG\n+\nI\n");
+        let fa = temp_file("pull.fa", b">r4\nT\n");
+        let mut reader = SeqReader::open(&fq).unwrap();
+        let sizes: Vec<usize> = std::iter::from_fn(|| Some(reader.next_batch(2).unwrap().len())).take(3).collect();
+        assert_eq!(sizes, [2, 1, 0]);
 
-    Ok((fmt, counter.load(std::sync::atomic::Ordering::Relaxed)))
+        let missing = fq.with_extension("missing.fq");
+        let files: Vec<InputFile> = [&fq, &fa, &missing].iter()
+            .map(|p| InputFile { path: p.to_path_buf(), source: Some(Arc::from("run")), secondary: false })
+            .collect();
+        let reads: Vec<Result<NARead>> = read_inputs(files).collect();
+        std::fs::remove_file(&fq).unwrap();
+        std::fs::remove_file(&fa).unwrap();
+        let ids: Vec<&str> = reads[..4].iter().map(|r| r.as_ref().unwrap().id.as_str()).collect();
+        assert_eq!(ids, ["r1", "r2", "r3", "r4"]);
+        assert!(reads[..4].iter().all(|r| r.as_ref().unwrap().source.as_deref() == Some("run")));
+        let err = reads[4].as_ref().unwrap_err().to_string();
+        assert!(err.contains("missing.fq"), "{}", err);
+        assert_eq!(reads.len(), 5);
+    }
 }
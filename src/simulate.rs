@@ -0,0 +1,431 @@
+//! Synthetic reads with known truth, built from a kit's read layout.
+//!
+//! Each molecule follows [`Kit::layout`]: adapters, flanks and barcodes are
+//! taken from the kit (IUPAC codes resolved to a random covered base) and the
+//! insert is random sequence or a record drawn from a user FASTA/FASTQ. Reads
+//! are then passed through an ONT-like [`ErrorModel`]. A fraction of reads
+//! has the adapter end truncated, and a fraction is a chimera of two molecules.
+//!
+//! The truth CSV starts with `read_id,label` as read by
+//! [`benchmark::load_truth`](crate::benchmark::load_truth). The label is the read's first
+//! record in [`benchmark::label_records`](crate::benchmark::label_records) order, the set
+//! and order the benchmark scores against. Further columns describe how the read was built.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use flate2::write::GzEncoder;
+use needletail::parse_fastx_file;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::iupac;
+use crate::kit::{BaseChemistry, Kit, LayoutSlot, SeqKind, SequenceRecord};
+
+/// Per-base error rates applied to every simulated read.
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorModel {
+    pub substitution: f64,
+    pub insertion: f64,
+    pub deletion: f64,
+    /// Probability that a homopolymer run (2+ bases) is called one base longer or shorter.
+    pub homopolymer: f64,
+}
+
+impl Default for ErrorModel {
+    /// Roughly R10.4.1 with a high-accuracy basecaller.
+    fn default() -> Self {
+        ErrorModel { substitution: 0.01, insertion: 0.005, deletion: 0.01, homopolymer: 0.05 }
+    }
+}
+
+impl ErrorModel {
+    /// Return a noisy copy of `seq` and its Phred+33 qualities.
+    pub fn apply<R: Rng>(&self, seq: &[u8], rng: &mut R) -> (Vec<u8>, Vec<u8>) {
+        let total = (self.substitution + self.insertion + self.deletion).clamp(1e-4, 0.5);
+        let base_q = (-10.0 * total.log10()).round() as i32;
+        let mut out = Vec::with_capacity(seq.len() + seq.len() / 20);
+        let mut qual = Vec::with_capacity(out.capacity());
+        let push = |out: &mut Vec<u8>, qual: &mut Vec<u8>, b: u8, bad: bool, rng: &mut R| {
+            let q = if bad { rng.gen_range(3..=10) } else { (base_q + rng.gen_range(-3..=3)).clamp(3, 40) };
+            out.push(b);
+            qual.push(q as u8 + 33);
+        };
+        let mut i = 0;
+        while i < seq.len() {
+            let b = seq[i];
+            let mut run = 1;
+            while i + run < seq.len() && seq[i + run] == b { run += 1; }
+            let mut len = run;
+            let mut hp_bad = false;
+            if run >= 2 && rng.gen_bool(self.homopolymer.clamp(0.0, 1.0)) {
+                len = if rng.gen_bool(0.5) { run + 1 } else { run - 1 };
+                hp_bad = true;
+            }
+            for _ in 0..len {
+                let r: f64 = rng.gen();
+                if r < self.deletion {
+                    continue;
+                } else if r < self.deletion + self.substitution {
+                    let alt = loop {
+                        let c = b"ACGT"[rng.gen_range(0..4)];
+                        if c != b { break c; }
+                    };
+                    push(&mut out, &mut qual, alt, true, rng);
+                } else {
+                    push(&mut out, &mut qual, b, hp_bad, rng);
+                }
+                if rng.gen_bool(self.insertion.clamp(0.0, 1.0)) {
+                    push(&mut out, &mut qual, b"ACGT"[rng.gen_range(0..4)], true, rng);
+                }
+            }
+            i += run;
+        }
+        (out, qual)
+    }
+}
+
+/// Options for [`run_simulate`].
+#[derive(Debug, Clone)]
+pub struct SimulateOpts {
+    /// Kit id or expression.
+    pub kit: String,
+    pub n: usize,
+    /// FASTQ output (gzip-compressed if it ends in `.gz`).
+    pub output: PathBuf,
+    /// Truth CSV output.
+    pub truth: PathBuf,
+    /// FASTA/FASTQ to draw inserts from; random sequence if None.
+    pub inserts: Option<PathBuf>,
+    /// Inclusive length range of random inserts.
+    pub insert_len: (usize, usize),
+    pub errors: ErrorModel,
+    /// Fraction of reads with one end's outermost element cut short.
+    pub truncation_rate: f64,
+    /// Fraction of reads made of two molecules.
+    pub chimera_rate: f64,
+    pub seed: u64,
+}
+
+/// Counts of what [`run_simulate`] produced.
+#[derive(Debug, Clone, Default)]
+pub struct SimStats {
+    pub reads: usize,
+    pub reverse: usize,
+    pub truncated: usize,
+    pub chimeras: usize,
+}
+
+/// Default truth path next to a FASTQ output: `sim.fastq.gz` → `sim.truth.csv`.
+pub fn default_truth_path(output: &Path) -> PathBuf {
+    let name = output.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let stem = [".fastq.gz", ".fq.gz", ".fastq", ".fq", ".gz"].iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(&name);
+    output.with_file_name(format!("{}.truth.csv", stem))
+}
+
+struct Molecule {
+    seq: Vec<u8>,
+    label: Option<&'static str>,
+    barcode: Option<&'static str>,
+    insert_len: usize,
+    /// Length of the outermost element before and after the insert (0 if none), for truncation.
+    ends: (usize, usize),
+}
+
+/// Concrete bases for a registry motif (ambiguity codes drawn at random).
+fn concrete<R: Rng>(r: &SequenceRecord, rng: &mut R) -> Vec<u8> {
+    iupac::normalize_motif(r.sequence.as_bytes())
+        .iter()
+        .map(|&b| {
+            let opts = iupac::expand(b);
+            opts[rng.gen_range(0..opts.len())]
+        })
+        .collect()
+}
+
+fn random_insert<R: Rng>(pool: &[Vec<u8>], len: (usize, usize), rng: &mut R) -> Vec<u8> {
+    if !pool.is_empty() {
+        return pool[rng.gen_range(0..pool.len())].clone();
+    }
+    let n = rng.gen_range(len.0..=len.1.max(len.0));
+    (0..n).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect()
+}
+
+/// Record for a layout slot. Unnamed slots with several candidates take the
+/// `nth` one of their kind, so `primer ... rc(primer)` uses two different primers.
+fn record_for(kit: &Kit, slot: LayoutSlot, nth: usize, barcode: Option<&'static SequenceRecord>) -> Option<&'static SequenceRecord> {
+    let all = || kit.adapters_and_primers.iter().chain(kit.barcodes.iter());
+    match slot {
+        LayoutSlot::Insert => None,
+        LayoutSlot::Barcode => barcode,
+        LayoutSlot::Named(n) => all().find(|r| r.name == n),
+        LayoutSlot::Adapter => all().find(|r| r.kind == SeqKind::AdapterTop)
+            .or_else(|| all().find(|r| r.kind == SeqKind::AdapterBottom)),
+        LayoutSlot::Primer | LayoutSlot::Flank => {
+            let cands: Vec<&'static SequenceRecord> = all().filter(|r| slot.accepts(r.name, r.kind)).collect();
+            if cands.is_empty() { None } else { Some(cands[nth % cands.len()]) }
+        }
+    }
+}
+
+fn build_molecule<R: Rng>(kit: &Kit, labels: &[&'static SequenceRecord], barcodes: &[&'static SequenceRecord],
+                          inserts: &[Vec<u8>], insert_len: (usize, usize), rng: &mut R) -> Molecule {
+    let barcode = if barcodes.is_empty() { None } else { Some(barcodes[rng.gen_range(0..barcodes.len())]) };
+    let mut seq = Vec::new();
+    let mut placed: Vec<&'static str> = Vec::new();
+    let mut n_insert = 0;
+    let mut after_insert = false;
+    let (mut head, mut tail) = (None, 0);
+    let mut seen: Vec<LayoutSlot> = Vec::new();
+    for e in kit.layout.elements() {
+        if e.slot == LayoutSlot::Insert {
+            let ins = random_insert(inserts, insert_len, rng);
+            n_insert = ins.len();
+            seq.extend_from_slice(&ins);
+            after_insert = true;
+            continue;
+        }
+        let nth = seen.iter().filter(|s| **s == e.slot).count();
+        seen.push(e.slot);
+        let Some(r) = record_for(kit, e.slot, nth, barcode) else { continue };
+        placed.push(r.name);
+        let mut m = concrete(r, rng);
+        if e.rc { m = iupac::revcomp(&m); }
+        if after_insert {
+            tail = m.len();
+        } else if head.is_none() {
+            head = Some(m.len());
+        }
+        seq.extend_from_slice(&m);
+    }
+    Molecule {
+        seq,
+        label: labels.iter().find(|l| placed.contains(&l.name)).map(|l| l.name),
+        barcode: barcode.map(|b| b.name),
+        insert_len: n_insert,
+        ends: (head.unwrap_or(0), tail),
+    }
+}
+
+/// FASTQ sink of [`run_simulate`]; [`FastqOut::finish`] writes the gzip trailer.
+enum FastqOut {
+    Plain(BufWriter<File>),
+    Gz(BufWriter<GzEncoder<File>>),
+}
+
+impl FastqOut {
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        match self {
+            FastqOut::Plain(w) => w.write_all(buf),
+            FastqOut::Gz(w) => w.write_all(buf),
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            FastqOut::Plain(mut w) => w.flush(),
+            FastqOut::Gz(w) => w.into_inner().map_err(|e| e.into_error())?.finish().map(drop),
+        }
+    }
+}
+
+fn load_inserts(path: &Path) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut reader = parse_fastx_file(path)
+        .with_context(|| format!("cannot read inserts from {}", path.display()))?;
+    let mut v = Vec::new();
+    while let Some(rec) = reader.next() {
+        let rec = rec?;
+        v.push(rec.seq().to_ascii_uppercase());
+    }
+    if v.is_empty() {
+        bail!("no sequences in {}", path.display());
+    }
+    Ok(v)
+}
+
+/// Simulate `opts.n` reads and write the FASTQ and truth CSV.
+pub fn run_simulate(opts: &SimulateOpts) -> anyhow::Result<SimStats> {
+    let kit = crate::kits::resolve_kit_expr(&opts.kit)?;
+    if opts.insert_len.0 == 0 && opts.inserts.is_none() {
+        bail!("minimum insert length must be at least 1");
+    }
+    let inserts = match &opts.inserts {
+        Some(p) => load_inserts(p)?,
+        None => Vec::new(),
+    };
+    let labels = crate::benchmark::label_records(kit);
+    let barcodes: Vec<&'static SequenceRecord> = kit.barcodes.iter().filter(|r| r.kind == SeqKind::Barcode).collect();
+    let mut rng = StdRng::seed_from_u64(opts.seed);
+
+    let file = File::create(&opts.output).with_context(|| format!("cannot create {}", opts.output.display()))?;
+    let mut fq = if opts.output.extension().map(|e| e == "gz").unwrap_or(false) {
+        FastqOut::Gz(BufWriter::new(GzEncoder::new(file, flate2::Compression::default())))
+    } else {
+        FastqOut::Plain(BufWriter::new(file))
+    };
+    let mut truth = csv::Writer::from_path(&opts.truth)
+        .with_context(|| format!("cannot create {}", opts.truth.display()))?;
+    truth.write_record(["read_id", "label", "barcode", "strand", "insert_len", "truncated", "chimera"])?;
+
+    let prefix: String = kit.id.0.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let mut stats = SimStats::default();
+    for i in 0..opts.n {
+        let mut m = build_molecule(kit, &labels, &barcodes, &inserts, opts.insert_len, &mut rng);
+
+        let mut truncated = false;
+        if rng.gen_bool(opts.truncation_rate.clamp(0.0, 1.0)) {
+            let (head, tail) = m.ends;
+            let five = head > 0 && (tail == 0 || rng.gen_bool(0.5));
+            let cut_in = if five { head } else { tail };
+            if cut_in > 1 {
+                let cut = rng.gen_range(1..cut_in);
+                if five { m.seq.drain(..cut); } else { m.seq.truncate(m.seq.len() - cut); }
+                truncated = true;
+            }
+        }
+
+        // Direct RNA is single-stranded: every read is the RNA itself.
+        let reverse = kit.chemistry != BaseChemistry::DirectRNA && rng.gen_bool(0.5);
+        let mut seq = if reverse { iupac::revcomp(&m.seq) } else { m.seq };
+
+        let chimera = rng.gen_bool(opts.chimera_rate.clamp(0.0, 1.0));
+        if chimera {
+            let other = build_molecule(kit, &labels, &barcodes, &inserts, opts.insert_len, &mut rng);
+            if rng.gen_bool(0.5) { seq.extend(iupac::revcomp(&other.seq)); } else { seq.extend(other.seq); }
+        }
+
+        let (read, qual) = opts.errors.apply(&seq, &mut rng);
+        let id = format!("{}_sim_{:08}", prefix, i + 1);
+        fq.write_all(b"@")?;
+        fq.write_all(id.as_bytes())?;
+        fq.write_all(b"\n")?;
+        fq.write_all(&read)?;
+        fq.write_all(b"\n+\n")?;
+        fq.write_all(&qual)?;
+        fq.write_all(b"\n")?;
+        truth.write_record([
+            id.as_str(),
+            m.label.unwrap_or(""),
+            m.barcode.unwrap_or(""),
+            if reverse { "-" } else { "+" },
+            &m.insert_len.to_string(),
+            if truncated { "true" } else { "false" },
+            if chimera { "true" } else { "false" },
+        ])?;
+
+        stats.reads += 1;
+        stats.reverse += reverse as usize;
+        stats.truncated += truncated as usize;
+        stats.chimeras += chimera as usize;
+    }
+    fq.finish().with_context(|| format!("cannot write {}", opts.output.display()))?;
+    truth.flush()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmark::{self, BenchmarkAlgo};
+    use crate::seqio::{InputFile, NARead, SeqReader};
+
+    const EXACT: ErrorModel = ErrorModel { substitution: 0.0, insertion: 0.0, deletion: 0.0, homopolymer: 0.0 };
+
+    fn opts(kit: &str, name: &str, n: usize) -> SimulateOpts {
+        let output = std::env::temp_dir().join(format!("porkchop-{}-{}", std::process::id(), name));
+        SimulateOpts {
+            kit: kit.to_string(),
+            n,
+            truth: default_truth_path(&output),
+            output,
+            inserts: None,
+            insert_len: (200, 400),
+            errors: EXACT,
+            truncation_rate: 0.0,
+            chimera_rate: 0.0,
+            seed: 7,
+        }
+    }
+
+    fn cleanup(o: &SimulateOpts) {
+        let _ = std::fs::remove_file(&o.output);
+        let _ = std::fs::remove_file(&o.truth);
+    }
+
+    #[test]
+    fn exact_reads_round_trip_through_benchmark() {
+        for kit in ["NBD114.24", "RBK114.24", "PCB114.24", "LSK114", "RNA004"] {
+            let o = opts(kit, &format!("{}.fastq.gz", kit), 40);
+            let stats = run_simulate(&o).unwrap();
+            assert_eq!(stats.reads, 40);
+            let truth = benchmark::load_truth(&o.truth).unwrap();
+            assert_eq!(truth.len(), 40);
+            let input = InputFile { path: o.output.clone(), source: None, secondary: false };
+            for algo in [BenchmarkAlgo::Myers, BenchmarkAlgo::Edlib, BenchmarkAlgo::ACMyers] {
+                let (tp, fp, fn_, _, nseq, ..) = benchmark::benchmark_inputs(std::slice::from_ref(&input),
+                    resolve(kit), algo, Some(truth.clone()), None, Default::default(), Some(1), 0).unwrap();
+                assert_eq!((tp, fp, fn_, nseq), (40, 0, 0, 40), "{} {}", kit, algo.as_str());
+            }
+            cleanup(&o);
+        }
+    }
+
+    fn resolve(kit: &str) -> &'static Kit { crate::kits::resolve_kit_expr(kit).unwrap() }
+
+    #[test]
+    fn exact_reads_carry_their_truth_barcode() {
+        let o = opts("NBD114.24", "bc.fastq", 30);
+        let stats = run_simulate(&o).unwrap();
+        let reads: Vec<NARead> = SeqReader::open(&o.output).unwrap().collect::<anyhow::Result<_>>().unwrap();
+        let mut rdr = csv::Reader::from_path(&o.truth).unwrap();
+        let rows: Vec<csv::StringRecord> = rdr.records().map(|r| r.unwrap()).collect();
+        cleanup(&o);
+        assert_eq!(reads.len(), 30);
+        assert_eq!(rows.iter().filter(|r| &r[3] == "-").count(), stats.reverse);
+        let kit = resolve("NBD114.24");
+        for (read, row) in reads.iter().zip(&rows) {
+            assert_eq!(read.id, &row[0]);
+            assert_eq!(read.qual.as_ref().unwrap().len(), read.seq.len());
+            let bc = kit.barcodes.iter().find(|r| r.name == &row[2]).unwrap();
+            let core = iupac::normalize_motif(bc.sequence.as_bytes()).into_owned();
+            let seq = if &row[3] == "-" { iupac::revcomp(&read.seq) } else { read.seq.clone() };
+            // the barcode sits at the 5' end and, reverse-complemented, at the 3' end
+            assert!(seq.windows(core.len()).any(|w| w == core.as_slice()), "{}", read.id);
+            assert!(seq.windows(core.len()).any(|w| w == iupac::revcomp(&core).as_slice()), "{}", read.id);
+            let insert: usize = row[4].parse().unwrap();
+            assert!((200..=400).contains(&insert));
+        }
+    }
+
+    #[test]
+    fn same_seed_same_reads() {
+        let noisy = |name| SimulateOpts { errors: ErrorModel::default(), chimera_rate: 0.2, truncation_rate: 0.2, ..opts("RBK114.24", name, 20) };
+        let (a, b) = (noisy("seed-a.fastq"), noisy("seed-b.fastq"));
+        run_simulate(&a).unwrap();
+        run_simulate(&b).unwrap();
+        let (fa, fb) = (std::fs::read(&a.output).unwrap(), std::fs::read(&b.output).unwrap());
+        let (ta, tb) = (std::fs::read(&a.truth).unwrap(), std::fs::read(&b.truth).unwrap());
+        cleanup(&a);
+        cleanup(&b);
+        assert_eq!(fa, fb);
+        assert_eq!(ta, tb);
+    }
+
+    #[test]
+    fn error_model_and_truth_path() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (read, qual) = EXACT.apply(b"ACGTTTTACG", &mut rng);
+        assert_eq!(read, b"ACGTTTTACG");
+        assert_eq!(qual.len(), read.len());
+        let (read, qual) = ErrorModel { substitution: 0.5, ..EXACT }.apply(&[b'A'; 200], &mut rng);
+        assert_eq!((read.len(), qual.len()), (200, 200));
+        assert!(read.iter().any(|&b| b != b'A'));
+        assert_eq!(default_truth_path(Path::new("out/sim.fastq.gz")), Path::new("out/sim.truth.csv"));
+        assert_eq!(default_truth_path(Path::new("reads.fq")), Path::new("reads.truth.csv"));
+        assert!(run_simulate(&SimulateOpts { insert_len: (0, 10), ..opts("LSK114", "zero.fastq", 1) }).is_err());
+    }
+}
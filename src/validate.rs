@@ -0,0 +1,355 @@
+//! Registry integrity checks.
+//!
+//! [`validate_registry`] audits a set of kits and returns a serialisable
+//! [`RegistryReport`]; [`validate_loaded`] audits the registry built from
+//! [`KITS`](crate::kits::KITS) and the kits of `--registry` files, checking the
+//! loaded list itself before the merge hides repeated ids. Checks:
+//! - duplicate kit ids, custom kits replacing built-in ones, and duplicate record names within a kit
+//! - the same record name bound to different sequences in different kits
+//! - empty sequences and characters outside A/C/G/T/U, IUPAC codes and `mX` modifications
+//! - identical or reverse-complement-identical sequences under different names
+//! - minimum pairwise edit distance within each kit's barcode set (both orientations)
+//! - barcoded kits without any flank record
+//! - read layouts naming records the kit does not contain
+//!
+//! Issues are either [`Severity::Error`] (the registry is wrong) or
+//! [`Severity::Warning`] (legal, but worth a second look, e.g. aliases).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bio::alignment::distance::levenshtein;
+use serde::Serialize;
+
+use crate::iupac;
+use crate::kit::{Kit, LayoutSlot, SeqKind, SequenceRecord};
+
+/// How serious a registry issue is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A single finding.
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Stable machine-readable code, e.g. `illegal_character`.
+    pub code: &'static str,
+    /// Kit the issue belongs to (None for registry-wide findings).
+    pub kit: Option<String>,
+    /// Record names involved.
+    pub names: Vec<String>,
+    pub message: String,
+}
+
+/// Pairwise separation of one kit's barcodes.
+#[derive(Debug, Clone, Serialize)]
+pub struct BarcodeSetStats {
+    pub kit: String,
+    pub barcodes: usize,
+    /// Smallest Levenshtein distance between any two barcodes, comparing each
+    /// pair both as given and with one side reverse-complemented.
+    pub min_distance: Option<usize>,
+    /// A pair achieving `min_distance`.
+    pub closest: Option<(String, String)>,
+}
+
+/// Result of [`validate_registry`].
+#[derive(Debug, Clone, Serialize)]
+pub struct RegistryReport {
+    pub kits: usize,
+    pub sequences: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub issues: Vec<Issue>,
+    pub barcode_sets: Vec<BarcodeSetStats>,
+}
+
+impl RegistryReport {
+    /// Does the report contain any hard errors?
+    pub fn has_errors(&self) -> bool { self.errors > 0 }
+}
+
+fn all_records(k: &Kit) -> impl Iterator<Item = &'static SequenceRecord> {
+    k.adapters_and_primers.iter().chain(k.barcodes.iter())
+}
+
+/// Characters outside the allowed alphabet (modification prefixes `mX` are allowed).
+fn illegal_chars(seq: &str) -> Vec<char> {
+    let b = seq.as_bytes();
+    let mut bad = Vec::new();
+    let mut i = 0;
+    while i < b.len() {
+        let c = b[i];
+        if c == b'm' && b.get(i + 1).map(|n| n.is_ascii_alphabetic()).unwrap_or(false) {
+            i += 1;
+            continue;
+        }
+        let u = c.to_ascii_uppercase();
+        if !(matches!(u, b'A' | b'C' | b'G' | b'T' | b'U') || iupac::is_ambiguous(u)) {
+            bad.push(c as char);
+        }
+        i += 1;
+    }
+    bad
+}
+
+fn issue(severity: Severity, code: &'static str, kit: Option<&str>, names: Vec<String>, message: String) -> Issue {
+    Issue { severity, code, kit: kit.map(|s| s.to_string()), names, message }
+}
+
+/// Audit `kits` and return a report of everything found.
+pub fn validate_registry(kits: &[Kit]) -> RegistryReport {
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut barcode_sets: Vec<BarcodeSetStats> = Vec::new();
+
+    // Kit ids
+    let mut seen_ids: HashSet<&str> = HashSet::new();
+    for k in kits {
+        if !seen_ids.insert(k.id.0) {
+            issues.push(issue(Severity::Error, "duplicate_kit_id", Some(k.id.0), vec![],
+                format!("kit id {} is defined more than once", k.id.0)));
+        }
+    }
+
+    // Unique (name, sequence) pairs across the registry, and where they are used.
+    let mut records: BTreeMap<(&str, &str), Vec<&str>> = BTreeMap::new();
+
+    for k in kits {
+        let kit = Some(k.id.0);
+
+        // Names within the kit
+        let mut by_name: HashMap<&str, &str> = HashMap::new();
+        for r in all_records(k) {
+            match by_name.get(r.name) {
+                Some(prev) if *prev != r.sequence => issues.push(issue(Severity::Error, "duplicate_name", kit,
+                    vec![r.name.to_string()],
+                    format!("{} appears twice in kit {} with different sequences", r.name, k.id.0))),
+                Some(_) => issues.push(issue(Severity::Warning, "duplicate_name", kit,
+                    vec![r.name.to_string()],
+                    format!("{} is listed twice in kit {}", r.name, k.id.0))),
+                None => { by_name.insert(r.name, r.sequence); }
+            }
+            records.entry((r.name, r.sequence)).or_default().push(k.id.0);
+        }
+
+        // Layout references
+        for e in k.layout.elements() {
+            if let LayoutSlot::Named(n) = e.slot {
+                if !all_records(k).any(|r| r.name == n) {
+                    issues.push(issue(Severity::Error, "layout_unknown_record", kit, vec![n.to_string()],
+                        format!("layout of kit {} names {}, which is not one of its records", k.id.0, n)));
+                }
+            }
+        }
+
+        // Barcode separation
+        let bcs: Vec<(&str, Vec<u8>)> = k.barcodes.iter()
+            .filter(|r| r.kind == SeqKind::Barcode)
+            .map(|r| (r.name, iupac::normalize_motif(r.sequence.as_bytes()).into_owned()))
+            .collect();
+        if !bcs.is_empty() {
+            let mut best: Option<(usize, usize, usize)> = None;
+            let rcs: Vec<Vec<u8>> = bcs.iter().map(|(_, s)| iupac::revcomp(s)).collect();
+            for i in 0..bcs.len() {
+                for j in (i + 1)..bcs.len() {
+                    let d = levenshtein(&bcs[i].1, &bcs[j].1)
+                        .min(levenshtein(&rcs[i], &bcs[j].1))
+                        .min(levenshtein(&bcs[i].1, &rcs[j])) as usize;
+                    if best.map(|b| d < b.0).unwrap_or(true) { best = Some((d, i, j)); }
+                }
+            }
+            if let Some((0, i, j)) = best {
+                issues.push(issue(Severity::Error, "identical_barcodes", kit,
+                    vec![bcs[i].0.to_string(), bcs[j].0.to_string()],
+                    format!("barcodes {} and {} in kit {} cannot be told apart", bcs[i].0, bcs[j].0, k.id.0)));
+            }
+            barcode_sets.push(BarcodeSetStats {
+                kit: k.id.0.to_string(),
+                barcodes: bcs.len(),
+                min_distance: best.map(|b| b.0),
+                closest: best.map(|(_, i, j)| (bcs[i].0.to_string(), bcs[j].0.to_string())),
+            });
+
+            let has_flank = all_records(k).any(|r| r.kind == SeqKind::Flank);
+            if !has_flank {
+                issues.push(issue(Severity::Warning, "barcodes_without_flanks", kit, vec![],
+                    format!("kit {} has {} barcodes but no flank records", k.id.0, bcs.len())));
+            }
+        }
+    }
+
+    // Per-record content
+    for ((name, seq), used_in) in &records {
+        let kit = if used_in.len() == 1 { Some(used_in[0]) } else { None };
+        if seq.trim().is_empty() {
+            issues.push(issue(Severity::Error, "empty_sequence", kit, vec![name.to_string()],
+                format!("{} has an empty sequence", name)));
+            continue;
+        }
+        let bad = illegal_chars(seq);
+        if !bad.is_empty() {
+            let mut uniq: Vec<char> = bad.clone();
+            uniq.sort_unstable();
+            uniq.dedup();
+            issues.push(issue(Severity::Error, "illegal_character", kit, vec![name.to_string()],
+                format!("{} contains illegal character(s) {:?}", name, uniq.iter().collect::<String>())));
+        }
+    }
+
+    // Same name, different sequence in different kits
+    let mut seqs_by_name: BTreeMap<&str, Vec<(&str, &Vec<&str>)>> = BTreeMap::new();
+    for ((name, seq), used_in) in &records {
+        seqs_by_name.entry(name).or_default().push((seq, used_in));
+    }
+    for (name, variants) in &seqs_by_name {
+        if variants.len() > 1 {
+            let kits: Vec<String> = variants.iter().map(|(_, u)| u.join(",")).collect();
+            issues.push(issue(Severity::Warning, "conflicting_name", None, vec![name.to_string()],
+                format!("{} has {} different sequences across kits ({})", name, variants.len(), kits.join(" | "))));
+        }
+    }
+
+    // Identical / reverse-complement-identical sequences under different names
+    let mut names_by_seq: BTreeMap<Vec<u8>, Vec<&str>> = BTreeMap::new();
+    for (name, seq) in records.keys() {
+        let c = iupac::normalize_motif(seq.as_bytes()).into_owned();
+        if c.is_empty() { continue; }
+        let names = names_by_seq.entry(c).or_default();
+        if !names.contains(name) { names.push(name); }
+    }
+    let mut reported: HashSet<(Vec<u8>, Vec<u8>)> = HashSet::new();
+    for (seq, names) in &names_by_seq {
+        if names.len() > 1 {
+            issues.push(issue(Severity::Warning, "identical_sequence", None,
+                names.iter().map(|s| s.to_string()).collect(),
+                format!("{} share the same sequence", names.join(", "))));
+        }
+        let rc = iupac::revcomp(seq);
+        if rc == *seq { continue; }
+        if let Some(rc_names) = names_by_seq.get(&rc) {
+            let key = if *seq < rc { (seq.clone(), rc.clone()) } else { (rc.clone(), seq.clone()) };
+            if reported.insert(key) {
+                let all: Vec<String> = names.iter().chain(rc_names.iter()).map(|s| s.to_string()).collect();
+                issues.push(issue(Severity::Warning, "revcomp_identical_sequence", None, all,
+                    format!("{} is the reverse complement of {}", names.join(", "), rc_names.join(", "))));
+            }
+        }
+    }
+
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    let warnings = issues.len() - errors;
+    RegistryReport {
+        kits: kits.len(),
+        sequences: records.len(),
+        errors,
+        warnings,
+        issues,
+        barcode_sets,
+    }
+}
+
+/// Audit the registry made of `builtin` and the custom kits `loaded` from registry files, in
+/// load order. The merge keeps one kit per id, so repeated ids are checked on `loaded` itself:
+/// an id defined twice in the files is an error, one replacing a built-in kit a warning.
+pub fn validate_loaded(builtin: &[Kit], loaded: &[Kit]) -> RegistryReport {
+    let mut report = validate_registry(&crate::kits::merge_registry(builtin, loaded));
+    let mut ids: Vec<Issue> = Vec::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    for k in loaded {
+        if !seen.insert(k.id.0) {
+            ids.push(issue(Severity::Error, "duplicate_kit_id", Some(k.id.0), vec![],
+                format!("kit id {} is defined more than once in the registry files", k.id.0)));
+        } else if builtin.iter().any(|b| b.id == k.id) {
+            ids.push(issue(Severity::Warning, "replaces_builtin_kit", Some(k.id.0), vec![],
+                format!("custom kit {} replaces the built-in kit of that id", k.id.0)));
+        }
+    }
+    report.errors += ids.iter().filter(|i| i.severity == Severity::Error).count();
+    report.warnings += ids.iter().filter(|i| i.severity == Severity::Warning).count();
+    ids.append(&mut report.issues);
+    report.issues = ids;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kit::{KitId, Provenance, ReadLayout};
+    use crate::kits::KITS;
+
+    fn rec(name: &'static str, kind: SeqKind, sequence: &'static str) -> SequenceRecord {
+        SequenceRecord { name, kind, sequence, provenance: Provenance { source: "test", appendix: None, notes: None } }
+    }
+
+    fn kit(id: &'static str, barcodes: Vec<SequenceRecord>, layout: &str) -> Kit {
+        Kit {
+            id: KitId(id),
+            barcodes: Box::leak(barcodes.into_boxed_slice()),
+            layout: layout.parse::<ReadLayout>().unwrap(),
+            ..*KITS.iter().find(|k| k.id.0 == "RBK114.24").unwrap()
+        }
+    }
+
+    fn codes(report: &RegistryReport, severity: Severity) -> Vec<&'static str> {
+        report.issues.iter().filter(|i| i.severity == severity).map(|i| i.code).collect()
+    }
+
+    #[test]
+    fn builtin_registry_has_no_errors() {
+        let report = validate_registry(KITS);
+        assert!(!report.has_errors(), "{:?}", codes(&report, Severity::Error));
+        assert_eq!(report.kits, KITS.len());
+    }
+
+    #[test]
+    fn barcode_distance_compares_both_orientations() {
+        let k = kit("T-BC", vec![
+            rec("T01", SeqKind::Barcode, "AAAACCCCGG"),
+            rec("T02", SeqKind::Barcode, "CCGGGGTTTT"),
+            rec("T03", SeqKind::Barcode, "ACGTTGCAAC"),
+        ], "adapter > barcode > insert");
+        let report = validate_registry(&[k]);
+        let set = &report.barcode_sets[0];
+        assert_eq!(set.barcodes, 3);
+        // T02 is the reverse complement of T01
+        assert_eq!(set.min_distance, Some(0));
+        assert_eq!(set.closest, Some(("T01".to_string(), "T02".to_string())));
+        assert!(codes(&report, Severity::Warning).contains(&"revcomp_identical_sequence"));
+    }
+
+    #[test]
+    fn record_errors_are_reported() {
+        let k = kit("T-BAD", vec![
+            rec("B1", SeqKind::Barcode, "ACGTXZ"),
+            rec("B2", SeqKind::Barcode, ""),
+            rec("B3", SeqKind::Barcode, "ACGTAC"),
+            rec("B3", SeqKind::Barcode, "TTGCAA"),
+            rec("B4", SeqKind::Barcode, "acmgtU"),
+        ], "adapter > missing_flank > barcode > insert");
+        let report = validate_registry(&[k]);
+        let errors = codes(&report, Severity::Error);
+        for code in ["illegal_character", "empty_sequence", "duplicate_name", "layout_unknown_record"] {
+            assert!(errors.contains(&code), "{} missing from {:?}", code, errors);
+        }
+        let illegal: Vec<&Issue> = report.issues.iter().filter(|i| i.code == "illegal_character").collect();
+        assert_eq!(illegal.len(), 1);
+        assert_eq!(illegal[0].names, ["B1"]);
+        assert_eq!(report.errors, errors.len());
+    }
+
+    #[test]
+    fn loaded_ids_are_checked_before_the_merge() {
+        let a = kit("T-DUP", vec![rec("D1", SeqKind::Barcode, "ACGTACGTAA")], "adapter > barcode > insert");
+        let b = kit("T-DUP", vec![rec("D1", SeqKind::Barcode, "ACGTACGTAA")], "adapter > barcode > insert");
+        let lsk = *KITS.iter().find(|k| k.id.0 == "LSK114").unwrap();
+        let report = validate_loaded(KITS, &[a, b, lsk]);
+        assert_eq!(codes(&report, Severity::Error), ["duplicate_kit_id"]);
+        assert!(codes(&report, Severity::Warning).contains(&"replaces_builtin_kit"));
+        assert_eq!(report.kits, KITS.len() + 1);
+        // the same id only in the built-in list is fine
+        assert!(!validate_loaded(KITS, &[]).has_errors());
+    }
+}
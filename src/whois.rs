@@ -0,0 +1,127 @@
+//! Reverse lookup: which registry records (and kits) contain a sequence.
+//!
+//! [`whois`] compares a query against every adapter, primer, flank and barcode
+//! of the active registry, in both orientations. The shorter of the two is
+//! searched inside the longer one, so a read fragment finds the adapter it came
+//! from and a full adapter finds the barcodes embedded in it. IUPAC codes on
+//! either side match every base they cover.
+
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+
+use crate::edwrap;
+use crate::iupac;
+use crate::kit::SequenceRecord;
+use crate::list_supported_kits;
+use crate::screen::collect_all_sequences;
+
+/// One registry record matching the query.
+#[derive(Debug, Clone)]
+pub struct WhoisHit {
+    pub record: SequenceRecord,
+    /// The query matched the record's reverse complement.
+    pub reverse: bool,
+    /// Edit distance of the best alignment.
+    pub distance: usize,
+    /// Every kit that includes this record (same name and sequence).
+    pub kits: Vec<&'static str>,
+}
+
+/// Best edit distance of the shorter sequence inside the longer one.
+fn infix_distance(a: &[u8], b: &[u8], max_dist: usize) -> Option<usize> {
+    let (pat, text) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if pat.len() <= max_dist { return None; }
+    edwrap::locate_all(pat, text, max_dist as i32)
+        .first()
+        .map(|h| h.edits as usize)
+}
+
+/// Search the registry for `query` in both orientations, allowing `max_dist` edits.
+///
+/// Hits are sorted by distance, then record name. Records that are shorter
+/// than or equal to `max_dist` are skipped, since they would match anything.
+pub fn whois(query: &str, max_dist: usize) -> anyhow::Result<Vec<WhoisHit>> {
+    let q: Vec<u8> = query.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if q.is_empty() {
+        bail!("empty query sequence");
+    }
+    if let Some(&c) = q.iter().find(|&&b| {
+        let u = b.to_ascii_uppercase();
+        !(matches!(u, b'A' | b'C' | b'G' | b'T' | b'U') || iupac::is_ambiguous(u))
+    }) {
+        bail!("query contains illegal character {:?}", c as char);
+    }
+    let q = iupac::normalize_motif(&q).into_owned();
+    let q_rc = iupac::revcomp(&q);
+
+    // Unique (name, sequence) records, with the kits that include them.
+    let mut kits_of: BTreeMap<(&str, &str), Vec<&'static str>> = BTreeMap::new();
+    for k in list_supported_kits() {
+        for r in k.adapters_and_primers.iter().chain(k.barcodes.iter()) {
+            let kits = kits_of.entry((r.name, r.sequence)).or_default();
+            if !kits.contains(&k.id.0) { kits.push(k.id.0); }
+        }
+    }
+
+    let mut hits: Vec<WhoisHit> = Vec::new();
+    for r in collect_all_sequences() {
+        let Some(kits) = kits_of.remove(&(r.name, r.sequence)) else { continue };
+        let seq = iupac::normalize_motif(r.sequence.as_bytes());
+        if seq.is_empty() { continue; }
+        let fwd = infix_distance(&q, &seq, max_dist);
+        let rev = infix_distance(&q_rc, &seq, max_dist);
+        let best = match (fwd, rev) {
+            (Some(f), Some(r)) if r < f => Some((r, true)),
+            (Some(f), _) => Some((f, false)),
+            (None, Some(r)) => Some((r, true)),
+            (None, None) => None,
+        };
+        if let Some((distance, reverse)) = best {
+            hits.push(WhoisHit { record: r, reverse, distance, kits });
+        }
+    }
+    hits.sort_by(|a, b| a.distance.cmp(&b.distance).then_with(|| a.record.name.cmp(b.record.name)));
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(hits: &[WhoisHit]) -> Vec<&'static str> { hits.iter().map(|h| h.record.name).collect() }
+
+    #[test]
+    fn finds_a_flank_in_both_orientations_with_its_kits() {
+        let hits = whois("ATTGCTAAGGTTAA", 0).unwrap();
+        let fwd = hits.iter().find(|h| h.record.name == "NB_flank_fwd").unwrap();
+        assert!(!fwd.reverse);
+        assert_eq!(fwd.distance, 0);
+        assert!(fwd.kits.contains(&"NBD114.24") && fwd.kits.contains(&"EXP-NBD114"));
+        let rev = hits.iter().find(|h| h.record.name == "NB_flank_rev3").unwrap();
+        assert!(rev.reverse);
+        // the reverse complement finds the same records, strands swapped
+        let back = whois(std::str::from_utf8(&iupac::revcomp(b"ATTGCTAAGGTTAA")).unwrap(), 0).unwrap();
+        assert!(back.iter().any(|h| h.record.name == "NB_flank_fwd" && h.reverse));
+    }
+
+    #[test]
+    fn query_is_normalised_and_edits_are_allowed() {
+        // lowercase, U and whitespace are accepted; one substitution needs max_dist 1
+        assert!(names(&whois("auu gcu aag guu aa", 0).unwrap()).contains(&"NB_flank_fwd"));
+        assert!(!names(&whois("ATTGCTAAGCTTAA", 0).unwrap()).contains(&"NB_flank_fwd"));
+        let hits = whois("ATTGCTAAGCTTAA", 1).unwrap();
+        let h = hits.iter().find(|h| h.record.name == "NB_flank_fwd").unwrap();
+        assert_eq!(h.distance, 1);
+        // sorted by distance
+        assert!(hits.windows(2).all(|w| w[0].distance <= w[1].distance));
+    }
+
+    #[test]
+    fn rejects_empty_and_illegal_queries() {
+        assert!(whois("  ", 0).unwrap_err().to_string().contains("empty"));
+        assert!(whois("ACGT-ACGT", 0).unwrap_err().to_string().contains("illegal character '-'"));
+        // ambiguity codes are legal
+        assert!(whois("ATTGCTAAGGTTAN", 0).is_ok());
+    }
+}